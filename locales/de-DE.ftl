@@ -10,45 +10,128 @@ save-command-about = Aktuelle Änderungen als WIP-Branch speichern
 list-command-about = Alle WIP-Branches auflisten
 delete-command-about = Einen WIP-Branch löschen
 restore-command-about = Änderungen aus einem WIP-Branch wiederherstellen
+show-command-about = Inhalt eines WIP-Branches anzeigen, ohne ihn wiederherzustellen
+show-command-long-about = Die gespeicherten Metadaten und Dateien eines WIP-Branches sowie seine Änderungen gegenüber dem Commit anzeigen, auf dem er gespeichert wurde
+log-command-about = Die Snapshots eines WIP-Branches auflisten
+log-command-long-about = Die mit save --update zu einem WIP-Branch hinzugefügten Snapshots auflisten, die neuesten zuerst, mit den Namen, unter denen sie wiederhergestellt oder angezeigt werden können
+diff-command-about = Einen WIP-Branch mit dem Arbeitsverzeichnis oder einem anderen WIP-Branch vergleichen
+diff-command-long-about = Die auf beiden Seiten erfassten Dateien mit ihrem Zustand auflisten, gefolgt von den Änderungen zwischen einem WIP-Branch und dem Arbeitsverzeichnis, einschließlich nicht verfolgter Dateien, oder zwischen zwei WIP-Branches
+sync-command-about = Ihre WIP-Branches mit dem Remote abgleichen
+sync-command-long-about = Ihre WIP-Branches vom Remote abrufen, nur lokal gespeicherte pushen, lokale Kopien von auf dem Remote gelöschten löschen und die melden, die nur auf dem Remote existieren oder davon abweichen
+prune-command-about = Nicht mehr benötigte WIP-Branches löschen
+prune-command-long-about = WIP-Branches löschen, die älter als ein bestimmtes Alter sind, deren Quell-Branch gemergt oder gelöscht wurde oder die über die neuesten hinausgehen, für Sie oder das ganze Team. Mit --dry-run sehen, was gelöscht würde
+watch-command-about = Snapshots der Änderungen während der Arbeit anlegen
+watch-command-long-about = Alle paar Minuten, oder sobald sich die Dateien nicht mehr ändern, einen Snapshot des Arbeitsverzeichnisses in einem eigenen lokalen WIP-Branch speichern. Unveränderte Arbeitsverzeichnisse werden übersprungen, nur die neuesten Snapshots werden behalten, und HEAD und der Index werden nie verändert. Mit Strg-C beenden, oder mit --background im Hintergrund ausführen
+recover-command-about = Unterbrochenes Speichern oder Wiederherstellen rückgängig machen
+recover-command-long-about = Die Schritte eines unterbrochenen Speicherns oder Wiederherstellens zurücknehmen und übrig gebliebene temporäre Branches entfernen
 
 # Operation messages
 saving-wip = Speichere WIP-Änderungen...
 created-branch = Branch '{ $name }' erstellt
-staged-all-changes = Alle Änderungen gestaged
+recorded-changes = Gestagte und nicht gestagte Änderungen erfasst
 committed-changes = Änderungen committed
 pushed-changes = Änderungen zum Remote gepusht
 skipped-push-no-remote = Kein Remote-Repository konfiguriert, überspringe Push
-switched-back = Zurück zum Branch '{ $name }' gewechselt
+cleared-changes = Gespeicherte Änderungen aus dem Arbeitsverzeichnis entfernt
+kept-changes = Gespeicherte Änderungen im Arbeitsverzeichnis belassen
 delete-complete = WIP-Branch erfolgreich gelöscht
 no-wip-branches = Keine WIP-Branches für Benutzer '{ $username }' gefunden
 restoring-wip = Stelle Änderungen von Branch '{ $name }' wieder her...
 checked-out-branch = Branch '{ $name }' ausgecheckt
+checked-out-commit = Commit '{ $commit }' ausgecheckt
 unstaged-changes = Änderungen unstaged
 stashed-changes = Änderungen gestashed
 applied-stash = Gestashte Änderungen angewendet
 recreated-file-states = Ursprüngliche Dateizustände wiederhergestellt
 deleted-local-branch = Lokaler Branch '{ $name }' gelöscht
+kept-wip-branch = WIP-Branch '{ $name }' behalten
+restore-conflicts = Der Branch hat sich seit dem Speichern verändert, Dateien mit Konflikten ({ $count }):
+kept-wip-branch-conflicts = WIP-Branch '{ $name }' behalten. Löse die Konflikte und lösche ihn dann mit git wippy delete
 deleted-remote-branch = Remote Branch '{ $name }' gelöscht
 restore-complete = Änderungen von '{ $name }' erfolgreich wiederhergestellt
 operation-cancelled = Operation abgebrochen
 branch-not-found = Branch '{ $name }' nicht gefunden
 branch-name = { $name }
 wip-branch-created = WIP-Branch '{ $name }' erstellt
+updated-branch = Snapshot zu Branch '{ $name }' hinzugefügt
+wip-snapshot-added = Snapshot zu WIP-Branch '{ $name }' hinzugefügt
+restore-older-snapshot = Ein älterer Snapshot wird wiederhergestellt, daher wird '{ $name }' mit seinen neueren Snapshots behalten
+log-heading = Snapshots von '{ $name }'
+log-column-snapshot = Snapshot
+log-column-commit = Commit
 wip-branch-deleted = WIP-Branch '{ $name }' gelöscht { $remote ->
     [true] (lokal und remote)
     *[false] (nur lokal)
 }
+wip-message = Nachricht: { $message }
+fetched-wip-branch = Branch '{ $name }' von Remote '{ $remote }' abgerufen
+wip-branch-deleted-remote = WIP-Branch '{ $name }' von Remote '{ $remote }' gelöscht
+wip-branch-remote-only = '{ $name }' existiert nur auf einem Remote und wurde behalten
+show-heading = WIP-Branch '{ $name }'
+show-source = Quelle: { $source }
+show-saved = Gespeichert: { $timestamp }
+show-staged-files = Vorgemerkte Dateien:
+show-changed-files = Geänderte Dateien:
+show-untracked-files = Nicht verfolgte Dateien:
+diff-heading = Vergleiche '{ $from }' mit { $to }
+diff-working-tree = dem Arbeitsverzeichnis
+diff-no-files = Keine Seite hat geänderte Dateien erfasst
+diff-file-column = Datei
+diff-state-staged = vorgemerkt
+diff-state-changed = geändert
+diff-state-untracked = nicht verfolgt
+sync-fetching = WIP-Branches werden von '{ $remote }' abgerufen...
+sync-branch-column = Branch
+sync-status-column = Status
+sync-up-to-date = aktuell
+sync-pushed = gepusht
+sync-remote-only = nur auf dem Remote
+sync-pruned = gelöscht, auf dem Remote wurde er gelöscht
+sync-diverged = weicht vom Remote ab, unverändert gelassen
+prune-user-column = Benutzer
+prune-reason-column = Grund
+prune-older-than = älter als die Grenze
+prune-source-merged = Quell-Branch gemergt
+prune-source-deleted = Quell-Branch gelöscht
+prune-keep-last = nicht unter den neuesten zu behaltenden
+prune-nothing = Keine WIP-Branches zu bereinigen
+prune-dry-run = Probelauf, nichts wurde gelöscht
+watch-started = Das Arbeitsverzeichnis wird beobachtet, alle { $interval } wird nach Änderungen gesucht
+watch-started-quiet-period = Das Arbeitsverzeichnis wird beobachtet, ein Snapshot wird angelegt, sobald sich die Dateien { $quiet_period } lang nicht ändern
+watch-snapshot = Snapshot in '{ $name }' gespeichert
+watch-dropped-snapshots = Die ältesten Snapshots von '{ $name }' wurden verworfen, die neuesten { $keep } bleiben erhalten
+watch-failed = Snapshot konnte nicht angelegt werden, es wird später erneut versucht: { $error }
+watch-stopped = Beobachtung des Arbeitsverzeichnisses beendet
+watch-background = Das Arbeitsverzeichnis wird im Hintergrund von Prozess { $pid } beobachtet, Protokoll in { $log }
+branch-with-message = { $name } - { $message }
+no-wip-branches-all = Keine WIP-Branches gefunden
+wip-user-heading = { $username }:
+location-local = lokal
+list-column-branch = Branch
+list-column-source = Quelle
+list-column-age = Alter
+list-column-staged = Gestaged
+list-column-changed = Geändert
+list-column-untracked = Unversioniert
+list-column-location = Ort
+list-column-message = Nachricht
 
 # Dialog prompts
 delete-branch-prompt = Diesen Branch löschen?
 delete-all-prompt = Alle { $count } WIP-Branches löschen?
+delete-branches-prompt = Diese { $count } WIP-Branches löschen?
 delete-remote-prompt = Auch { $count } Remote-Branches löschen?
 select-branches-to-delete = Branches zum Löschen auswählen:
 selection-instructions = Leertaste zum Auswählen/Abwählen, Enter zum Bestätigen
 no-branches-selected = Keine Branches ausgewählt
 selected-branches = Ausgewählte Branches:
+restore-target-prompt = Branch '{ $name }' existiert nicht mehr. Wo sollen die Änderungen wiederhergestellt werden?
+restore-target-recreate = '{ $name }' bei { $commit } neu erstellen
+restore-target-create = '{ $name }' hier erstellen
+restore-target-current = { $name } (aktuell)
 found-wip-branch = WIP-Branch gefunden:
 found-wip-branches = WIP-Branches gefunden:
+found-wip-branches-all = WIP-Branches aller Benutzer:
 
 # Error messages
 remote-delete-failed = Fehler beim Löschen des Remote-Branch '{ $name }': { $error }
@@ -56,15 +139,61 @@ remote-delete-failed = Fehler beim Löschen des Remote-Branch '{ $name }': { $er
 # Help messages
 save-local-help = Änderungen nicht zum Remote-Repository pushen
 save-username-help = Benutzerdefinierten Benutzernamen angeben
-save-datetime-help = Benutzerdefiniertes Datum und Uhrzeit angeben
-delete-branch-help = Name des zu löschenden Branches
+save-datetime-help = Benutzerdefiniertes Datum und Uhrzeit angeben (YYYY-MM-DD-HH-mm-SS)
+save-message-help = Beschreibung der gespeicherten Arbeit
+save-update-help = Einen Snapshot zu Ihrem neuesten von diesem Branch gespeicherten WIP oder zum angegebenen WIP-Branch hinzufügen, statt einen neuen zu erstellen
+save-keep-help = Änderungen nach dem Speichern im Arbeitsverzeichnis behalten
+save-remote-help = Remote, auf das statt wippy.remote oder origin gepusht wird
+list-all-help = WIP-Branches aller Benutzer anzeigen
+list-remote-help = Nur Remote-Branches auf diesem Remote anzeigen
+delete-branch-help = Namen der zu löschenden Branches
 delete-all-help = Alle WIP-Branches löschen
 delete-force-help = Bestätigung überspringen
 delete-local-help = Nur lokale Branches löschen
+delete-remote-help = Remote, von dem statt wippy.remote oder origin gelöscht wird
 restore-branch-help = Name des wiederherzustellenden Branches
+restore-onto-help = Auf diesen Branch statt auf den ursprünglichen Branch wiederherstellen
 restore-force-help = Bestätigung überspringen
 restore-autostash-help = Lokale Änderungen automatisch stashen und wieder anwenden
+restore-keep-help = Den WIP-Branch lokal und auf dem Remote behalten, wie git stash apply
+restore-keep-remote-help = Nur den lokalen WIP-Branch löschen und die Kopie auf dem Remote behalten (auch über wippy.keepRemote)
+restore-remote-help = Remote, von dem der WIP-Branch statt von wippy.remote oder origin gelöscht wird
+show-branch-help = Name des anzuzeigenden Branches
+log-branch-help = Name des Branches, dessen Snapshots aufgelistet werden
+show-patch-help = Den vollständigen Patch statt einer Diffstat anzeigen
+diff-branch-help = Name des zu vergleichenden WIP-Branches
+diff-other-help = WIP-Branch, mit dem statt des Arbeitsverzeichnisses verglichen wird
+diff-stat-help = Eine Diffstat statt des vollständigen Patches anzeigen
+sync-remote-help = Remote, mit dem statt wippy.remote oder origin abgeglichen wird
+prune-older-than-help = WIP-Branches bereinigen, die vor längerer Zeit gespeichert wurden, z. B. 30d, 12h oder 2w
+prune-source-merged-help = WIP-Branches bereinigen, deren Quell-Branch gemergt oder gelöscht wurde
+prune-keep-last-help = Die neuesten N WIP-Branches jedes Benutzers behalten
+prune-all-users-help = Die WIP-Branches aller Benutzer bereinigen
+prune-dry-run-help = Nur anzeigen, was bereinigt würde
+watch-interval-help = So oft nach Änderungen suchen, z. B. 5m oder 30s
+watch-quiet-period-help = Einen Snapshot anlegen, sobald sich die Dateien so lange nicht geändert haben, z. B. 30s, und mindestens einmal pro Intervall
+watch-keep-last-help = Höchstens N Snapshots behalten, die ältesten werden verworfen
+watch-background-help = In einem Hintergrundprozess ausführen, mit Protokoll in .git/wippy-watch.log
+format-help = Ausgabeformat
 
 # Stashing messages
 stashing-existing-changes = Sichere bestehende Änderungen...
 restoring-existing-changes = Stelle bestehende Änderungen wieder her...
+kept-autostash = Deine lokalen Änderungen wurden nicht wieder angewendet, sie liegen im Stash '{ $name }'
+
+# Rollback messages
+rolling-back = Etwas ist schiefgelaufen, die abgeschlossenen Schritte werden rückgängig gemacht...
+rolled-back-created-branch = Branch '{ $name }' gelöscht
+rolled-back-pushed-branch = Branch '{ $name }' auf Remote '{ $remote }' gelöscht
+rolled-back-updated-branch = Neuen Snapshot von Branch '{ $name }' entfernt
+rolled-back-pushed-update = Neuen Snapshot von Branch '{ $name }' vom Remote '{ $remote }' entfernt
+rolled-back-cleared-changes = Gespeicherte Änderungen zurück ins Arbeitsverzeichnis gelegt
+rolled-back-stash = Gestashte lokale Änderungen wiederhergestellt
+rolled-back-switched-branch = Zurück zum Branch '{ $name }' gewechselt
+rolled-back-applied-changes = Teilweise wiederhergestellte Änderungen verworfen
+rolled-back-deleted-branch = Branch '{ $name }' wiederhergestellt
+rollback-complete = Alles ist wieder wie vorher
+rollback-incomplete = Nicht alle Schritte von 'git wippy { $command }' konnten rückgängig gemacht werden. Behebe das folgende Problem und führe 'git wippy recover' aus
+recovering = Unterbrochenes 'git wippy { $command }' wird rückgängig gemacht...
+nothing-to-recover = Kein unterbrochenes Speichern oder Wiederherstellen gefunden
+deleted-temp-branch = Übrig gebliebenen Branch '{ $name }' gelöscht
@@ -10,45 +10,128 @@ save-command-about = Save current changes as a WIP branch
 list-command-about = List all WIP branches
 delete-command-about = Delete a WIP branch
 restore-command-about = Restore changes from a WIP branch
+show-command-about = Show what a WIP branch holds without restoring it
+show-command-long-about = Print the saved metadata and files of a WIP branch, and its changes against the commit it was saved on
+log-command-about = List the snapshots of a WIP branch
+log-command-long-about = List the snapshots added to a WIP branch with save --update, newest first, with the names to restore or show them by
+diff-command-about = Compare a WIP branch to the working tree or to another WIP branch
+diff-command-long-about = List the files recorded on either side with their state, followed by the changes between a WIP branch and the working tree, untracked files included, or between two WIP branches
+sync-command-about = Sync your WIP branches with the remote
+sync-command-long-about = Fetch your WIP branches from the remote, push the ones only saved locally, delete local copies of the ones deleted on the remote, and report the ones that only exist on the remote or differ from it
+prune-command-about = Delete WIP branches that are no longer needed
+prune-command-long-about = Delete WIP branches that are older than a given age, whose source branch was merged or deleted, or beyond the newest few, for you or the whole team. Use --dry-run to see what would be deleted
+watch-command-about = Take snapshots of your changes while you work
+watch-command-long-about = Save a snapshot of the working tree every few minutes, or once the files stop changing, to a local WIP branch of its own. Unchanged working trees are skipped, only the newest snapshots are kept, and HEAD and the index are never touched. Stop it with Ctrl-C, or run it with --background
+recover-command-about = Undo an interrupted save or restore
+recover-command-long-about = Roll back the steps of a save or restore that was interrupted, and remove leftover temporary branches
 
 # Operation messages
 saving-wip = Saving WIP changes...
 created-branch = Created branch '{ $name }'
-staged-all-changes = Staged all changes
+recorded-changes = Recorded staged and unstaged changes
 committed-changes = Changes committed
 pushed-changes = Changes pushed to remote
 skipped-push-no-remote = No remote repository configured, skipping push
-switched-back = Switched back to branch '{ $name }'
+cleared-changes = Removed the saved changes from the working tree
+kept-changes = Left the saved changes in the working tree
 delete-complete = WIP branch deleted successfully
 no-wip-branches = No WIP branches found for user '{ $username }'
 restoring-wip = Restoring changes from branch '{ $name }'...
 checked-out-branch = Checked out branch '{ $name }'
+checked-out-commit = Checked out commit '{ $commit }'
 unstaged-changes = Unstaged changes
 stashed-changes = Stashed changes
 applied-stash = Applied stashed changes
 recreated-file-states = Recreated original file states
 deleted-local-branch = Deleted local branch '{ $name }'
+kept-wip-branch = Kept WIP branch '{ $name }'
+restore-conflicts = The branch moved on since saving, files with conflicts ({ $count }):
+kept-wip-branch-conflicts = Kept WIP branch '{ $name }'. Resolve the conflicts, then delete it with git wippy delete
 deleted-remote-branch = Deleted remote branch '{ $name }'
 restore-complete = Successfully restored changes from '{ $name }'
 operation-cancelled = Operation cancelled
 branch-not-found = Branch '{ $name }' not found
 branch-name = { $name }
 wip-branch-created = Created WIP branch '{ $name }'
+updated-branch = Added a snapshot to branch '{ $name }'
+wip-snapshot-added = Added a snapshot to WIP branch '{ $name }'
+restore-older-snapshot = Restoring an older snapshot, so '{ $name }' is kept with its newer snapshots
+log-heading = Snapshots of '{ $name }'
+log-column-snapshot = Snapshot
+log-column-commit = Commit
 wip-branch-deleted = Deleted WIP branch '{ $name }' { $remote ->
     [true] (local and remote)
     *[false] (local only)
 }
+wip-message = Message: { $message }
+fetched-wip-branch = Fetched '{ $name }' from remote '{ $remote }'
+wip-branch-deleted-remote = Deleted WIP branch '{ $name }' from remote '{ $remote }'
+wip-branch-remote-only = '{ $name }' only exists on a remote and was kept
+show-heading = WIP branch '{ $name }'
+show-source = Source: { $source }
+show-saved = Saved: { $timestamp }
+show-staged-files = Staged files:
+show-changed-files = Changed files:
+show-untracked-files = Untracked files:
+diff-heading = Comparing '{ $from }' with { $to }
+diff-working-tree = the working tree
+diff-no-files = Neither side recorded any changed files
+diff-file-column = File
+diff-state-staged = staged
+diff-state-changed = changed
+diff-state-untracked = untracked
+sync-fetching = Fetching WIP branches from '{ $remote }'...
+sync-branch-column = Branch
+sync-status-column = Status
+sync-up-to-date = up to date
+sync-pushed = pushed
+sync-remote-only = only on the remote
+sync-pruned = deleted, it was deleted on the remote
+sync-diverged = differs from the remote, left alone
+prune-user-column = User
+prune-reason-column = Reason
+prune-older-than = older than the limit
+prune-source-merged = source branch merged
+prune-source-deleted = source branch deleted
+prune-keep-last = beyond the newest to keep
+prune-nothing = No WIP branches to prune
+prune-dry-run = Dry run, nothing was deleted
+watch-started = Watching the working tree, looking for changes every { $interval }
+watch-started-quiet-period = Watching the working tree, taking a snapshot once the files stop changing for { $quiet_period }
+watch-snapshot = Saved a snapshot to '{ $name }'
+watch-dropped-snapshots = Dropped the oldest snapshots of '{ $name }', keeping the newest { $keep }
+watch-failed = Could not take a snapshot, trying again later: { $error }
+watch-stopped = Stopped watching the working tree
+watch-background = Watching the working tree in the background as process { $pid }, logging to { $log }
+branch-with-message = { $name } - { $message }
+no-wip-branches-all = No WIP branches found
+wip-user-heading = { $username }:
+location-local = local
+list-column-branch = Branch
+list-column-source = Source
+list-column-age = Age
+list-column-staged = Staged
+list-column-changed = Changed
+list-column-untracked = Untracked
+list-column-location = Location
+list-column-message = Message
 
 # Dialogue prompts
 delete-branch-prompt = Delete this branch?
 delete-all-prompt = Delete all { $count } WIP branches?
+delete-branches-prompt = Delete these { $count } WIP branches?
 delete-remote-prompt = Also delete { $count } remote branches?
 select-branches-to-delete = Select branches to delete:
 selection-instructions = Use space to select/deselect, press enter to confirm
 no-branches-selected = No branches selected
 selected-branches = Selected branches:
+restore-target-prompt = Branch '{ $name }' no longer exists. Where should the changes be restored?
+restore-target-recreate = Recreate '{ $name }' at { $commit }
+restore-target-create = Create '{ $name }' here
+restore-target-current = { $name } (current)
 found-wip-branch = Found WIP branch:
 found-wip-branches = Found WIP branches:
+found-wip-branches-all = WIP branches of all users:
 
 # Error messages
 remote-delete-failed = Failed to delete remote branch '{ $name }': { $error }
@@ -56,15 +139,61 @@ remote-delete-failed = Failed to delete remote branch '{ $name }': { $error }
 # Help messages
 save-local-help = Do not push changes to remote repository
 save-username-help = Specify a custom username
-save-datetime-help = Specify a custom date and time
-delete-branch-help = Name of the branch to delete
+save-datetime-help = Specify a custom date and time (YYYY-MM-DD-HH-mm-SS)
+save-message-help = Describe the work being saved
+save-update-help = Add a snapshot to your newest WIP saved from this branch, or to the given WIP branch, instead of creating a new one
+save-keep-help = Keep the changes in the working tree after saving
+save-remote-help = Remote to push to instead of wippy.remote or origin
+list-all-help = Show the WIP branches of all users
+list-remote-help = Only show remote branches on this remote
+delete-branch-help = Names of the branches to delete
 delete-all-help = Delete all WIP branches
 delete-force-help = Skip confirmation prompt
 delete-local-help = Only delete local branches
+delete-remote-help = Remote to delete from instead of wippy.remote or origin
 restore-branch-help = Name of the branch to restore
+restore-onto-help = Restore onto this branch instead of the one the work was saved on
 restore-force-help = Skip confirmation prompt
 restore-autostash-help = Automatically stash and reapply local changes
+restore-keep-help = Keep the WIP branch, locally and on the remote, like git stash apply
+restore-keep-remote-help = Only delete the local WIP branch and keep its remote copy (also set by wippy.keepRemote)
+restore-remote-help = Remote to delete the WIP branch from instead of wippy.remote or origin
+show-branch-help = Name of the branch to show
+log-branch-help = Name of the branch to list the snapshots of
+show-patch-help = Show the full patch instead of a diffstat
+diff-branch-help = Name of the WIP branch to compare
+diff-other-help = WIP branch to compare with instead of the working tree
+diff-stat-help = Show a diffstat instead of the full patch
+sync-remote-help = Remote to sync with instead of wippy.remote or origin
+prune-older-than-help = Prune WIP branches saved longer ago than this, e.g. 30d, 12h or 2w
+prune-source-merged-help = Prune WIP branches whose source branch was merged or deleted
+prune-keep-last-help = Keep the newest N WIP branches of each user
+prune-all-users-help = Prune the WIP branches of all users
+prune-dry-run-help = Only show what would be pruned
+watch-interval-help = Look for changes this often, e.g. 5m or 30s
+watch-quiet-period-help = Take a snapshot once the files stopped changing for this long, e.g. 30s, and at least every interval
+watch-keep-last-help = Keep at most N snapshots, dropping the oldest ones
+watch-background-help = Run in a background process, logging to .git/wippy-watch.log
+format-help = Output format
 
 # Stashing messages
 stashing-existing-changes = Stashing existing changes...
 restoring-existing-changes = Restoring existing changes...
+kept-autostash = Your local changes were not reapplied, they are kept in the stash '{ $name }'
+
+# Rollback messages
+rolling-back = Something went wrong, undoing the completed steps...
+rolled-back-created-branch = Deleted branch '{ $name }'
+rolled-back-pushed-branch = Deleted branch '{ $name }' from remote '{ $remote }'
+rolled-back-updated-branch = Removed the new snapshot from branch '{ $name }'
+rolled-back-pushed-update = Removed the new snapshot of branch '{ $name }' from remote '{ $remote }'
+rolled-back-cleared-changes = Put the saved changes back in the working tree
+rolled-back-stash = Restored your stashed local changes
+rolled-back-switched-branch = Switched back to branch '{ $name }'
+rolled-back-applied-changes = Discarded the partially restored changes
+rolled-back-deleted-branch = Recreated branch '{ $name }'
+rollback-complete = Everything is back the way it was
+rollback-incomplete = Could not undo every step of 'git wippy { $command }'. Fix the problem below and run 'git wippy recover'
+recovering = Undoing an interrupted 'git wippy { $command }'...
+nothing-to-recover = No interrupted save or restore to undo
+deleted-temp-branch = Deleted leftover branch '{ $name }'
@@ -10,45 +10,128 @@ save-command-about = Sauvegarder les modifications actuelles dans une branche WI
 list-command-about = Lister toutes les branches WIP
 delete-command-about = Supprimer une branche WIP
 restore-command-about = Restaurer les modifications depuis une branche WIP
+show-command-about = Afficher le contenu d'une branche WIP sans la restaurer
+show-command-long-about = Afficher les métadonnées et fichiers sauvegardés d'une branche WIP, ainsi que ses modifications par rapport au commit sur lequel elle a été sauvegardée
+log-command-about = Lister les instantanés d'une branche WIP
+log-command-long-about = Lister les instantanés ajoutés à une branche WIP avec save --update, du plus récent au plus ancien, avec les noms permettant de les restaurer ou de les afficher
+diff-command-about = Comparer une branche WIP au répertoire de travail ou à une autre branche WIP
+diff-command-long-about = Lister les fichiers enregistrés de chaque côté avec leur état, puis les modifications entre une branche WIP et le répertoire de travail, fichiers non suivis compris, ou entre deux branches WIP
+sync-command-about = Synchroniser vos branches WIP avec le dépôt distant
+sync-command-long-about = Récupérer vos branches WIP depuis le dépôt distant, pousser celles enregistrées uniquement en local, supprimer les copies locales de celles supprimées sur le dépôt distant, et signaler celles qui n'existent que sur le dépôt distant ou qui en diffèrent
+prune-command-about = Supprimer les branches WIP devenues inutiles
+prune-command-long-about = Supprimer les branches WIP plus anciennes qu'un âge donné, dont la branche source a été fusionnée ou supprimée, ou au-delà des plus récentes, pour vous ou toute l'équipe. Utiliser --dry-run pour voir ce qui serait supprimé
+watch-command-about = Prendre des instantanés de vos modifications pendant que vous travaillez
+watch-command-long-about = Sauvegarder un instantané de la copie de travail toutes les quelques minutes, ou dès que les fichiers ne changent plus, dans une branche WIP locale dédiée. Une copie de travail inchangée est ignorée, seuls les instantanés les plus récents sont conservés, et HEAD et l'index ne sont jamais modifiés. Arrêter avec Ctrl-C, ou lancer avec --background
+recover-command-about = Annuler une sauvegarde ou une restauration interrompue
+recover-command-long-about = Annuler les étapes d'une sauvegarde ou d'une restauration interrompue et supprimer les branches temporaires restantes
 
 # Operation messages
 saving-wip = Sauvegarde des modifications WIP...
 created-branch = Branche '{ $name }' créée
-staged-all-changes = Modifications indexées
+recorded-changes = Modifications indexées et non indexées enregistrées
 committed-changes = Modifications validées
 pushed-changes = Modifications poussées vers le dépôt distant
 skipped-push-no-remote = Aucun dépôt distant configuré, envoi ignoré
-switched-back = Retour à la branche '{ $name }'
+cleared-changes = Modifications sauvegardées retirées de la copie de travail
+kept-changes = Modifications sauvegardées conservées dans la copie de travail
 delete-complete = Branche WIP supprimée avec succès
 no-wip-branches = Aucune branche WIP trouvée pour l'utilisateur '{ $username }'
 restoring-wip = Restauration des modifications depuis la branche '{ $name }'...
 checked-out-branch = Branche '{ $name }' extraite
+checked-out-commit = Commit '{ $commit }' extrait
 unstaged-changes = Modifications désindexées
 stashed-changes = Modifications remisées
 applied-stash = Modifications remisées appliquées
 recreated-file-states = États des fichiers d'origine recréés
 deleted-local-branch = Branche locale '{ $name }' supprimée
+kept-wip-branch = Branche WIP '{ $name }' conservée
+restore-conflicts = La branche a évolué depuis la sauvegarde, fichiers en conflit ({ $count }) :
+kept-wip-branch-conflicts = Branche WIP '{ $name }' conservée. Résolvez les conflits, puis supprimez-la avec git wippy delete
 deleted-remote-branch = Branche distante '{ $name }' supprimée
 restore-complete = Modifications de '{ $name }' restaurées avec succès
 operation-cancelled = Opération annulée
 branch-not-found = Branche '{ $name }' introuvable
 branch-name = { $name }
 wip-branch-created = Branche WIP '{ $name }' créée
+updated-branch = Instantané ajouté à la branche '{ $name }'
+wip-snapshot-added = Instantané ajouté à la branche WIP '{ $name }'
+restore-older-snapshot = Restauration d'un instantané plus ancien, '{ $name }' est donc conservée avec ses instantanés plus récents
+log-heading = Instantanés de '{ $name }'
+log-column-snapshot = Instantané
+log-column-commit = Commit
 wip-branch-deleted = Branche WIP '{ $name }' supprimée { $remote ->
     [true] (locale et distante)
     *[false] (locale uniquement)
 }
+wip-message = Message : { $message }
+fetched-wip-branch = Branche '{ $name }' récupérée depuis le dépôt distant '{ $remote }'
+wip-branch-deleted-remote = Branche WIP '{ $name }' supprimée du dépôt distant '{ $remote }'
+wip-branch-remote-only = '{ $name }' n'existe que sur un dépôt distant et a été conservée
+show-heading = Branche WIP '{ $name }'
+show-source = Source : { $source }
+show-saved = Sauvegardée : { $timestamp }
+show-staged-files = Fichiers indexés :
+show-changed-files = Fichiers modifiés :
+show-untracked-files = Fichiers non suivis :
+diff-heading = Comparaison de '{ $from }' avec { $to }
+diff-working-tree = le répertoire de travail
+diff-no-files = Aucun côté n'a enregistré de fichiers modifiés
+diff-file-column = Fichier
+diff-state-staged = indexé
+diff-state-changed = modifié
+diff-state-untracked = non suivi
+sync-fetching = Récupération des branches WIP depuis '{ $remote }'...
+sync-branch-column = Branche
+sync-status-column = État
+sync-up-to-date = à jour
+sync-pushed = poussée
+sync-remote-only = uniquement sur le dépôt distant
+sync-pruned = supprimée, elle a été supprimée sur le dépôt distant
+sync-diverged = diffère du dépôt distant, laissée telle quelle
+prune-user-column = Utilisateur
+prune-reason-column = Raison
+prune-older-than = plus ancienne que la limite
+prune-source-merged = branche source fusionnée
+prune-source-deleted = branche source supprimée
+prune-keep-last = au-delà des plus récentes à conserver
+prune-nothing = Aucune branche WIP à nettoyer
+prune-dry-run = Simulation, rien n'a été supprimé
+watch-started = Surveillance de la copie de travail, recherche de modifications toutes les { $interval }
+watch-started-quiet-period = Surveillance de la copie de travail, un instantané sera pris dès que les fichiers n'auront pas changé pendant { $quiet_period }
+watch-snapshot = Instantané sauvegardé dans '{ $name }'
+watch-dropped-snapshots = Les instantanés les plus anciens de '{ $name }' ont été retirés, les { $keep } plus récents sont conservés
+watch-failed = Impossible de prendre un instantané, nouvel essai plus tard : { $error }
+watch-stopped = Surveillance de la copie de travail arrêtée
+watch-background = Surveillance de la copie de travail en arrière-plan par le processus { $pid }, journal dans { $log }
+branch-with-message = { $name } - { $message }
+no-wip-branches-all = Aucune branche WIP trouvée
+wip-user-heading = { $username } :
+location-local = locale
+list-column-branch = Branche
+list-column-source = Source
+list-column-age = Âge
+list-column-staged = Indexés
+list-column-changed = Modifiés
+list-column-untracked = Non suivis
+list-column-location = Emplacement
+list-column-message = Message
 
 # Dialog prompts
 delete-branch-prompt = Supprimer cette branche ?
 delete-all-prompt = Supprimer toutes les { $count } branches WIP ?
+delete-branches-prompt = Supprimer ces { $count } branches WIP ?
 delete-remote-prompt = Supprimer aussi les { $count } branches distantes ?
 select-branches-to-delete = Sélectionner les branches à supprimer :
 selection-instructions = Espace pour sélectionner/désélectionner, Entrée pour confirmer
 no-branches-selected = Aucune branche sélectionnée
 selected-branches = Branches sélectionnées :
+restore-target-prompt = La branche '{ $name }' n'existe plus. Où restaurer les modifications ?
+restore-target-recreate = Recréer '{ $name }' sur { $commit }
+restore-target-create = Créer '{ $name }' ici
+restore-target-current = { $name } (actuelle)
 found-wip-branch = Branche WIP trouvée :
 found-wip-branches = Branches WIP trouvées :
+found-wip-branches-all = Branches WIP de tous les utilisateurs :
 
 # Error messages
 remote-delete-failed = Échec de la suppression de la branche distante '{ $name }' : { $error }
@@ -56,15 +139,61 @@ remote-delete-failed = Échec de la suppression de la branche distante '{ $name
 # Help messages
 save-local-help = Ne pas pousser les modifications vers le dépôt distant
 save-username-help = Spécifier un nom d'utilisateur personnalisé
-save-datetime-help = Spécifier une date et une heure personnalisées
-delete-branch-help = Nom de la branche à supprimer
+save-datetime-help = Spécifier une date et une heure personnalisées (YYYY-MM-DD-HH-mm-SS)
+save-message-help = Décrire le travail sauvegardé
+save-update-help = Ajouter un instantané à votre WIP le plus récent enregistré depuis cette branche, ou à la branche WIP indiquée, au lieu d'en créer une nouvelle
+save-keep-help = Conserver les modifications dans la copie de travail après la sauvegarde
+save-remote-help = Dépôt distant vers lequel pousser au lieu de wippy.remote ou origin
+list-all-help = Afficher les branches WIP de tous les utilisateurs
+list-remote-help = Afficher uniquement les branches distantes de ce dépôt distant
+delete-branch-help = Noms des branches à supprimer
 delete-all-help = Supprimer toutes les branches WIP
 delete-force-help = Ignorer la confirmation
 delete-local-help = Supprimer uniquement les branches locales
+delete-remote-help = Dépôt distant où supprimer au lieu de wippy.remote ou origin
 restore-branch-help = Nom de la branche à restaurer
+restore-onto-help = Restaurer sur cette branche au lieu de celle où le travail a été sauvegardé
 restore-force-help = Ignorer la confirmation
 restore-autostash-help = Remiser et réappliquer automatiquement les modifications locales
+restore-keep-help = Conserver la branche WIP, en local et sur le dépôt distant, comme git stash apply
+restore-keep-remote-help = Supprimer uniquement la branche WIP locale et conserver sa copie distante (aussi via wippy.keepRemote)
+restore-remote-help = Dépôt distant où supprimer la branche WIP au lieu de wippy.remote ou origin
+show-branch-help = Nom de la branche à afficher
+log-branch-help = Nom de la branche dont lister les instantanés
+show-patch-help = Afficher le patch complet au lieu d'un diffstat
+diff-branch-help = Nom de la branche WIP à comparer
+diff-other-help = Branche WIP à comparer au lieu du répertoire de travail
+diff-stat-help = Afficher un diffstat au lieu du patch complet
+sync-remote-help = Dépôt distant avec lequel synchroniser au lieu de wippy.remote ou origin
+prune-older-than-help = Nettoyer les branches WIP enregistrées depuis plus longtemps, par ex. 30d, 12h ou 2w
+prune-source-merged-help = Nettoyer les branches WIP dont la branche source a été fusionnée ou supprimée
+prune-keep-last-help = Conserver les N branches WIP les plus récentes de chaque utilisateur
+prune-all-users-help = Nettoyer les branches WIP de tous les utilisateurs
+prune-dry-run-help = Afficher seulement ce qui serait nettoyé
+watch-interval-help = Rechercher des modifications à cette fréquence, par ex. 5m ou 30s
+watch-quiet-period-help = Prendre un instantané dès que les fichiers n'ont pas changé pendant cette durée, par ex. 30s, et au moins à chaque intervalle
+watch-keep-last-help = Conserver au plus N instantanés, en retirant les plus anciens
+watch-background-help = Lancer dans un processus en arrière-plan, avec un journal dans .git/wippy-watch.log
+format-help = Format de sortie
 
 # Stashing messages
 stashing-existing-changes = Sauvegarde des modifications existantes...
 restoring-existing-changes = Restauration des modifications existantes...
+kept-autostash = Vos modifications locales n'ont pas été réappliquées, elles sont conservées dans la remise '{ $name }'
+
+# Rollback messages
+rolling-back = Une erreur est survenue, annulation des étapes effectuées...
+rolled-back-created-branch = Branche '{ $name }' supprimée
+rolled-back-pushed-branch = Branche '{ $name }' supprimée du dépôt distant '{ $remote }'
+rolled-back-updated-branch = Nouvel instantané retiré de la branche '{ $name }'
+rolled-back-pushed-update = Nouvel instantané de la branche '{ $name }' retiré du dépôt distant '{ $remote }'
+rolled-back-cleared-changes = Modifications sauvegardées remises dans la copie de travail
+rolled-back-stash = Modifications locales remisées restaurées
+rolled-back-switched-branch = Retour à la branche '{ $name }'
+rolled-back-applied-changes = Modifications partiellement restaurées abandonnées
+rolled-back-deleted-branch = Branche '{ $name }' recréée
+rollback-complete = Tout est revenu à l'état initial
+rollback-incomplete = Impossible d'annuler toutes les étapes de 'git wippy { $command }'. Corrigez le problème ci-dessous puis lancez 'git wippy recover'
+recovering = Annulation d'un 'git wippy { $command }' interrompu...
+nothing-to-recover = Aucune sauvegarde ou restauration interrompue à annuler
+deleted-temp-branch = Branche restante '{ $name }' supprimée
@@ -1,6 +1,7 @@
 #[allow(unused_imports)]
 use crate::i18n;
 use crate::i18n::t;
+use crate::output::OutputFormat;
 use clap::{Args, CommandFactory, Parser, Subcommand};
 
 #[derive(Parser)]
@@ -17,6 +18,10 @@ use clap::{Args, CommandFactory, Parser, Subcommand};
 pub struct Cli {
     #[command(subcommand)]
     pub command: Commands,
+
+    /// Output format
+    #[arg(long, global = true, value_enum, value_name = "FORMAT", default_value_t = OutputFormat::Text, help = t("format-help"))]
+    pub format: OutputFormat,
 }
 
 #[derive(Args)]
@@ -25,6 +30,14 @@ pub struct SaveArgs {
     #[arg(short, long, action = clap::ArgAction::SetTrue, help = t("save-local-help"))]
     pub local: bool,
 
+    /// Remote to use instead of `wippy.remote` or `origin`
+    #[arg(short, long, value_name = "REMOTE", help = t("save-remote-help"))]
+    pub remote: Option<String>,
+
+    /// Leave the changes in the working tree after saving
+    #[arg(short, long, action = clap::ArgAction::SetTrue, help = t("save-keep-help"))]
+    pub keep: bool,
+
     /// Specify a custom username
     #[arg(short, long, value_name = "USERNAME", help = t("save-username-help"))]
     pub username: Option<String>,
@@ -32,13 +45,32 @@ pub struct SaveArgs {
     /// Specify a custom date and time
     #[arg(short, long, value_name = "DATETIME", help = t("save-datetime-help"))]
     pub datetime: Option<String>,
+
+    /// Describe the work being saved
+    #[arg(short, long, value_name = "MESSAGE", help = t("save-message-help"))]
+    pub message: Option<String>,
+
+    /// Add a snapshot to an existing WIP branch instead of creating one
+    #[arg(long, value_name = "BRANCH", help = t("save-update-help"))]
+    pub update: Option<Option<String>>,
+}
+
+#[derive(Args)]
+pub struct ListArgs {
+    /// Show the WIP branches of all users
+    #[arg(short, long, action = clap::ArgAction::SetTrue, help = t("list-all-help"))]
+    pub all: bool,
+
+    /// Only show remote branches on this remote
+    #[arg(short, long, value_name = "REMOTE", help = t("list-remote-help"))]
+    pub remote: Option<String>,
 }
 
 #[derive(Args)]
 pub struct DeleteArgs {
-    /// Name of the branch to delete
+    /// Names of the branches to delete
     #[arg(value_name = "BRANCH", help = t("delete-branch-help"))]
-    pub branch: Option<String>,
+    pub branch: Vec<String>,
 
     /// Delete all WIP branches
     #[arg(short, long, action = clap::ArgAction::SetTrue, help = t("delete-all-help"))]
@@ -51,6 +83,10 @@ pub struct DeleteArgs {
     /// Only delete local branches
     #[arg(short, long, action = clap::ArgAction::SetTrue, help = t("delete-local-help"))]
     pub local: bool,
+
+    /// Remote to use instead of `wippy.remote` or `origin`
+    #[arg(short, long, value_name = "REMOTE", help = t("delete-remote-help"))]
+    pub remote: Option<String>,
 }
 
 #[derive(Args)]
@@ -59,6 +95,10 @@ pub struct RestoreArgs {
     #[arg(value_name = "BRANCH", help = t("restore-branch-help"))]
     pub branch: Option<String>,
 
+    /// Branch to restore onto instead of the one the work was saved on
+    #[arg(long, value_name = "BRANCH", help = t("restore-onto-help"))]
+    pub onto: Option<String>,
+
     /// Skip confirmation prompt
     #[arg(short = 'y', action = clap::ArgAction::SetTrue, help = t("restore-force-help"))]
     pub force: bool,
@@ -66,6 +106,122 @@ pub struct RestoreArgs {
     /// Automatically stash and reapply local changes
     #[arg(long = "autostash", action = clap::ArgAction::SetTrue, help = t("restore-autostash-help"))]
     pub autostash: bool,
+
+    /// Keep the WIP branch after restoring it
+    #[arg(short, long, action = clap::ArgAction::SetTrue, help = t("restore-keep-help"))]
+    pub keep: bool,
+
+    /// Keep the remote copy of the WIP branch
+    #[arg(long, action = clap::ArgAction::SetTrue, help = t("restore-keep-remote-help"))]
+    pub keep_remote: bool,
+
+    /// Remote to use instead of `wippy.remote` or `origin`
+    #[arg(short, long, value_name = "REMOTE", help = t("restore-remote-help"))]
+    pub remote: Option<String>,
+}
+
+#[derive(Args)]
+pub struct ShowArgs {
+    /// Name of the branch to show
+    #[arg(value_name = "BRANCH", help = t("show-branch-help"))]
+    pub branch: Option<String>,
+
+    /// Show the full patch instead of a diffstat
+    #[arg(short, long, action = clap::ArgAction::SetTrue, help = t("show-patch-help"))]
+    pub patch: bool,
+}
+
+#[derive(Args)]
+pub struct LogArgs {
+    /// Name of the branch to list the snapshots of
+    #[arg(value_name = "BRANCH", help = t("log-branch-help"))]
+    pub branch: Option<String>,
+}
+
+#[derive(Args)]
+pub struct DiffArgs {
+    /// Name of the WIP branch to compare
+    #[arg(value_name = "BRANCH", help = t("diff-branch-help"))]
+    pub branch: Option<String>,
+
+    /// WIP branch to compare with instead of the working tree
+    #[arg(value_name = "OTHER", help = t("diff-other-help"))]
+    pub other: Option<String>,
+
+    /// Show a diffstat instead of the full patch
+    #[arg(long, action = clap::ArgAction::SetTrue, help = t("diff-stat-help"))]
+    pub stat: bool,
+}
+
+#[derive(Args)]
+pub struct SyncArgs {
+    /// Remote to use instead of `wippy.remote` or `origin`
+    #[arg(short, long, value_name = "REMOTE", help = t("sync-remote-help"))]
+    pub remote: Option<String>,
+}
+
+#[derive(Args)]
+pub struct PruneArgs {
+    /// Prune WIP branches saved longer ago than this, e.g. `30d`
+    #[arg(long, value_name = "AGE", help = t("prune-older-than-help"))]
+    pub older_than: Option<String>,
+
+    /// Prune WIP branches whose source branch was merged or deleted
+    #[arg(long, action = clap::ArgAction::SetTrue, help = t("prune-source-merged-help"))]
+    pub source_merged: bool,
+
+    /// Keep the newest N WIP branches of each user
+    #[arg(long, value_name = "N", help = t("prune-keep-last-help"))]
+    pub keep_last: Option<usize>,
+
+    /// Prune the WIP branches of all users
+    #[arg(long, action = clap::ArgAction::SetTrue, help = t("prune-all-users-help"))]
+    pub all_users: bool,
+
+    /// Only show what would be pruned
+    #[arg(short = 'n', long, action = clap::ArgAction::SetTrue, help = t("prune-dry-run-help"))]
+    pub dry_run: bool,
+
+    /// Skip confirmation prompt
+    #[arg(short, long, action = clap::ArgAction::SetTrue, help = t("delete-force-help"))]
+    pub force: bool,
+
+    /// Only delete local branches
+    #[arg(short, long, action = clap::ArgAction::SetTrue, help = t("delete-local-help"))]
+    pub local: bool,
+
+    /// Remote to use instead of `wippy.remote` or `origin`
+    #[arg(short, long, value_name = "REMOTE", help = t("delete-remote-help"))]
+    pub remote: Option<String>,
+}
+
+#[derive(Args)]
+pub struct WatchArgs {
+    /// Check for changes this often, e.g. `5m`
+    #[arg(long, value_name = "AGE", default_value = "5m", help = t("watch-interval-help"))]
+    pub interval: String,
+
+    /// Take a snapshot once the files stopped changing for this long, e.g. `30s`
+    #[arg(long, value_name = "AGE", help = t("watch-quiet-period-help"))]
+    pub quiet_period: Option<String>,
+
+    /// Keep at most N snapshots, at least one
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 20,
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..),
+        help = t("watch-keep-last-help")
+    )]
+    pub keep_last: usize,
+
+    /// Describe the work being saved
+    #[arg(short, long, value_name = "MESSAGE", help = t("save-message-help"))]
+    pub message: Option<String>,
+
+    /// Run as a background process
+    #[arg(short, long, action = clap::ArgAction::SetTrue, help = t("watch-background-help"))]
+    pub background: bool,
 }
 
 #[derive(Subcommand)]
@@ -78,7 +234,7 @@ pub enum Commands {
     #[command(alias = "l")]
     #[command(about = t("list-command-about"))]
     #[command(long_about = t("list-command-long-about"))]
-    List,
+    List(ListArgs),
 
     #[command(alias = "d")]
     #[command(about = t("delete-command-about"))]
@@ -89,36 +245,149 @@ pub enum Commands {
     #[command(about = t("restore-command-about"))]
     #[command(long_about = t("restore-command-long-about"))]
     Restore(RestoreArgs),
+
+    #[command(about = t("show-command-about"))]
+    #[command(long_about = t("show-command-long-about"))]
+    Show(ShowArgs),
+
+    #[command(about = t("log-command-about"))]
+    #[command(long_about = t("log-command-long-about"))]
+    Log(LogArgs),
+
+    #[command(about = t("diff-command-about"))]
+    #[command(long_about = t("diff-command-long-about"))]
+    Diff(DiffArgs),
+
+    #[command(about = t("sync-command-about"))]
+    #[command(long_about = t("sync-command-long-about"))]
+    Sync(SyncArgs),
+
+    #[command(about = t("prune-command-about"))]
+    #[command(long_about = t("prune-command-long-about"))]
+    Prune(PruneArgs),
+
+    #[command(about = t("watch-command-about"))]
+    #[command(long_about = t("watch-command-long-about"))]
+    Watch(WatchArgs),
+
+    #[command(about = t("recover-command-about"))]
+    #[command(long_about = t("recover-command-long-about"))]
+    Recover,
 }
 
 impl Cli {
     pub fn new() -> Self {
         let matches = Self::command().get_matches();
+        let format = matches
+            .get_one::<OutputFormat>("format")
+            .copied()
+            .unwrap_or_default();
         match matches.subcommand() {
             Some(("save", sub_matches)) => Self {
                 command: Commands::Save(SaveArgs {
                     local: sub_matches.get_flag("local"),
+                    remote: sub_matches.get_one::<String>("remote").cloned(),
+                    keep: sub_matches.get_flag("keep"),
                     username: sub_matches.get_one::<String>("username").cloned(),
                     datetime: sub_matches.get_one::<String>("datetime").cloned(),
+                    message: sub_matches.get_one::<String>("message").cloned(),
+                    update: sub_matches
+                        .contains_id("update")
+                        .then(|| sub_matches.get_one::<String>("update").cloned()),
                 }),
+                format,
             },
-            Some(("list", _)) => Self {
-                command: Commands::List,
+            Some(("list", sub_matches)) => Self {
+                command: Commands::List(ListArgs {
+                    all: sub_matches.get_flag("all"),
+                    remote: sub_matches.get_one::<String>("remote").cloned(),
+                }),
+                format,
             },
             Some(("delete", sub_matches)) => Self {
                 command: Commands::Delete(DeleteArgs {
-                    branch: sub_matches.get_one::<String>("branch").cloned(),
+                    branch: sub_matches
+                        .get_many::<String>("branch")
+                        .map(|branches| branches.cloned().collect())
+                        .unwrap_or_default(),
                     all: sub_matches.get_flag("all"),
                     force: sub_matches.get_flag("force"),
                     local: sub_matches.get_flag("local"),
+                    remote: sub_matches.get_one::<String>("remote").cloned(),
                 }),
+                format,
             },
             Some(("restore", sub_matches)) => Self {
                 command: Commands::Restore(RestoreArgs {
                     branch: sub_matches.get_one::<String>("branch").cloned(),
+                    onto: sub_matches.get_one::<String>("onto").cloned(),
                     force: sub_matches.get_flag("force"),
                     autostash: sub_matches.get_flag("autostash"),
+                    keep: sub_matches.get_flag("keep"),
+                    keep_remote: sub_matches.get_flag("keep_remote"),
+                    remote: sub_matches.get_one::<String>("remote").cloned(),
+                }),
+                format,
+            },
+            Some(("show", sub_matches)) => Self {
+                command: Commands::Show(ShowArgs {
+                    branch: sub_matches.get_one::<String>("branch").cloned(),
+                    patch: sub_matches.get_flag("patch"),
                 }),
+                format,
+            },
+            Some(("log", sub_matches)) => Self {
+                command: Commands::Log(LogArgs {
+                    branch: sub_matches.get_one::<String>("branch").cloned(),
+                }),
+                format,
+            },
+            Some(("diff", sub_matches)) => Self {
+                command: Commands::Diff(DiffArgs {
+                    branch: sub_matches.get_one::<String>("branch").cloned(),
+                    other: sub_matches.get_one::<String>("other").cloned(),
+                    stat: sub_matches.get_flag("stat"),
+                }),
+                format,
+            },
+            Some(("sync", sub_matches)) => Self {
+                command: Commands::Sync(SyncArgs {
+                    remote: sub_matches.get_one::<String>("remote").cloned(),
+                }),
+                format,
+            },
+            Some(("prune", sub_matches)) => Self {
+                command: Commands::Prune(PruneArgs {
+                    older_than: sub_matches.get_one::<String>("older_than").cloned(),
+                    source_merged: sub_matches.get_flag("source_merged"),
+                    keep_last: sub_matches.get_one::<usize>("keep_last").copied(),
+                    all_users: sub_matches.get_flag("all_users"),
+                    dry_run: sub_matches.get_flag("dry_run"),
+                    force: sub_matches.get_flag("force"),
+                    local: sub_matches.get_flag("local"),
+                    remote: sub_matches.get_one::<String>("remote").cloned(),
+                }),
+                format,
+            },
+            Some(("watch", sub_matches)) => Self {
+                command: Commands::Watch(WatchArgs {
+                    interval: sub_matches
+                        .get_one::<String>("interval")
+                        .cloned()
+                        .unwrap_or_default(),
+                    quiet_period: sub_matches.get_one::<String>("quiet_period").cloned(),
+                    keep_last: sub_matches
+                        .get_one::<usize>("keep_last")
+                        .copied()
+                        .unwrap_or_default(),
+                    message: sub_matches.get_one::<String>("message").cloned(),
+                    background: sub_matches.get_flag("background"),
+                }),
+                format,
+            },
+            Some(("recover", _)) => Self {
+                command: Commands::Recover,
+                format,
             },
             _ => unreachable!(),
         }
@@ -1,16 +1,38 @@
 use crate::i18n::t_with_args;
-use crate::output::Output;
-use crate::utils::{git_username_with_git, Git, GitCommand};
+use crate::output::{Output, OutputFormat};
+use crate::utils::{
+    git_username_with_git, open_git, retain_wip_commits, wip_remote_with_git, Git, WipBranch,
+};
 use anyhow::{Context, Result};
 use dialoguer::{theme::ColorfulTheme, Confirm, MultiSelect};
+use serde::Serialize;
 
 pub struct DeleteOptions {
-    pub branch_name: Option<String>,
+    pub branch_names: Vec<String>,
     pub all: bool,
+    pub all_users: bool,
     pub force: bool,
     pub local_only: bool,
+    pub remote: Option<String>,
+    pub format: OutputFormat,
 }
 
+/// The result of a delete, as printed by `--format json`.
+#[derive(Serialize, Default)]
+pub(crate) struct DeleteResult {
+    deleted: Vec<DeletedBranch>,
+}
+
+#[derive(Serialize)]
+struct DeletedBranch {
+    branch: String,
+    local: bool,
+    remote: Option<String>,
+}
+
+/// How to delete without being asked, where nobody can answer a prompt
+pub(crate) const FORCE_HINT: &str = "pass --force to delete without asking";
+
 /// Deletes one or more WIP branches.
 ///
 /// # Arguments
@@ -18,23 +40,69 @@ pub struct DeleteOptions {
 ///
 /// # Features
 /// * Interactive branch selection if no branch specified
-/// * Confirmation prompt (unless force flag used)
-/// * Handles both local and remote deletion
+/// * Deletes other users' WIP branches too with `all_users`, as `prune` does
+/// * Only deletes branches whose tip is a commit `save` wrote
+/// * Named branches that were only pushed from another machine are fetched
+///   first, and a name that is found nowhere is an error
+/// * Confirmation prompt (unless force flag used), an error with `--format json`
+/// * Handles both local and remote deletion, on the remote from `--remote`,
+///   `wippy.remote` or `origin`
 /// * Can delete all user's WIP branches
 pub async fn delete_wip_branches(options: DeleteOptions) -> Result<()> {
-    let git = GitCommand::new();
+    let git = open_git();
     delete_wip_branches_with_git(&git, options).await
 }
 
 pub async fn delete_wip_branches_with_git(git: &impl Git, options: DeleteOptions) -> Result<()> {
-    let output = Output::new().await?;
+    let output = Output::with_format(options.format).await?;
     let username = git_username_with_git(git).await?;
-    let wip_branches = git.get_user_wip_branches(&username).await?;
+    let mut user_branches = find_wip_branches(git, &username, options.all_users).await?;
+
+    // WIPs pushed from another machine may not have been fetched yet
+    let unknown: Vec<&String> = options
+        .branch_names
+        .iter()
+        .filter(|name| !user_branches.iter().any(|branch| &branch.name == *name))
+        .collect();
+    if !unknown.is_empty() && !options.local_only {
+        if let Some(remote) = wip_remote_with_git(git, options.remote.as_deref()).await? {
+            let mut fetched = false;
+            for name in unknown {
+                if git.fetch_branch(&remote, name).await? {
+                    let message =
+                        t_with_args("fetched-wip-branch", &[("name", name), ("remote", &remote)]);
+                    output.info(
+                        &output.format_with_highlights(&message, &[&format!("'{}'", name)]),
+                    )?;
+                    fetched = true;
+                }
+            }
+            if fetched {
+                user_branches = find_wip_branches(git, &username, options.all_users).await?;
+            }
+        }
+    }
+
+    // Naming a branch that doesn't exist is a mistake, not nothing to do
+    let missing: Vec<String> = options
+        .branch_names
+        .iter()
+        .filter(|name| !user_branches.iter().any(|branch| &branch.name == *name))
+        .map(|name| format!("'{}'", name))
+        .collect();
+    if !missing.is_empty() {
+        anyhow::bail!("No WIP branch named {}", missing.join(", "));
+    }
+
+    let wip_branches: Vec<String> = user_branches
+        .iter()
+        .map(|branch| branch.name.clone())
+        .collect();
 
     if wip_branches.is_empty() {
         let message = t_with_args("no-wip-branches", &[("username", &username)]);
         output.info(&output.format_with_highlights(&message, &[&username]))?;
-        return Ok(());
+        return output.result(&DeleteResult::default());
     }
 
     let branches_to_delete = if options.all {
@@ -43,33 +111,34 @@ pub async fn delete_wip_branches_with_git(git: &impl Git, options: DeleteOptions
                 "delete-all-prompt",
                 &[("count", &wip_branches.len().to_string())],
             );
+            output.ensure_can_prompt(FORCE_HINT)?;
             let confirm = Confirm::with_theme(&ColorfulTheme::default())
                 .with_prompt(message)
                 .interact()?;
 
             if !confirm {
                 output.info(&t_with_args("operation-cancelled", &[]))?;
-                return Ok(());
+                return output.result(&DeleteResult::default());
             }
         }
         wip_branches
-    } else if let Some(branch) = options.branch_name {
-        if !wip_branches.contains(&branch) {
-            let message = t_with_args("branch-not-found", &[("name", &branch)]);
-            output.info(&output.format_with_highlights(&message, &[&format!("'{}'", branch)]))?;
-            return Ok(());
-        }
+    } else if !options.branch_names.is_empty() {
         if !options.force {
+            let prompt = match options.branch_names.len() {
+                1 => t_with_args("delete-branch-prompt", &[]),
+                count => t_with_args("delete-branches-prompt", &[("count", &count.to_string())]),
+            };
+            output.ensure_can_prompt(FORCE_HINT)?;
             let confirm = Confirm::with_theme(&ColorfulTheme::default())
-                .with_prompt(t_with_args("delete-branch-prompt", &[]))
+                .with_prompt(prompt)
                 .interact()?;
 
             if !confirm {
                 output.info(&t_with_args("operation-cancelled", &[]))?;
-                return Ok(());
+                return output.result(&DeleteResult::default());
             }
         }
-        vec![branch]
+        options.branch_names
     } else if wip_branches.len() == 1 {
         // For a single branch, use a simple confirm dialog
         let branch = &wip_branches[0];
@@ -82,18 +151,20 @@ pub async fn delete_wip_branches_with_git(git: &impl Git, options: DeleteOptions
         )?;
 
         if !options.force {
+            output.ensure_can_prompt(FORCE_HINT)?;
             let confirm = Confirm::with_theme(&ColorfulTheme::default())
                 .with_prompt(t_with_args("delete-branch-prompt", &[]))
                 .interact()?;
 
             if !confirm {
                 output.info(&t_with_args("operation-cancelled", &[]))?;
-                return Ok(());
+                return output.result(&DeleteResult::default());
             }
         }
         wip_branches
     } else {
         // Multiple branches - use multi-select
+        output.ensure_can_prompt("pass the names of the branches to delete, or --all")?;
         output.info(&t_with_args("select-branches-to-delete", &[]))?;
         output.info(&t_with_args("selection-instructions", &[]))?;
 
@@ -105,7 +176,7 @@ pub async fn delete_wip_branches_with_git(git: &impl Git, options: DeleteOptions
 
         if selections.is_empty() {
             output.info(&t_with_args("no-branches-selected", &[]))?;
-            return Ok(());
+            return output.result(&DeleteResult::default());
         }
 
         // Show what's selected before confirmation
@@ -126,35 +197,49 @@ pub async fn delete_wip_branches_with_git(git: &impl Git, options: DeleteOptions
     };
 
     // Ask about remote deletion if not specified
-    let delete_remote = if !options.local_only {
-        let remotes = git.get_remotes().await?;
-        if remotes.contains(&"origin".to_string()) {
-            if options.force {
-                true
-            } else {
+    let remote = if !options.local_only {
+        match wip_remote_with_git(git, options.remote.as_deref()).await? {
+            Some(remote) if options.force => Some(remote),
+            Some(remote) => {
+                output.ensure_can_prompt(FORCE_HINT)?;
                 let count = branches_to_delete.len().to_string();
                 Confirm::with_theme(&ColorfulTheme::default())
                     .with_prompt(t_with_args("delete-remote-prompt", &[("count", &count)]))
                     .interact()?
+                    .then_some(remote)
             }
-        } else {
-            false
+            None => None,
         }
     } else {
-        false
+        None
     };
+    let delete_remote = remote.is_some();
 
     // Delete branches
+    let mut result = DeleteResult::default();
     for branch in &branches_to_delete {
+        // Branches pushed from another machine may only exist on a remote
+        let local = user_branches
+            .iter()
+            .any(|wip_branch| &wip_branch.name == branch && wip_branch.local);
+        if !local && remote.is_none() {
+            let message = t_with_args("wip-branch-remote-only", &[("name", branch)]);
+            output.info(&output.format_with_highlights(&message, &[&format!("'{}'", branch)]))?;
+            continue;
+        }
+
         // Delete local branch
-        git.delete_branch(branch, true)
-            .await
-            .context(format!("Failed to delete local branch '{}'", branch))?;
+        if local {
+            git.delete_branch(branch, true)
+                .await
+                .context(format!("Failed to delete local branch '{}'", branch))?;
+        }
 
         // Delete remote branch if requested
-        if delete_remote {
-            match git.delete_remote_branch("origin", branch).await {
-                Ok(_) => {}
+        let mut deleted_remote = None;
+        if let Some(remote) = &remote {
+            match git.delete_remote_branch(remote, branch).await {
+                Ok(_) => deleted_remote = Some(remote.clone()),
                 Err(e) => {
                     let message = t_with_args(
                         "remote-delete-failed",
@@ -167,14 +252,26 @@ pub async fn delete_wip_branches_with_git(git: &impl Git, options: DeleteOptions
             }
         }
 
-        let message = t_with_args(
-            "wip-branch-deleted",
-            &[
-                ("name", branch),
-                ("remote", if delete_remote { "true" } else { "false" }),
-            ],
-        );
+        let message = match (local, &deleted_remote) {
+            (false, Some(remote)) => t_with_args(
+                "wip-branch-deleted-remote",
+                &[("name", branch), ("remote", remote)],
+            ),
+            (false, None) => continue,
+            _ => t_with_args(
+                "wip-branch-deleted",
+                &[
+                    ("name", branch),
+                    ("remote", if delete_remote { "true" } else { "false" }),
+                ],
+            ),
+        };
         output.info(&output.format_with_highlights(&message, &[&format!("'{}'", branch)]))?;
+        result.deleted.push(DeletedBranch {
+            branch: branch.clone(),
+            local,
+            remote: deleted_remote,
+        });
     }
 
     let message = t_with_args(
@@ -185,7 +282,21 @@ pub async fn delete_wip_branches_with_git(git: &impl Git, options: DeleteOptions
         ],
     );
     output.info(&message)?;
-    Ok(())
+    output.result(&result)
+}
+
+/// Lists the WIP branches `delete` may delete, other users' too with `all_users`.
+async fn find_wip_branches(
+    git: &impl Git,
+    username: &str,
+    all_users: bool,
+) -> Result<Vec<WipBranch>> {
+    let branches = if all_users {
+        git.get_all_wip_branches().await?
+    } else {
+        git.get_user_wip_branches(username).await?
+    };
+    Ok(retain_wip_commits(git, branches).await)
 }
 
 #[cfg(test)]
@@ -202,6 +313,31 @@ mod tests {
         });
     }
 
+    fn mock_wip_branches(mock_git: &mut MockGit, names: &[&str]) {
+        let branches: Vec<WipBranch> = names
+            .iter()
+            .map(|name| WipBranch {
+                name: name.to_string(),
+                user: "test-user".to_string(),
+                timestamp: name.rsplit('/').next().unwrap().to_string(),
+                local: true,
+                remotes: Vec::new(),
+            })
+            .collect();
+        mock_git
+            .expect_get_user_wip_branches()
+            .with(mockall::predicate::eq("test-user"))
+            .returning(move |_| Ok(branches.clone()));
+        expect_wip_commits(mock_git);
+    }
+
+    /// Mocks the tips of the branches as commits `save` wrote
+    fn expect_wip_commits(mock_git: &mut MockGit) {
+        mock_git
+            .expect_get_commit_message()
+            .returning(|_| Ok("chore: saving work in progress\n\nWippy-Version: 1".to_string()));
+    }
+
     #[tokio::test]
     async fn test_delete_single_branch() -> Result<()> {
         let mut mock_git = MockGit::new();
@@ -216,16 +352,13 @@ mod tests {
             .returning(|_| Ok("test-user".to_string()));
 
         // Mock WIP branches
-        mock_git
-            .expect_get_user_wip_branches()
-            .with(mockall::predicate::eq("test-user"))
-            .returning(|_| Ok(vec!["wip/test-user/branch1".to_string()]));
+        mock_wip_branches(&mut mock_git, &["wip/test-user/2024-03-21-17-59-30"]);
 
         // Mock local branch deletion
         mock_git
             .expect_delete_branch()
             .with(
-                mockall::predicate::eq("wip/test-user/branch1"),
+                mockall::predicate::eq("wip/test-user/2024-03-21-17-59-30"),
                 mockall::predicate::eq(true),
             )
             .returning(|_, _| Ok("Deleted branch".to_string()));
@@ -234,21 +367,25 @@ mod tests {
         mock_git
             .expect_get_remotes()
             .returning(|| Ok(vec!["origin".to_string()]));
+        mock_git.expect_get_config_value().returning(|_| Ok(None));
 
         // Mock remote branch deletion
         mock_git
             .expect_delete_remote_branch()
             .with(
                 mockall::predicate::eq("origin"),
-                mockall::predicate::eq("wip/test-user/branch1"),
+                mockall::predicate::eq("wip/test-user/2024-03-21-17-59-30"),
             )
             .returning(|_, _| Ok("".to_string()));
 
         let options = DeleteOptions {
-            branch_name: Some("wip/test-user/branch1".to_string()),
+            branch_names: vec!["wip/test-user/2024-03-21-17-59-30".to_string()],
             all: false,
+            all_users: false,
             force: true,
             local_only: false,
+            remote: None,
+            format: OutputFormat::Text,
         };
 
         delete_wip_branches_with_git(&mock_git, options).await?;
@@ -269,18 +406,19 @@ mod tests {
             .returning(|_| Ok("test-user".to_string()));
 
         // Mock WIP branches
-        mock_git
-            .expect_get_user_wip_branches()
-            .with(mockall::predicate::eq("test-user"))
-            .returning(|_| {
-                Ok(vec![
-                    "wip/test-user/branch1".to_string(),
-                    "wip/test-user/branch2".to_string(),
-                ])
-            });
+        mock_wip_branches(
+            &mut mock_git,
+            &[
+                "wip/test-user/2024-03-21-17-59-30",
+                "wip/test-user/2024-03-22-09-00-00",
+            ],
+        );
 
         // Mock local branch deletions
-        for branch in ["wip/test-user/branch1", "wip/test-user/branch2"] {
+        for branch in [
+            "wip/test-user/2024-03-21-17-59-30",
+            "wip/test-user/2024-03-22-09-00-00",
+        ] {
             mock_git
                 .expect_delete_branch()
                 .with(mockall::predicate::eq(branch), mockall::predicate::eq(true))
@@ -291,9 +429,13 @@ mod tests {
         mock_git
             .expect_get_remotes()
             .returning(|| Ok(vec!["origin".to_string()]));
+        mock_git.expect_get_config_value().returning(|_| Ok(None));
 
         // Mock remote branch deletions
-        for branch in ["wip/test-user/branch1", "wip/test-user/branch2"] {
+        for branch in [
+            "wip/test-user/2024-03-21-17-59-30",
+            "wip/test-user/2024-03-22-09-00-00",
+        ] {
             mock_git
                 .expect_delete_remote_branch()
                 .with(
@@ -304,10 +446,13 @@ mod tests {
         }
 
         let options = DeleteOptions {
-            branch_name: None,
+            branch_names: Vec::new(),
             all: true,
+            all_users: false,
             force: true,
             local_only: false,
+            remote: None,
+            format: OutputFormat::Text,
         };
 
         delete_wip_branches_with_git(&mock_git, options).await?;
@@ -315,7 +460,71 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_delete_nonexistent_branch() -> Result<()> {
+    async fn test_delete_all_skips_branches_without_wip_commit() -> Result<()> {
+        let mut mock_git = MockGit::new();
+        mock_git
+            .expect_execute()
+            .with(mockall::predicate::eq(vec![
+                "config".to_string(),
+                "user.name".to_string(),
+            ]))
+            .returning(|_| Ok("test-user".to_string()));
+        mock_git.expect_get_user_wip_branches().returning(|_| {
+            Ok([
+                "wip/test-user/2024-03-21-17-59-30",
+                "wip/test-user/2024-03-22-09-00-00",
+            ]
+            .iter()
+            .map(|name| WipBranch {
+                name: name.to_string(),
+                user: "test-user".to_string(),
+                timestamp: name.rsplit('/').next().unwrap().to_string(),
+                local: true,
+                remotes: Vec::new(),
+            })
+            .collect())
+        });
+        mock_git
+            .expect_get_commit_message()
+            .with(mockall::predicate::eq("wip/test-user/2024-03-21-17-59-30"))
+            .returning(|_| Ok("chore: saving work in progress\n\nWippy-Version: 1".to_string()));
+        // Named like a WIP branch, but not saved by git-wippy
+        mock_git
+            .expect_get_commit_message()
+            .with(mockall::predicate::eq("wip/test-user/2024-03-22-09-00-00"))
+            .returning(|_| Ok("Release 1.0".to_string()));
+        mock_git
+            .expect_delete_branch()
+            .with(
+                mockall::predicate::eq("wip/test-user/2024-03-21-17-59-30"),
+                mockall::predicate::eq(true),
+            )
+            .times(1)
+            .returning(|_, _| Ok("Deleted branch".to_string()));
+        mock_git
+            .expect_delete_branch()
+            .with(
+                mockall::predicate::eq("wip/test-user/2024-03-22-09-00-00"),
+                mockall::predicate::always(),
+            )
+            .never();
+        mock_git.expect_get_remotes().returning(|| Ok(Vec::new()));
+        mock_git.expect_get_config_value().returning(|_| Ok(None));
+
+        let options = DeleteOptions {
+            branch_names: Vec::new(),
+            all: true,
+            all_users: false,
+            force: true,
+            local_only: true,
+            remote: None,
+            format: OutputFormat::Text,
+        };
+        delete_wip_branches_with_git(&mock_git, options).await
+    }
+
+    #[tokio::test]
+    async fn test_delete_nonexistent_branch() {
         let mut mock_git = MockGit::new();
 
         // Mock username lookup
@@ -328,20 +537,106 @@ mod tests {
             .returning(|_| Ok("test-user".to_string()));
 
         // Mock WIP branches
+        mock_wip_branches(&mut mock_git, &["wip/test-user/2024-03-20-09-00-00"]);
+
+        // The remote doesn't have it either
         mock_git
-            .expect_get_user_wip_branches()
-            .with(mockall::predicate::eq("test-user"))
-            .returning(|_| Ok(vec!["wip/test-user/existing-branch".to_string()]));
+            .expect_get_remotes()
+            .returning(|| Ok(vec!["origin".to_string()]));
+        mock_git.expect_get_config_value().returning(|_| Ok(None));
+        mock_git
+            .expect_fetch_branch()
+            .with(
+                mockall::predicate::eq("origin"),
+                mockall::predicate::eq("wip/test-user/2024-03-19-09-00-00"),
+            )
+            .times(1)
+            .returning(|_, _| Ok(false));
+        mock_git.expect_delete_branch().never();
 
         let options = DeleteOptions {
-            branch_name: Some("wip/test-user/nonexistent".to_string()),
+            branch_names: vec!["wip/test-user/2024-03-19-09-00-00".to_string()],
             all: false,
+            all_users: false,
             force: true,
             local_only: false,
+            remote: None,
+            format: OutputFormat::Text,
         };
 
-        delete_wip_branches_with_git(&mock_git, options).await?;
-        Ok(())
+        let error = delete_wip_branches_with_git(&mock_git, options)
+            .await
+            .unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("'wip/test-user/2024-03-19-09-00-00'"));
+    }
+
+    #[tokio::test]
+    async fn test_delete_unfetched_branch() -> Result<()> {
+        let mut mock_git = MockGit::new();
+        mock_git
+            .expect_execute()
+            .with(mockall::predicate::eq(vec![
+                "config".to_string(),
+                "user.name".to_string(),
+            ]))
+            .returning(|_| Ok("test-user".to_string()));
+
+        // Pushed from another machine and not fetched, it is only known once fetched
+        let mut seq = mockall::Sequence::new();
+        mock_git
+            .expect_get_user_wip_branches()
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|_| Ok(Vec::new()));
+        mock_git
+            .expect_fetch_branch()
+            .with(
+                mockall::predicate::eq("origin"),
+                mockall::predicate::eq("wip/test-user/2024-03-21-17-59-30"),
+            )
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|_, _| Ok(true));
+        mock_git
+            .expect_get_user_wip_branches()
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|_| {
+                Ok(vec![WipBranch {
+                    name: "wip/test-user/2024-03-21-17-59-30".to_string(),
+                    user: "test-user".to_string(),
+                    timestamp: "2024-03-21-17-59-30".to_string(),
+                    local: false,
+                    remotes: vec!["origin".to_string()],
+                }])
+            });
+        expect_wip_commits(&mut mock_git);
+        mock_git
+            .expect_get_remotes()
+            .returning(|| Ok(vec!["origin".to_string()]));
+        mock_git.expect_get_config_value().returning(|_| Ok(None));
+        mock_git.expect_delete_branch().never();
+        mock_git
+            .expect_delete_remote_branch()
+            .with(
+                mockall::predicate::eq("origin"),
+                mockall::predicate::eq("wip/test-user/2024-03-21-17-59-30"),
+            )
+            .times(1)
+            .returning(|_, _| Ok("".to_string()));
+
+        let options = DeleteOptions {
+            branch_names: vec!["wip/test-user/2024-03-21-17-59-30".to_string()],
+            all: false,
+            all_users: false,
+            force: true,
+            local_only: false,
+            remote: None,
+            format: OutputFormat::Text,
+        };
+        delete_wip_branches_with_git(&mock_git, options).await
     }
 
     #[tokio::test]
@@ -358,31 +653,92 @@ mod tests {
             .returning(|_| Ok("test-user".to_string()));
 
         // Mock WIP branches
-        mock_git
-            .expect_get_user_wip_branches()
-            .with(mockall::predicate::eq("test-user"))
-            .returning(|_| Ok(vec!["wip/test-user/branch1".to_string()]));
+        mock_wip_branches(&mut mock_git, &["wip/test-user/2024-03-21-17-59-30"]);
 
         // Mock only local branch deletion
         mock_git
             .expect_delete_branch()
             .with(
-                mockall::predicate::eq("wip/test-user/branch1"),
+                mockall::predicate::eq("wip/test-user/2024-03-21-17-59-30"),
                 mockall::predicate::eq(true),
             )
             .returning(|_, _| Ok("Deleted branch".to_string()));
 
         let options = DeleteOptions {
-            branch_name: Some("wip/test-user/branch1".to_string()),
+            branch_names: vec!["wip/test-user/2024-03-21-17-59-30".to_string()],
             all: false,
+            all_users: false,
             force: true,
             local_only: true,
+            remote: None,
+            format: OutputFormat::Text,
         };
 
         delete_wip_branches_with_git(&mock_git, options).await?;
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_delete_remote_only_branch() -> Result<()> {
+        let mut mock_git = MockGit::new();
+        mock_git
+            .expect_execute()
+            .with(mockall::predicate::eq(vec![
+                "config".to_string(),
+                "user.name".to_string(),
+            ]))
+            .returning(|_| Ok("test-user".to_string()));
+
+        // Pushed from another machine, there is no local branch to delete
+        mock_git.expect_get_user_wip_branches().returning(|_| {
+            Ok(vec![WipBranch {
+                name: "wip/test-user/2024-03-21-17-59-30".to_string(),
+                user: "test-user".to_string(),
+                timestamp: "2024-03-21-17-59-30".to_string(),
+                local: false,
+                remotes: vec!["origin".to_string()],
+            }])
+        });
+        expect_wip_commits(&mut mock_git);
+        mock_git.expect_delete_branch().never();
+        mock_git
+            .expect_get_remotes()
+            .returning(|| Ok(vec!["origin".to_string()]));
+        mock_git.expect_get_config_value().returning(|_| Ok(None));
+        mock_git
+            .expect_delete_remote_branch()
+            .with(
+                mockall::predicate::eq("origin"),
+                mockall::predicate::eq("wip/test-user/2024-03-21-17-59-30"),
+            )
+            .times(1)
+            .returning(|_, _| Ok("Deleted remote branch".to_string()));
+
+        let options = DeleteOptions {
+            branch_names: vec!["wip/test-user/2024-03-21-17-59-30".to_string()],
+            all: false,
+            all_users: false,
+            force: true,
+            local_only: false,
+            remote: None,
+            format: OutputFormat::Text,
+        };
+
+        delete_wip_branches_with_git(&mock_git, options).await?;
+
+        // With --local-only it is left alone
+        let options = DeleteOptions {
+            branch_names: vec!["wip/test-user/2024-03-21-17-59-30".to_string()],
+            all: false,
+            all_users: false,
+            force: true,
+            local_only: true,
+            remote: None,
+            format: OutputFormat::Text,
+        };
+        delete_wip_branches_with_git(&mock_git, options).await
+    }
+
     #[tokio::test]
     async fn test_interactive_delete() -> Result<()> {
         setup();
@@ -398,16 +754,13 @@ mod tests {
             .returning(|_| Ok("test-user".to_string()));
 
         // Mock WIP branches
-        mock_git
-            .expect_get_user_wip_branches()
-            .with(mockall::predicate::eq("test-user"))
-            .returning(|_| Ok(vec!["wip/test-user/branch1".to_string()]));
+        mock_wip_branches(&mut mock_git, &["wip/test-user/2024-03-21-17-59-30"]);
 
         // Mock local branch deletion
         mock_git
             .expect_delete_branch()
             .with(
-                mockall::predicate::eq("wip/test-user/branch1"),
+                mockall::predicate::eq("wip/test-user/2024-03-21-17-59-30"),
                 mockall::predicate::eq(true),
             )
             .returning(|_, _| Ok("Deleted branch".to_string()));
@@ -416,21 +769,25 @@ mod tests {
         mock_git
             .expect_get_remotes()
             .returning(|| Ok(vec!["origin".to_string()]));
+        mock_git.expect_get_config_value().returning(|_| Ok(None));
 
         // Mock remote branch deletion
         mock_git
             .expect_delete_remote_branch()
             .with(
                 mockall::predicate::eq("origin"),
-                mockall::predicate::eq("wip/test-user/branch1"),
+                mockall::predicate::eq("wip/test-user/2024-03-21-17-59-30"),
             )
             .returning(|_, _| Ok("".to_string()));
 
         let options = DeleteOptions {
-            branch_name: None,
+            branch_names: Vec::new(),
             all: false,
+            all_users: false,
             force: true,
             local_only: false,
+            remote: None,
+            format: OutputFormat::Text,
         };
 
         delete_wip_branches_with_git(&mock_git, options).await?;
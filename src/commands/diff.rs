@@ -0,0 +1,368 @@
+use crate::commands::restore::select_wip_branch;
+use crate::commands::save::{collect_metadata, snapshot_trees};
+use crate::i18n::{t, t_with_args};
+use crate::output::{Output, OutputFormat};
+use crate::utils::{open_git, parse_commit_message, Git, WipMetadata};
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+pub struct DiffOptions {
+    pub branch_name: Option<String>,
+    pub other_branch: Option<String>,
+    pub stat: bool,
+    pub format: OutputFormat,
+}
+
+/// How a file differs on each side, as printed by `diff --format json`.
+#[derive(Serialize, Debug, PartialEq)]
+struct FileComparison {
+    path: String,
+    from: Vec<&'static str>,
+    to: Vec<&'static str>,
+}
+
+/// A comparison as printed by `diff --format json`. `to` is `None` for the
+/// working tree.
+#[derive(Serialize, Default)]
+struct DiffResult {
+    from: Option<String>,
+    to: Option<String>,
+    files: Vec<FileComparison>,
+    diff: Option<String>,
+}
+
+/// Compares a WIP branch to the working tree or to another WIP branch.
+///
+/// # Arguments
+/// * `options` - Configuration for the diff operation
+///   - `branch_name`: Optional name of the WIP branch to compare
+///   - `other_branch`: WIP branch to compare with instead of the working tree
+///   - `stat`: Show a diffstat instead of the full patch
+///   - `format`: Print the comparison or a JSON record
+///
+/// # Details
+/// * Lists every file either side recorded with its state on both sides
+/// * The working tree is snapshotted like `save` does, untracked files included,
+///   without touching the index
+/// * The diff goes through git's pager and colors
+pub async fn diff_wip_branch(options: DiffOptions) -> Result<()> {
+    let git = open_git();
+    diff_wip_branch_with_git(&git, options).await
+}
+
+pub async fn diff_wip_branch_with_git(git: &impl Git, options: DiffOptions) -> Result<()> {
+    let output = Output::with_format(options.format).await?;
+    let Some(from) = select_wip_branch(
+        git,
+        &output,
+        options.branch_name,
+        None,
+        "Select a WIP branch to compare",
+    )
+    .await?
+    else {
+        return output.result(&DiffResult::default());
+    };
+    let source = from.reference();
+    let from = from.name;
+
+    let from_metadata = parse_commit_message(&git.get_commit_message(&source).await?);
+    let (to, to_metadata, target) = match options.other_branch {
+        Some(other) => {
+            let Some(other) = select_wip_branch(
+                git,
+                &output,
+                Some(other),
+                None,
+                "Select a WIP branch to compare",
+            )
+            .await?
+            else {
+                return output.result(&DiffResult::default());
+            };
+            let reference = other.reference();
+            let metadata = parse_commit_message(&git.get_commit_message(&reference).await?);
+            (Some(other.name), metadata, reference)
+        }
+        None => {
+            let head = git.get_head().await?;
+            let metadata = collect_metadata(git, &head, None).await?;
+            let (_, worktree_tree) = snapshot_trees(git).await?;
+            (None, metadata, worktree_tree)
+        }
+    };
+
+    let files = compare_files(&file_states(&from_metadata), &file_states(&to_metadata));
+    let diff_args = vec![
+        "diff".to_string(),
+        if options.stat { "--stat" } else { "--patch" }.to_string(),
+        source,
+        target,
+    ];
+
+    if output.is_json() {
+        let diff = git.execute(diff_args).await?;
+        return output.result(&DiffResult {
+            from: Some(from),
+            to,
+            files,
+            diff: Some(diff),
+        });
+    }
+
+    let to_label = match &to {
+        Some(other) => format!("'{}'", other),
+        None => t("diff-working-tree"),
+    };
+    let heading = t_with_args("diff-heading", &[("from", &from), ("to", &to_label)]);
+    let from_label = format!("'{}'", from);
+    let highlights: Vec<&str> = match &to {
+        Some(_) => vec![&from_label, &to_label],
+        None => vec![&from_label],
+    };
+    output.info(&output.format_with_highlights(&heading, &highlights))?;
+
+    if files.is_empty() {
+        output.info(&t("diff-no-files"))?;
+    } else {
+        let mut rows = vec![vec![
+            t("diff-file-column"),
+            from.clone(),
+            to.unwrap_or_else(|| t("diff-working-tree")),
+        ]];
+        rows.extend(files.iter().map(|file| {
+            vec![
+                file.path.clone(),
+                state_label(&file.from),
+                state_label(&file.to),
+            ]
+        }));
+        output.info("")?;
+        for line in output.format_table(&rows) {
+            output.info(&line)?;
+        }
+    }
+
+    output.info("")?;
+    git.execute_in_terminal(diff_args).await
+}
+
+/// Maps every recorded file to the states it is in, e.g. a partially staged
+/// file is both `staged` and `changed`.
+fn file_states(metadata: &WipMetadata) -> BTreeMap<&str, Vec<&'static str>> {
+    let mut states: BTreeMap<&str, Vec<&'static str>> = BTreeMap::new();
+    for (state, files) in [
+        ("staged", &metadata.staged_files),
+        ("changed", &metadata.changed_files),
+        ("untracked", &metadata.untracked_files),
+    ] {
+        for file in files {
+            states.entry(file.as_str()).or_default().push(state);
+        }
+    }
+    states
+}
+
+/// Lines up the files of both sides, sorted by path.
+fn compare_files(
+    from: &BTreeMap<&str, Vec<&'static str>>,
+    to: &BTreeMap<&str, Vec<&'static str>>,
+) -> Vec<FileComparison> {
+    let mut paths: Vec<&str> = from.keys().chain(to.keys()).copied().collect();
+    paths.sort_unstable();
+    paths.dedup();
+
+    paths
+        .into_iter()
+        .map(|path| FileComparison {
+            path: path.to_string(),
+            from: from.get(path).cloned().unwrap_or_default(),
+            to: to.get(path).cloned().unwrap_or_default(),
+        })
+        .collect()
+}
+
+/// Translates the states of a file for the table, `-` if it wasn't recorded.
+fn state_label(states: &[&str]) -> String {
+    if states.is_empty() {
+        return "-".to_string();
+    }
+    states
+        .iter()
+        .map(|state| t(&format!("diff-state-{}", state)))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{Head, MockGit, WipBranch};
+    use mockall::predicate::eq;
+
+    const FROM: &str = "wip/test-user/2024-03-21-17-59-30";
+    const TO: &str = "wip/test-user/2024-03-22-09-00-00";
+
+    fn mock_wip_branches() -> MockGit {
+        let mut mock_git = MockGit::new();
+        mock_git
+            .expect_execute()
+            .with(eq(vec!["config".to_string(), "user.name".to_string()]))
+            .returning(|_| Ok("test-user".to_string()));
+        mock_git.expect_get_user_wip_branches().returning(|_| {
+            Ok([FROM, TO]
+                .into_iter()
+                .map(|name| WipBranch {
+                    name: name.to_string(),
+                    user: "test-user".to_string(),
+                    timestamp: name.rsplit('/').next().unwrap().to_string(),
+                    local: true,
+                    remotes: Vec::new(),
+                })
+                .collect())
+        });
+        mock_git
+            .expect_get_commit_message()
+            .with(eq(FROM))
+            .returning(|_| {
+                Ok("chore: saving work in progress
+
+Wippy-Version: 1
+Wippy-Source-Branch: main
+Wippy-Staged: a.txt
+Wippy-Changed: a.txt
+Wippy-Untracked: b.txt"
+                    .to_string())
+            });
+        mock_git
+    }
+
+    #[test]
+    fn test_compare_files() {
+        let from = WipMetadata {
+            staged_files: vec!["a.txt".to_string()],
+            changed_files: vec!["a.txt".to_string(), "c.txt".to_string()],
+            ..Default::default()
+        };
+        let to = WipMetadata {
+            untracked_files: vec!["b.txt".to_string()],
+            changed_files: vec!["c.txt".to_string()],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            compare_files(&file_states(&from), &file_states(&to)),
+            vec![
+                FileComparison {
+                    path: "a.txt".to_string(),
+                    from: vec!["staged", "changed"],
+                    to: vec![],
+                },
+                FileComparison {
+                    path: "b.txt".to_string(),
+                    from: vec![],
+                    to: vec!["untracked"],
+                },
+                FileComparison {
+                    path: "c.txt".to_string(),
+                    from: vec!["changed"],
+                    to: vec!["changed"],
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_diff_against_working_tree() -> Result<()> {
+        let mut mock_git = mock_wip_branches();
+
+        // The working tree is described and snapshotted like a save would
+        mock_git.expect_get_head().returning(|| {
+            Ok(Head {
+                branch: Some("main".to_string()),
+                commit: Some("base-sha".to_string()),
+            })
+        });
+        mock_git
+            .expect_get_staged_files()
+            .returning(|| Ok(Vec::new()));
+        mock_git
+            .expect_get_changed_files()
+            .returning(|| Ok(vec!["a.txt".to_string()]));
+        mock_git
+            .expect_get_untracked_files()
+            .returning(|| Ok(Vec::new()));
+        mock_git
+            .expect_git_dir()
+            .returning(|| Ok("/repo/.git".to_string()));
+        let mut added = false;
+        mock_git
+            .expect_execute_with_env()
+            .returning(move |args, _| {
+                Ok(match args[0].as_str() {
+                    "write-tree" if added => "worktree-tree".to_string(),
+                    "write-tree" => "index-tree".to_string(),
+                    _ => {
+                        added = true;
+                        String::new()
+                    }
+                })
+            });
+
+        mock_git
+            .expect_execute_in_terminal()
+            .with(eq(vec![
+                "diff".to_string(),
+                "--stat".to_string(),
+                FROM.to_string(),
+                "worktree-tree".to_string(),
+            ]))
+            .times(1)
+            .returning(|_| Ok(()));
+
+        diff_wip_branch_with_git(
+            &mock_git,
+            DiffOptions {
+                branch_name: Some(FROM.to_string()),
+                other_branch: None,
+                stat: true,
+                format: OutputFormat::Text,
+            },
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_diff_two_wips_as_json() -> Result<()> {
+        let mut mock_git = mock_wip_branches();
+        mock_git
+            .expect_get_commit_message()
+            .with(eq(TO))
+            .returning(|_| Ok("chore: saving work in progress\n\nWippy-Version: 1".to_string()));
+
+        // Nothing is snapshotted when comparing two saved WIPs
+        mock_git.expect_execute_with_env().never();
+        mock_git
+            .expect_execute()
+            .with(eq(vec![
+                "diff".to_string(),
+                "--patch".to_string(),
+                FROM.to_string(),
+                TO.to_string(),
+            ]))
+            .times(1)
+            .returning(|_| Ok("diff --git a/a.txt b/a.txt".to_string()));
+
+        diff_wip_branch_with_git(
+            &mock_git,
+            DiffOptions {
+                branch_name: Some(FROM.to_string()),
+                other_branch: Some(TO.to_string()),
+                stat: false,
+                format: OutputFormat::Json,
+            },
+        )
+        .await
+    }
+}
@@ -1,40 +1,321 @@
 use crate::i18n::t_with_args;
-use crate::output::Output;
-use crate::utils::{git_username_with_git, Git, GitCommand};
+use crate::output::{Output, OutputFormat};
+use crate::utils::{
+    format_age, git_username_with_git, open_git, parse_commit_message, parse_formatted_datetime,
+    short_commit, wip_remote_with_git, Git, WipBranch, WipMetadata,
+};
 use anyhow::Result;
+use chrono::{DateTime, Local, TimeDelta};
+use serde::Serialize;
+use std::collections::BTreeMap;
 
-pub async fn list_wip_branches() -> Result<()> {
-    let git = GitCommand::new();
-    list_wip_branches_with_git(&git).await
+pub struct ListOptions {
+    pub all: bool,
+    pub remote: Option<String>,
+    pub format: OutputFormat,
 }
 
-pub async fn list_wip_branches_with_git(git: &impl Git) -> Result<()> {
-    let output = Output::new().await?;
+/// Lists WIP branches.
+///
+/// # Arguments
+/// * `options` - Configuration for the list operation
+///   - `all`: List the WIP branches of every user, grouped by user
+///   - `remote`: Only show remote copies on this remote, instead of on every remote
+///   - `format`: Print a table or a JSON array of WIP records
+pub async fn list_wip_branches(options: ListOptions) -> Result<()> {
+    let git = open_git();
+    list_wip_branches_with_git(&git, options).await
+}
+
+pub async fn list_wip_branches_with_git(git: &impl Git, options: ListOptions) -> Result<()> {
+    let output = Output::with_format(options.format).await?;
+
+    let wip_branches = find_wip_branches(git, options.remote.as_deref()).await?;
+
+    if options.all {
+        return list_all_wip_branches(git, &output, wip_branches).await;
+    }
+
     let username = git_username_with_git(git).await?;
-    let wip_branches = git.get_user_wip_branches(&username).await?;
+    let wip_branches: Vec<WipBranch> = wip_branches
+        .into_iter()
+        .filter(|branch| branch.user == username)
+        .collect();
 
     if wip_branches.is_empty() {
         let message = t_with_args("no-wip-branches", &[("username", &username)]);
         output.info(&output.format_with_highlights(&message, &[&username]))?;
-        return Ok(());
+        return output.result(&Vec::<WipRecord>::new());
+    }
+
+    let mut summaries = Vec::with_capacity(wip_branches.len());
+    for branch in wip_branches {
+        summaries.push(WipSummary::new(git, branch).await);
+    }
+    sort_newest_first(&mut summaries);
+
+    if output.is_json() {
+        let records: Vec<WipRecord> = summaries.iter().map(WipSummary::record).collect();
+        return output.result(&records);
     }
 
+    let mut rows = vec![table_header()];
+    rows.extend(summaries.iter().map(WipSummary::table_row));
+
     output.info(&t_with_args("found-wip-branches", &[]))?;
+    for line in output.format_table(&rows) {
+        output.info(&line)?;
+    }
+
+    Ok(())
+}
+
+/// Finds the WIP branches of all users, local and on every remote.
+///
+/// With a remote, copies on other remotes are ignored and branches that only
+/// exist there are left out.
+async fn find_wip_branches(git: &impl Git, remote: Option<&str>) -> Result<Vec<WipBranch>> {
+    let mut wip_branches = git.get_all_wip_branches().await?;
+
+    if let Some(remote) = remote {
+        // Fails for a remote that does not exist
+        wip_remote_with_git(git, Some(remote)).await?;
+        for branch in &mut wip_branches {
+            branch.remotes.retain(|name| name == remote);
+        }
+        wip_branches.retain(|branch| branch.local || !branch.remotes.is_empty());
+    }
+
+    Ok(wip_branches)
+}
+
+/// Lists the WIP branches of all users, local and remote, grouped by user.
+async fn list_all_wip_branches(
+    git: &impl Git,
+    output: &Output,
+    wip_branches: Vec<WipBranch>,
+) -> Result<()> {
+    if wip_branches.is_empty() {
+        output.info(&t_with_args("no-wip-branches-all", &[]))?;
+        return output.result(&Vec::<WipRecord>::new());
+    }
+
+    let mut summaries_by_user: BTreeMap<String, Vec<WipSummary>> = BTreeMap::new();
     for branch in wip_branches {
+        summaries_by_user
+            .entry(branch.user.clone())
+            .or_default()
+            .push(WipSummary::new(git, branch).await);
+    }
+
+    for summaries in summaries_by_user.values_mut() {
+        sort_newest_first(summaries);
+    }
+
+    if output.is_json() {
+        let records: Vec<WipRecord> = summaries_by_user
+            .values()
+            .flatten()
+            .map(WipSummary::record)
+            .collect();
+        return output.result(&records);
+    }
+
+    // Render all rows as one table so the columns line up across users
+    let mut rows = vec![table_header()];
+    for summaries in summaries_by_user.values() {
+        rows.extend(summaries.iter().map(WipSummary::table_row));
+    }
+    let mut lines = output.format_table(&rows).into_iter();
+
+    output.info(&t_with_args("found-wip-branches-all", &[]))?;
+    if let Some(header) = lines.next() {
+        output.info(&format!("  {}", header))?;
+    }
+    for (user, summaries) in &summaries_by_user {
         output.info(&output.format_with_highlights(
-            &t_with_args("branch-name", &[("name", &branch)]),
-            &[&branch],
+            &t_with_args("wip-user-heading", &[("username", user)]),
+            &[user],
         ))?;
+        for line in lines.by_ref().take(summaries.len()) {
+            output.info(&format!("  {}", line))?;
+        }
     }
 
     Ok(())
 }
 
+/// A WIP branch as printed by `list --format json`.
+#[derive(Serialize)]
+struct WipRecord {
+    branch: String,
+    user: String,
+    timestamp: String,
+    source_branch: Option<String>,
+    source_commit: Option<String>,
+    message: Option<String>,
+    staged_files: Vec<String>,
+    changed_files: Vec<String>,
+    untracked_files: Vec<String>,
+    age_seconds: Option<i64>,
+    local: bool,
+    remotes: Vec<String>,
+}
+
+/// A WIP branch together with the details shown by `list` and used by `prune`.
+pub(crate) struct WipSummary {
+    pub(crate) branch: WipBranch,
+    pub(crate) metadata: WipMetadata,
+    pub(crate) age: Option<TimeDelta>,
+}
+
+impl WipSummary {
+    /// Reads the metadata and age of a WIP branch.
+    ///
+    /// The age is taken from the timestamp in the branch name, falling back to
+    /// the commit date for branches saved by older versions with a free-form
+    /// `--datetime`, and for branches with more than one snapshot, including
+    /// ones `watch` dropped the older snapshots of.
+    pub(crate) async fn new(git: &impl Git, branch: WipBranch) -> Self {
+        let reference = branch.reference();
+        let metadata = git
+            .get_commit_message(&reference)
+            .await
+            .map(|message| parse_commit_message(&message))
+            .unwrap_or_default();
+
+        let now = Local::now();
+        let age = match parse_formatted_datetime(&branch.timestamp) {
+            // A WIP updated with `save --update` is as old as its latest snapshot
+            Some(saved_at)
+                if metadata.previous_snapshot.is_none() && metadata.dropped_snapshots == 0 =>
+            {
+                Some(now.naive_local() - saved_at)
+            }
+            _ => git
+                .get_commit_time(&reference)
+                .await
+                .ok()
+                .and_then(|timestamp| DateTime::from_timestamp(timestamp, 0))
+                .map(|saved_at| now.to_utc() - saved_at),
+        };
+
+        Self {
+            branch,
+            metadata,
+            age,
+        }
+    }
+
+    /// Describes where the branch exists, e.g. `local`, `origin` or `local, origin`.
+    fn location(&self) -> String {
+        let mut locations = Vec::new();
+        if self.branch.local {
+            locations.push(t_with_args("location-local", &[]));
+        }
+        locations.extend(self.branch.remotes.iter().cloned());
+        locations.join(", ")
+    }
+
+    fn record(&self) -> WipRecord {
+        let source_branch = Some(self.metadata.source_branch.clone()).filter(|s| !s.is_empty());
+        WipRecord {
+            branch: self.branch.name.clone(),
+            user: self.branch.user.clone(),
+            timestamp: self.branch.timestamp.clone(),
+            source_branch,
+            source_commit: self.metadata.source_commit.clone(),
+            message: self.metadata.message.clone(),
+            staged_files: self.metadata.staged_files.clone(),
+            changed_files: self.metadata.changed_files.clone(),
+            untracked_files: self.metadata.untracked_files.clone(),
+            age_seconds: self.age.map(|age| age.num_seconds().max(0)),
+            local: self.branch.local,
+            remotes: self.branch.remotes.clone(),
+        }
+    }
+
+    fn table_row(&self) -> Vec<String> {
+        // Work saved on a detached HEAD shows the commit it was saved on
+        let source = match (&self.metadata.source_branch, &self.metadata.source_commit) {
+            (branch, _) if !branch.is_empty() => branch.clone(),
+            (_, Some(commit)) => short_commit(commit).to_string(),
+            _ => "?".to_string(),
+        };
+        vec![
+            self.branch.name.clone(),
+            source,
+            self.age.map(format_age).unwrap_or_else(|| "?".to_string()),
+            self.metadata.staged_files.len().to_string(),
+            self.metadata.changed_files.len().to_string(),
+            self.metadata.untracked_files.len().to_string(),
+            self.location(),
+            self.metadata.message.clone().unwrap_or_default(),
+        ]
+    }
+}
+
+fn table_header() -> Vec<String> {
+    [
+        "list-column-branch",
+        "list-column-source",
+        "list-column-age",
+        "list-column-staged",
+        "list-column-changed",
+        "list-column-untracked",
+        "list-column-location",
+        "list-column-message",
+    ]
+    .iter()
+    .map(|key| t_with_args(key, &[]))
+    .collect()
+}
+
+/// Sorts summaries newest first, with branches of unknown age last.
+pub(crate) fn sort_newest_first(summaries: &mut [WipSummary]) {
+    summaries.sort_by(|a, b| match (a.age, b.age) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => a.branch.name.cmp(&b.branch.name),
+    });
+}
+
+/// Builds the display label for a WIP branch, including its message if one was saved.
+///
+/// Branches whose commit can't be read (e.g. remote-only branches) are shown by name only.
+pub(crate) async fn branch_label(git: &impl Git, branch: &str) -> String {
+    let message = git
+        .get_commit_message(branch)
+        .await
+        .ok()
+        .and_then(|commit_message| parse_commit_message(&commit_message).message);
+
+    match message {
+        Some(message) => t_with_args(
+            "branch-with-message",
+            &[("name", branch), ("message", &message)],
+        ),
+        None => t_with_args("branch-name", &[("name", branch)]),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::utils::MockGit;
 
+    fn wip_branch(name: &str, local: bool, remotes: Vec<&str>) -> WipBranch {
+        let (user, timestamp) = name.strip_prefix("wip/").unwrap().split_once('/').unwrap();
+        WipBranch {
+            name: name.to_string(),
+            user: user.to_string(),
+            timestamp: timestamp.to_string(),
+            local,
+            remotes: remotes.into_iter().map(String::from).collect(),
+        }
+    }
+
     #[tokio::test]
     async fn test_list_wip_branches() -> Result<()> {
         let mut mock_git = MockGit::new();
@@ -48,16 +329,243 @@ mod tests {
             ]))
             .returning(|_| Ok("test-user".to_string()));
 
-        // Mock WIP branches
+        // Mock WIP branches, including one of another user
+        mock_git.expect_get_all_wip_branches().returning(|| {
+            Ok(vec![
+                wip_branch("wip/other-user/2024-03-21-17-59-30", true, vec![]),
+                wip_branch("wip/test-user/2024-03-21-17-59-30", true, vec!["origin"]),
+                wip_branch("wip/test-user/2024-03-21-17-59-30", false, vec!["origin"]),
+            ])
+        });
+
+        // Mock commit message lookups, remote-only branches are read through their remote ref
+        mock_git
+            .expect_get_commit_message()
+            .with(mockall::predicate::eq("wip/test-user/2024-03-21-17-59-30"))
+            .returning(|_| {
+                Ok(
+                    "chore: saving work in progress\n\nSource branch: main\nChanges:\n\ta.txt"
+                        .to_string(),
+                )
+            });
+        mock_git
+            .expect_get_commit_message()
+            .with(mockall::predicate::eq(
+                "origin/wip/test-user/2024-03-21-17-59-30",
+            ))
+            .returning(|_| {
+                Ok("chore: saving work in progress\n\nSource branch: feature/x".to_string())
+            });
+
+        // Branches saved with a custom datetime fall back to the commit date
+        mock_git
+            .expect_get_commit_time()
+            .with(mockall::predicate::eq("wip/test-user/2024-03-21-17-59-30"))
+            .returning(|_| Ok(1_700_000_000));
+
+        list_wip_branches_with_git(
+            &mock_git,
+            ListOptions {
+                all: false,
+                remote: None,
+                format: OutputFormat::Text,
+            },
+        )
+        .await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_list_all_wip_branches() -> Result<()> {
+        let mut mock_git = MockGit::new();
+
+        // Mock WIP branches of several users, one of them only on the remote
+        mock_git.expect_get_all_wip_branches().returning(|| {
+            Ok(vec![
+                wip_branch("wip/jane/2024-03-21-17-59-30", true, vec!["origin"]),
+                wip_branch("wip/john/2024-03-20-09-00-00", false, vec!["origin"]),
+            ])
+        });
+
+        // Remote-only branches are read through their remote ref
+        mock_git
+            .expect_get_commit_message()
+            .with(mockall::predicate::eq("wip/jane/2024-03-21-17-59-30"))
+            .returning(|_| Ok("chore: saving work in progress\n\nSource branch: main".to_string()));
+        mock_git
+            .expect_get_commit_message()
+            .with(mockall::predicate::eq(
+                "origin/wip/john/2024-03-20-09-00-00",
+            ))
+            .returning(|_| {
+                Ok("chore: saving work in progress\n\nSource branch: feature/x".to_string())
+            });
+
+        list_wip_branches_with_git(
+            &mock_git,
+            ListOptions {
+                all: true,
+                remote: None,
+                format: OutputFormat::Text,
+            },
+        )
+        .await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_list_all_wip_branches_empty() -> Result<()> {
+        let mut mock_git = MockGit::new();
+
         mock_git
-            .expect_get_user_wip_branches()
-            .with(mockall::predicate::eq("test-user"))
-            .returning(|_| Ok(vec!["wip/test-user/branch1".to_string()]));
+            .expect_get_all_wip_branches()
+            .returning(|| Ok(vec![]));
 
-        list_wip_branches_with_git(&mock_git).await?;
+        list_wip_branches_with_git(
+            &mock_git,
+            ListOptions {
+                all: true,
+                remote: None,
+                format: OutputFormat::Text,
+            },
+        )
+        .await?;
         Ok(())
     }
 
+    #[test]
+    fn test_sort_newest_first() {
+        let summary = |name: &str, age: Option<TimeDelta>| WipSummary {
+            branch: wip_branch(name, true, vec![]),
+            metadata: WipMetadata::default(),
+            age,
+        };
+        let mut summaries = vec![
+            summary("wip/a/before-release", None),
+            summary("wip/a/2024-03-18-09-00-00", Some(TimeDelta::days(3))),
+            summary("wip/a/2024-03-21-09-00-00", Some(TimeDelta::minutes(5))),
+        ];
+
+        sort_newest_first(&mut summaries);
+
+        let names: Vec<_> = summaries.iter().map(|s| s.branch.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "wip/a/2024-03-21-09-00-00",
+                "wip/a/2024-03-18-09-00-00",
+                "wip/a/before-release"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_age_of_updated_branch() {
+        let mut mock_git = MockGit::new();
+        let name = "wip/test-user/2024-03-21-17-59-30";
+        // Trimmed by `watch --keep-last 1`, the snapshot left has no previous one
+        mock_git
+            .expect_get_commit_message()
+            .with(mockall::predicate::eq(name))
+            .returning(|_| {
+                Ok(
+                    "chore: saving work in progress\n\nWippy-Version: 1\nWippy-Dropped: 5"
+                        .to_string(),
+                )
+            });
+        let saved_at = Local::now().timestamp() - 60;
+        mock_git
+            .expect_get_commit_time()
+            .with(mockall::predicate::eq(name))
+            .returning(move |_| Ok(saved_at));
+
+        let summary = WipSummary::new(&mock_git, wip_branch(name, true, vec![])).await;
+        let age = summary.age.unwrap();
+        assert!(age < TimeDelta::hours(1), "{}", age);
+    }
+
+    #[test]
+    fn test_table_row() {
+        let summary = WipSummary {
+            branch: wip_branch("wip/a/2024-03-21-17-59-30", true, vec!["origin"]),
+            metadata: WipMetadata {
+                version: 1,
+                source_branch: "main".to_string(),
+                message: Some("Login form".to_string()),
+                index_commit: None,
+                previous_snapshot: None,
+                dropped_snapshots: 0,
+                source_commit: Some("0123456789abcdef".to_string()),
+                staged_files: vec!["a.txt".to_string()],
+                changed_files: vec!["b.txt".to_string(), "c.txt".to_string()],
+                untracked_files: vec![],
+            },
+            age: Some(TimeDelta::hours(3)),
+        };
+
+        let row = summary.table_row();
+        assert_eq!(row[0], "wip/a/2024-03-21-17-59-30");
+        assert_eq!(row[1], "main");
+        assert_eq!(row[2], "3h");
+        assert_eq!(&row[3..6], &["1", "2", "0"]);
+        assert!(row[6].ends_with(", origin"));
+        assert_eq!(row[7], "Login form");
+
+        let record = serde_json::to_value(summary.record()).unwrap();
+        assert_eq!(record["branch"], "wip/a/2024-03-21-17-59-30");
+        assert_eq!(record["user"], "a");
+        assert_eq!(record["source_branch"], "main");
+        assert_eq!(record["source_commit"], "0123456789abcdef");
+        assert_eq!(
+            record["changed_files"],
+            serde_json::json!(["b.txt", "c.txt"])
+        );
+        assert_eq!(record["age_seconds"], 3 * 60 * 60);
+        assert_eq!(record["local"], true);
+        assert_eq!(record["remotes"], serde_json::json!(["origin"]));
+
+        // Saved on a detached HEAD
+        let summary = WipSummary {
+            metadata: WipMetadata {
+                source_branch: String::new(),
+                ..summary.metadata
+            },
+            ..summary
+        };
+        assert_eq!(summary.table_row()[1], "0123456");
+    }
+
+    #[tokio::test]
+    async fn test_branch_label_with_message() {
+        let mut mock_git = MockGit::new();
+
+        mock_git
+            .expect_get_commit_message()
+            .with(mockall::predicate::eq("wip/test-user/2024-03-21-17-59-30"))
+            .returning(|_| {
+                Ok(
+                    "chore: saving work in progress\n\nSource branch: main\nMessage: Login form"
+                        .to_string(),
+                )
+            });
+
+        let label = branch_label(&mock_git, "wip/test-user/2024-03-21-17-59-30").await;
+        assert!(label.contains("wip/test-user/2024-03-21-17-59-30"));
+        assert!(label.contains("Login form"));
+    }
+
+    #[tokio::test]
+    async fn test_branch_label_unreadable_commit() {
+        let mut mock_git = MockGit::new();
+
+        mock_git
+            .expect_get_commit_message()
+            .returning(|_| Err(anyhow::anyhow!("unknown revision")));
+
+        let label = branch_label(&mock_git, "wip/test-user/2024-03-21-17-59-30").await;
+        assert_eq!(label, "wip/test-user/2024-03-21-17-59-30");
+    }
+
     #[tokio::test]
     async fn test_list_wip_branches_empty() -> Result<()> {
         let mut mock_git = MockGit::new();
@@ -71,13 +579,24 @@ mod tests {
             ]))
             .returning(|_| Ok("test-user".to_string()));
 
-        // Mock WIP branches (empty)
-        mock_git
-            .expect_get_user_wip_branches()
-            .with(mockall::predicate::eq("test-user"))
-            .returning(|_| Ok(vec![]));
+        // Mock WIP branches (none of the current user)
+        mock_git.expect_get_all_wip_branches().returning(|| {
+            Ok(vec![wip_branch(
+                "wip/other-user/2024-03-21-17-59-30",
+                true,
+                vec![],
+            )])
+        });
 
-        list_wip_branches_with_git(&mock_git).await?;
+        list_wip_branches_with_git(
+            &mock_git,
+            ListOptions {
+                all: false,
+                remote: None,
+                format: OutputFormat::Text,
+            },
+        )
+        .await?;
         Ok(())
     }
 }
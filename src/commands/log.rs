@@ -0,0 +1,206 @@
+use crate::commands::restore::select_wip_branch;
+use crate::i18n::t_with_args;
+use crate::output::{Output, OutputFormat};
+use crate::utils::{format_age, list_snapshots, open_git, short_commit, Git, Snapshot};
+use anyhow::Result;
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::Serialize;
+
+pub struct LogOptions {
+    pub branch_name: Option<String>,
+    pub format: OutputFormat,
+}
+
+/// The snapshots of a WIP branch, as printed by `log --format json`.
+#[derive(Serialize, Default)]
+struct LogResult {
+    branch: Option<String>,
+    snapshots: Vec<SnapshotRecord>,
+}
+
+#[derive(Serialize)]
+struct SnapshotRecord {
+    /// What `restore` and `show` accept for the snapshot, e.g. `wip/jane/...@{1}`
+    snapshot: String,
+    commit: String,
+    source_branch: Option<String>,
+    source_commit: Option<String>,
+    message: Option<String>,
+    staged_files: Vec<String>,
+    changed_files: Vec<String>,
+    untracked_files: Vec<String>,
+    age_seconds: Option<i64>,
+}
+
+/// Lists the snapshots of a WIP branch, newest first.
+///
+/// # Arguments
+/// * `options` - Configuration for the log operation
+///   - `branch_name`: Optional name of the branch, prompted for if omitted
+///   - `format`: Print a table or a JSON record per snapshot
+///
+/// # Details
+/// * Every `save --update` adds a snapshot on top of the previous one
+/// * Snapshots are named `<branch>@{n}`, `n` saves before the latest one, which
+///   `restore` and `show` accept
+pub async fn log_wip_branch(options: LogOptions) -> Result<()> {
+    let git = open_git();
+    log_wip_branch_with_git(&git, options).await
+}
+
+pub async fn log_wip_branch_with_git(git: &impl Git, options: LogOptions) -> Result<()> {
+    let output = Output::with_format(options.format).await?;
+    let Some(wip_branch) = select_wip_branch(
+        git,
+        &output,
+        options.branch_name,
+        None,
+        "Select a WIP branch to list the snapshots of",
+    )
+    .await?
+    else {
+        return output.result(&LogResult::default());
+    };
+
+    let now = Utc::now();
+    let mut result = LogResult {
+        branch: Some(wip_branch.name.clone()),
+        snapshots: Vec::new(),
+    };
+    let snapshots = list_snapshots(git, &wip_branch.reference()).await?;
+    for (index, Snapshot { commit, metadata }) in snapshots.into_iter().enumerate() {
+        let age = git
+            .get_commit_time(&commit)
+            .await
+            .ok()
+            .and_then(|timestamp| DateTime::from_timestamp(timestamp, 0))
+            .map(|saved_at| now - saved_at);
+        result.snapshots.push(SnapshotRecord {
+            snapshot: format!("{}@{{{}}}", wip_branch.name, index),
+            commit,
+            source_branch: Some(metadata.source_branch).filter(|branch| !branch.is_empty()),
+            source_commit: metadata.source_commit,
+            message: metadata.message,
+            staged_files: metadata.staged_files,
+            changed_files: metadata.changed_files,
+            untracked_files: metadata.untracked_files,
+            age_seconds: age.map(|age| age.num_seconds().max(0)),
+        });
+    }
+
+    if output.is_json() {
+        return output.result(&result);
+    }
+
+    let heading = t_with_args("log-heading", &[("name", &wip_branch.name)]);
+    output.info(&output.format_with_highlights(&heading, &[&format!("'{}'", wip_branch.name)]))?;
+    output.info("")?;
+    let mut rows = vec![[
+        "log-column-snapshot",
+        "log-column-commit",
+        "list-column-source",
+        "list-column-age",
+        "list-column-staged",
+        "list-column-changed",
+        "list-column-untracked",
+        "list-column-message",
+    ]
+    .iter()
+    .map(|key| t_with_args(key, &[]))
+    .collect()];
+    rows.extend(result.snapshots.iter().enumerate().map(|(index, record)| {
+        // Work saved on a detached HEAD shows the commit it was saved on
+        let source = match (&record.source_branch, &record.source_commit) {
+            (Some(branch), _) => branch.clone(),
+            (None, Some(commit)) => short_commit(commit).to_string(),
+            (None, None) => "?".to_string(),
+        };
+        vec![
+            format!("@{{{}}}", index),
+            short_commit(&record.commit).to_string(),
+            source,
+            record
+                .age_seconds
+                .map(|seconds| format_age(TimeDelta::seconds(seconds)))
+                .unwrap_or_else(|| "?".to_string()),
+            record.staged_files.len().to_string(),
+            record.changed_files.len().to_string(),
+            record.untracked_files.len().to_string(),
+            record.message.clone().unwrap_or_default(),
+        ]
+    }));
+    for line in output.format_table(&rows) {
+        output.info(&line)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{MockGit, WipBranch};
+    use mockall::predicate::eq;
+
+    const BRANCH: &str = "wip/test-user/2024-03-21-17-59-30";
+
+    #[tokio::test]
+    async fn test_log_lists_snapshots_newest_first() -> Result<()> {
+        let mut mock_git = MockGit::new();
+        mock_git
+            .expect_execute()
+            .with(eq(vec!["config".to_string(), "user.name".to_string()]))
+            .returning(|_| Ok("test-user".to_string()));
+        mock_git.expect_get_config_value().returning(|_| Ok(None));
+        mock_git.expect_get_user_wip_branches().returning(|_| {
+            Ok(vec![WipBranch {
+                name: BRANCH.to_string(),
+                user: "test-user".to_string(),
+                timestamp: "2024-03-21-17-59-30".to_string(),
+                local: true,
+                remotes: Vec::new(),
+            }])
+        });
+        mock_git
+            .expect_rev_parse()
+            .with(eq(BRANCH))
+            .returning(|_| Ok("second-sha".to_string()));
+        mock_git
+            .expect_get_commit_message()
+            .with(eq("second-sha"))
+            .times(1)
+            .returning(|_| {
+                Ok("chore: saving work in progress
+
+Wippy-Version: 1
+Wippy-Source-Branch: main
+Wippy-Previous: first-sha
+Wippy-Changed: a.txt"
+                    .to_string())
+            });
+        mock_git
+            .expect_get_commit_message()
+            .with(eq("first-sha"))
+            .times(1)
+            .returning(|_| {
+                Ok("chore: saving work in progress
+
+Wippy-Version: 1
+Wippy-Source-Branch: main
+Wippy-Untracked: b.txt"
+                    .to_string())
+            });
+        mock_git
+            .expect_get_commit_time()
+            .times(2)
+            .returning(|_| Ok(1_711_040_370));
+
+        log_wip_branch_with_git(
+            &mock_git,
+            LogOptions {
+                branch_name: Some(BRANCH.to_string()),
+                format: OutputFormat::Json,
+            },
+        )
+        .await
+    }
+}
@@ -6,12 +6,26 @@
 //! - `list`: Shows all WIP branches for the current user
 //! - `restore`: Restores changes from a WIP branch back to the original branch
 //! - `delete`: Removes WIP branches locally and/or remotely
+//! - `show`: Shows what a WIP branch holds without restoring it
+//! - `log`: Lists the snapshots added to a WIP branch with `save --update`
+//! - `diff`: Compares a WIP branch to the working tree or to another WIP branch
+//! - `sync`: Reconciles WIP branches between the repository and its remote
+//! - `prune`: Deletes WIP branches that are old, or whose source branch was merged
+//! - `watch`: Takes snapshots of the working tree while you work
+//! - `recover`: Rolls back a `save` or `restore` that was interrupted
 //!
 //! Each command is implemented in its own submodule and follows a pattern of having
 //! both a public interface function and a testable implementation that accepts a
 //! Git trait object.
 
 pub mod delete;
+pub mod diff;
 pub mod list;
+pub mod log;
+pub mod prune;
+pub mod recover;
 pub mod restore;
 pub mod save;
+pub mod show;
+pub mod sync;
+pub mod watch;
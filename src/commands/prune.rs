@@ -0,0 +1,609 @@
+use crate::commands::delete::{
+    delete_wip_branches_with_git, DeleteOptions, DeleteResult, FORCE_HINT,
+};
+use crate::commands::list::{sort_newest_first, WipSummary};
+use crate::i18n::t_with_args;
+use crate::output::{Output, OutputFormat};
+use crate::utils::{
+    format_age, git_username_with_git, open_git, parse_age, retain_wip_commits,
+    wip_remote_with_git, Git,
+};
+use anyhow::Result;
+use chrono::TimeDelta;
+use dialoguer::{theme::ColorfulTheme, Confirm};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+pub struct PruneOptions {
+    pub older_than: Option<String>,
+    pub source_merged: bool,
+    pub keep_last: Option<usize>,
+    pub all_users: bool,
+    pub dry_run: bool,
+    pub force: bool,
+    pub local_only: bool,
+    pub remote: Option<String>,
+    pub format: OutputFormat,
+}
+
+/// Why a WIP branch is pruned.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum PruneReason {
+    /// Saved longer ago than `--older-than`
+    OlderThan,
+    /// The branch it was saved on was merged into the default branch
+    SourceMerged,
+    /// The branch it was saved on no longer exists, locally or on the remote
+    SourceDeleted,
+    /// Not among the newest `--keep-last` WIP branches of its user
+    KeepLast,
+}
+
+impl PruneReason {
+    fn label(self) -> String {
+        let key = match self {
+            Self::OlderThan => "prune-older-than",
+            Self::SourceMerged => "prune-source-merged",
+            Self::SourceDeleted => "prune-source-deleted",
+            Self::KeepLast => "prune-keep-last",
+        };
+        t_with_args(key, &[])
+    }
+}
+
+/// The branches a dry run would prune, as printed by `--dry-run --format json`.
+#[derive(Serialize, Default)]
+struct PruneResult {
+    branches: Vec<PrunedBranch>,
+}
+
+#[derive(Serialize)]
+struct PrunedBranch {
+    branch: String,
+    user: String,
+    age_seconds: Option<i64>,
+    reason: PruneReason,
+}
+
+/// The branch that source branches count as merged into.
+struct MergeBase {
+    /// Branch name, e.g. `main`
+    name: String,
+    /// What to check merges against, e.g. `refs/remotes/origin/main`
+    reference: String,
+}
+
+/// Deletes WIP branches that are no longer needed.
+///
+/// # Arguments
+/// * `options` - Configuration for the prune operation
+///   - `older_than`: Prune branches saved longer ago than this, e.g. `30d`
+///   - `source_merged`: Prune branches whose source branch was merged or deleted
+///   - `keep_last`: Keep the newest N branches per user. On its own, prunes
+///     all others; with another policy, protects the newest N from it
+///   - `all_users`: Prune the WIP branches of every user, not only one's own
+///   - `dry_run`: Only show what would be pruned
+///   - `force`, `local_only`, `remote`: As for `delete`
+///   - `format`: Print a summary or, for a dry run, a JSON record per branch
+///
+/// # Details
+/// * Ages come from the timestamp in the branch name, as in `list`
+/// * Source branches count as merged once they are reachable from the remote's
+///   default branch, or from the current branch if the remote has none
+/// * Other users' source branches only count as deleted with `force`, as they
+///   may only ever have existed on another machine
+/// * The pruned branches are deleted like `delete` does, locally and on the remote
+pub async fn prune_wip_branches(options: PruneOptions) -> Result<()> {
+    let git = open_git();
+    prune_wip_branches_with_git(&git, options).await
+}
+
+pub async fn prune_wip_branches_with_git(git: &impl Git, options: PruneOptions) -> Result<()> {
+    let output = Output::with_format(options.format).await?;
+    let max_age = options.older_than.as_deref().map(parse_age).transpose()?;
+    if max_age.is_none() && !options.source_merged && options.keep_last.is_none() {
+        anyhow::bail!("Nothing to prune by, use --older-than, --source-merged or --keep-last");
+    }
+
+    let username = git_username_with_git(git).await?;
+    let wip_branches = if options.all_users {
+        git.get_all_wip_branches().await?
+    } else {
+        git.get_user_wip_branches(&username).await?
+    };
+    let wip_branches = retain_wip_commits(git, wip_branches).await;
+    let mut by_user: BTreeMap<String, Vec<WipSummary>> = BTreeMap::new();
+    for branch in wip_branches {
+        let user = branch.user.clone();
+        let summary = WipSummary::new(git, branch).await;
+        by_user.entry(user).or_default().push(summary);
+    }
+
+    let remote = wip_remote_with_git(git, options.remote.as_deref()).await?;
+    let merge_base = if options.source_merged {
+        merge_base(git, remote.as_deref()).await?
+    } else {
+        None
+    };
+
+    let mut result = PruneResult::default();
+    for summaries in by_user.values_mut() {
+        sort_newest_first(summaries);
+        for (index, summary) in summaries.iter().enumerate() {
+            if options.keep_last.is_some_and(|keep| index < keep) {
+                continue;
+            }
+
+            let mut reason = None;
+            if let (Some(max_age), Some(age)) = (max_age, summary.age) {
+                if age > max_age {
+                    reason = Some(PruneReason::OlderThan);
+                }
+            }
+            if reason.is_none() && options.source_merged {
+                reason = source_reason(
+                    git,
+                    &summary.metadata.source_branch,
+                    remote.as_deref(),
+                    merge_base.as_ref(),
+                )
+                .await?;
+                // Other users may never have pushed their source branch, so not
+                // finding it only counts when asked to with --force
+                if reason == Some(PruneReason::SourceDeleted)
+                    && summary.branch.user != username
+                    && !options.force
+                {
+                    reason = None;
+                }
+            }
+            // --keep-last on its own prunes everything past the newest N
+            if reason.is_none() && max_age.is_none() && !options.source_merged {
+                reason = Some(PruneReason::KeepLast);
+            }
+
+            if let Some(reason) = reason {
+                result.branches.push(PrunedBranch {
+                    branch: summary.branch.name.clone(),
+                    user: summary.branch.user.clone(),
+                    age_seconds: summary.age.map(|age| age.num_seconds().max(0)),
+                    reason,
+                });
+            }
+        }
+    }
+
+    if result.branches.is_empty() {
+        output.info(&t_with_args("prune-nothing", &[]))?;
+        return if options.dry_run {
+            output.result(&result)
+        } else {
+            output.result(&DeleteResult::default())
+        };
+    }
+
+    print_preview(&output, &result)?;
+    if options.dry_run {
+        output.info("")?;
+        output.info(&t_with_args("prune-dry-run", &[]))?;
+        return output.result(&result);
+    }
+
+    if !options.force {
+        output.ensure_can_prompt(FORCE_HINT)?;
+        let count = result.branches.len().to_string();
+        let confirm = Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(t_with_args("delete-branches-prompt", &[("count", &count)]))
+            .interact()?;
+        if !confirm {
+            output.info(&t_with_args("operation-cancelled", &[]))?;
+            return output.result(&DeleteResult::default());
+        }
+    }
+
+    delete_wip_branches_with_git(
+        git,
+        DeleteOptions {
+            branch_names: result.branches.into_iter().map(|b| b.branch).collect(),
+            all: false,
+            all_users: options.all_users,
+            force: true,
+            local_only: options.local_only,
+            remote: options.remote,
+            format: options.format,
+        },
+    )
+    .await
+}
+
+/// Finds the branch that source branches are merged into: the remote's default
+/// branch, or the current branch if there is none.
+async fn merge_base(git: &impl Git, remote: Option<&str>) -> Result<Option<MergeBase>> {
+    if let Some(remote) = remote {
+        if let Some(name) = git.default_branch(remote).await? {
+            let reference = format!("refs/remotes/{}/{}", remote, name);
+            return Ok(Some(MergeBase { name, reference }));
+        }
+    }
+    Ok(git.get_head().await?.branch.map(|name| MergeBase {
+        reference: format!("refs/heads/{}", name),
+        name,
+    }))
+}
+
+/// Tells whether the branch a WIP was saved on is done with.
+///
+/// # Returns
+/// * `Some(SourceDeleted)` if it exists neither locally nor on the remote
+/// * `Some(SourceMerged)` if every copy of it is reachable from the merge base
+///   without being the same commit, so branches that were only just created
+///   don't count as merged
+/// * `None` otherwise, and for WIPs saved on a detached HEAD or on the merge
+///   base itself
+async fn source_reason(
+    git: &impl Git,
+    source_branch: &str,
+    remote: Option<&str>,
+    merge_base: Option<&MergeBase>,
+) -> Result<Option<PruneReason>> {
+    if source_branch.is_empty() || merge_base.is_some_and(|base| base.name == source_branch) {
+        return Ok(None);
+    }
+
+    let mut references = vec![format!("refs/heads/{}", source_branch)];
+    if let Some(remote) = remote {
+        references.push(format!("refs/remotes/{}/{}", remote, source_branch));
+    }
+    let mut tips = Vec::new();
+    for reference in references {
+        if git.branch_exists(&reference).await? {
+            tips.push(git.rev_parse(&reference).await?);
+        }
+    }
+    if tips.is_empty() {
+        return Ok(Some(PruneReason::SourceDeleted));
+    }
+
+    let Some(merge_base) = merge_base else {
+        return Ok(None);
+    };
+    let base_commit = git.rev_parse(&merge_base.reference).await?;
+    for tip in &tips {
+        if *tip == base_commit || !git.is_ancestor(tip, &base_commit).await? {
+            return Ok(None);
+        }
+    }
+    Ok(Some(PruneReason::SourceMerged))
+}
+
+/// Prints the branches that are about to be pruned, and why.
+fn print_preview(output: &Output, result: &PruneResult) -> Result<()> {
+    let mut rows = vec![vec![
+        t_with_args("list-column-branch", &[]),
+        t_with_args("prune-user-column", &[]),
+        t_with_args("list-column-age", &[]),
+        t_with_args("prune-reason-column", &[]),
+    ]];
+    rows.extend(result.branches.iter().map(|pruned| {
+        vec![
+            pruned.branch.clone(),
+            pruned.user.clone(),
+            pruned
+                .age_seconds
+                .map(|seconds| format_age(TimeDelta::seconds(seconds)))
+                .unwrap_or_else(|| "?".to_string()),
+            pruned.reason.label(),
+        ]
+    }));
+    for line in output.format_table(&rows) {
+        output.info(&line)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{MockGit, WipBranch};
+    use mockall::predicate::eq;
+
+    /// Mocks the WIP branches of two users, each saved on the given source branch
+    fn mock_wip_branches(mock_git: &mut MockGit, branches: &[(&str, &str)]) {
+        mock_git
+            .expect_execute()
+            .with(eq(vec!["config".to_string(), "user.name".to_string()]))
+            .returning(|_| Ok("test-user".to_string()));
+        mock_git.expect_get_config_value().returning(|_| Ok(None));
+        mock_git
+            .expect_get_remotes()
+            .returning(|| Ok(vec!["origin".to_string()]));
+
+        let wip_branches: Vec<WipBranch> = branches
+            .iter()
+            .map(|(name, _)| {
+                let mut parts = name.split('/').skip(1);
+                WipBranch {
+                    name: name.to_string(),
+                    user: parts.next().unwrap().to_string(),
+                    timestamp: parts.next().unwrap().to_string(),
+                    local: true,
+                    remotes: Vec::new(),
+                }
+            })
+            .collect();
+        let user_branches: Vec<WipBranch> = wip_branches
+            .iter()
+            .filter(|branch| branch.user == "test-user")
+            .cloned()
+            .collect();
+        mock_git
+            .expect_get_all_wip_branches()
+            .returning(move || Ok(wip_branches.clone()));
+        mock_git
+            .expect_get_user_wip_branches()
+            .with(eq("test-user"))
+            .returning(move |_| Ok(user_branches.clone()));
+
+        for (name, source) in branches {
+            let message = format!(
+                "chore: saving work in progress\n\nWippy-Version: 1\nWippy-Source-Branch: {}",
+                source
+            );
+            mock_git
+                .expect_get_commit_message()
+                .with(eq(name.to_string()))
+                .returning(move |_| Ok(message.clone()));
+        }
+    }
+
+    fn options() -> PruneOptions {
+        PruneOptions {
+            older_than: None,
+            source_merged: false,
+            keep_last: None,
+            all_users: false,
+            dry_run: true,
+            force: false,
+            local_only: false,
+            remote: None,
+            format: OutputFormat::Json,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_prune_needs_a_policy() {
+        let mock_git = MockGit::new();
+        let error = prune_wip_branches_with_git(&mock_git, options())
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("Nothing to prune by"));
+
+        let options = PruneOptions {
+            older_than: Some("soon".to_string()),
+            ..options()
+        };
+        let error = prune_wip_branches_with_git(&mock_git, options)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("Invalid age 'soon'"));
+    }
+
+    #[tokio::test]
+    async fn test_prune_older_than_and_keep_last() -> Result<()> {
+        let mut mock_git = MockGit::new();
+        mock_wip_branches(
+            &mut mock_git,
+            &[
+                ("wip/test-user/2020-01-01-00-00-00", "main"),
+                ("wip/test-user/2020-01-02-00-00-00", "main"),
+                ("wip/other-user/2020-01-01-00-00-00", "main"),
+                ("wip/test-user/2999-01-01-00-00-00", "main"),
+            ],
+        );
+        // Only the current user's branches are pruned, and of the two older
+        // than 30 days the newer one is kept by --keep-last 2
+        mock_git
+            .expect_delete_branch()
+            .with(eq("wip/test-user/2020-01-01-00-00-00"), eq(true))
+            .times(1)
+            .returning(|_, _| Ok(String::new()));
+        mock_git
+            .expect_delete_remote_branch()
+            .with(eq("origin"), eq("wip/test-user/2020-01-01-00-00-00"))
+            .times(1)
+            .returning(|_, _| Ok(String::new()));
+
+        prune_wip_branches_with_git(
+            &mock_git,
+            PruneOptions {
+                older_than: Some("30d".to_string()),
+                keep_last: Some(2),
+                dry_run: false,
+                force: true,
+                ..options()
+            },
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_prune_keep_last_for_all_users() -> Result<()> {
+        let mut mock_git = MockGit::new();
+        mock_wip_branches(
+            &mut mock_git,
+            &[
+                ("wip/test-user/2020-01-01-00-00-00", "main"),
+                ("wip/test-user/2020-01-02-00-00-00", "main"),
+                ("wip/other-user/2020-01-01-00-00-00", "main"),
+                ("wip/other-user/2020-01-02-00-00-00", "main"),
+                ("wip/other-user/2020-01-03-00-00-00", "main"),
+            ],
+        );
+        // A dry run deletes nothing
+        mock_git.expect_delete_branch().never();
+        mock_git.expect_delete_remote_branch().never();
+
+        prune_wip_branches_with_git(
+            &mock_git,
+            PruneOptions {
+                keep_last: Some(1),
+                all_users: true,
+                ..options()
+            },
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_prune_skips_branches_without_wip_commit() -> Result<()> {
+        let mut mock_git = MockGit::new();
+        mock_git
+            .expect_execute()
+            .with(eq(vec!["config".to_string(), "user.name".to_string()]))
+            .returning(|_| Ok("test-user".to_string()));
+        mock_git.expect_get_config_value().returning(|_| Ok(None));
+        mock_git
+            .expect_get_remotes()
+            .returning(|| Ok(vec!["origin".to_string()]));
+        mock_git.expect_get_user_wip_branches().returning(|_| {
+            Ok([
+                "wip/test-user/2020-01-01-00-00-00",
+                "wip/test-user/2020-01-02-00-00-00",
+            ]
+            .iter()
+            .map(|name| WipBranch {
+                name: name.to_string(),
+                user: "test-user".to_string(),
+                timestamp: name.rsplit('/').next().unwrap().to_string(),
+                local: true,
+                remotes: Vec::new(),
+            })
+            .collect())
+        });
+        mock_git
+            .expect_get_commit_message()
+            .with(eq("wip/test-user/2020-01-01-00-00-00"))
+            .returning(|_| Ok("chore: saving work in progress\n\nWippy-Version: 1".to_string()));
+        // Named like a WIP branch, but not saved by git-wippy
+        mock_git
+            .expect_get_commit_message()
+            .with(eq("wip/test-user/2020-01-02-00-00-00"))
+            .returning(|_| Ok("Release 1.0".to_string()));
+        mock_git
+            .expect_delete_branch()
+            .with(eq("wip/test-user/2020-01-01-00-00-00"), eq(true))
+            .times(1)
+            .returning(|_, _| Ok(String::new()));
+        mock_git
+            .expect_delete_branch()
+            .with(eq("wip/test-user/2020-01-02-00-00-00"), eq(true))
+            .never();
+
+        prune_wip_branches_with_git(
+            &mock_git,
+            PruneOptions {
+                older_than: Some("30d".to_string()),
+                dry_run: false,
+                force: true,
+                local_only: true,
+                ..options()
+            },
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_prune_source_deleted_for_all_users() -> Result<()> {
+        let mut mock_git = MockGit::new();
+        mock_wip_branches(
+            &mut mock_git,
+            &[("wip/other-user/2020-01-01-00-00-00", "unpushed")],
+        );
+        mock_git
+            .expect_default_branch()
+            .returning(|_| Ok(Some("main".to_string())));
+        mock_git.expect_branch_exists().returning(|_| Ok(false));
+        // Only deleted the second time, when asked to with --force
+        mock_git
+            .expect_delete_branch()
+            .with(eq("wip/other-user/2020-01-01-00-00-00"), eq(true))
+            .times(1)
+            .returning(|_, _| Ok(String::new()));
+        let options = |force| PruneOptions {
+            source_merged: true,
+            all_users: true,
+            dry_run: false,
+            force,
+            local_only: true,
+            ..options()
+        };
+
+        // The other user's source branch may only exist on their machine
+        prune_wip_branches_with_git(&mock_git, options(false)).await?;
+        prune_wip_branches_with_git(&mock_git, options(true)).await
+    }
+
+    #[tokio::test]
+    async fn test_source_reason() -> Result<()> {
+        let mut mock_git = MockGit::new();
+        for (reference, exists) in [
+            ("refs/heads/merged", true),
+            ("refs/remotes/origin/merged", false),
+            ("refs/heads/active", true),
+            ("refs/remotes/origin/active", true),
+            ("refs/heads/new", true),
+            ("refs/remotes/origin/new", false),
+            ("refs/heads/gone", false),
+            ("refs/remotes/origin/gone", false),
+        ] {
+            mock_git
+                .expect_branch_exists()
+                .with(eq(reference))
+                .returning(move |_| Ok(exists));
+        }
+        for (reference, commit) in [
+            ("refs/remotes/origin/main", "main-sha"),
+            ("refs/heads/merged", "merged-sha"),
+            ("refs/heads/active", "merged-sha"),
+            ("refs/remotes/origin/active", "active-sha"),
+            ("refs/heads/new", "main-sha"),
+        ] {
+            mock_git
+                .expect_rev_parse()
+                .with(eq(reference))
+                .returning(move |_| Ok(commit.to_string()));
+        }
+        mock_git
+            .expect_is_ancestor()
+            .with(eq("merged-sha"), eq("main-sha"))
+            .returning(|_, _| Ok(true));
+        mock_git
+            .expect_is_ancestor()
+            .with(eq("active-sha"), eq("main-sha"))
+            .returning(|_, _| Ok(false));
+
+        let base = MergeBase {
+            name: "main".to_string(),
+            reference: "refs/remotes/origin/main".to_string(),
+        };
+        for (source, reason) in [
+            ("merged", Some(PruneReason::SourceMerged)),
+            // Pushed commits that aren't merged yet keep the WIP
+            ("active", None),
+            // Just branched off, nothing to merge yet
+            ("new", None),
+            ("gone", Some(PruneReason::SourceDeleted)),
+            ("main", None),
+            ("", None),
+        ] {
+            assert_eq!(
+                source_reason(&mock_git, source, Some("origin"), Some(&base)).await?,
+                reason,
+                "{}",
+                source
+            );
+        }
+        Ok(())
+    }
+}
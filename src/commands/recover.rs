@@ -0,0 +1,395 @@
+use crate::commands::restore::restore_index_and_worktree;
+use crate::i18n::t_with_args;
+use crate::output::{Output, OutputFormat};
+use crate::utils::{open_git, Git, Journal, Step};
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+pub struct RecoverOptions {
+    pub format: OutputFormat,
+}
+
+/// The result of a recover, as printed by `--format json`.
+#[derive(Serialize, Default)]
+struct RecoverResult {
+    command: Option<String>,
+    rolled_back: Vec<Step>,
+    deleted_branches: Vec<String>,
+}
+
+/// Cleans up after an interrupted `save` or `restore`.
+///
+/// # Arguments
+/// * `options` - Configuration for the recover operation
+///   - `format`: Print progress messages or a JSON result
+///
+/// # Details
+/// * Undoes the steps recorded in the journal of the interrupted run, newest first
+/// * Leaves the journal of a run that is still going, e.g. a `watch` snapshot, alone
+/// * Deletes leftover `git-wippy-temp-*` branches from older versions
+pub async fn recover(options: RecoverOptions) -> Result<()> {
+    let git = open_git();
+    recover_with_git(&git, options).await
+}
+
+pub async fn recover_with_git(git: &impl Git, options: RecoverOptions) -> Result<()> {
+    let output = Output::with_format(options.format).await?;
+    let mut result = RecoverResult::default();
+
+    match Journal::load(git).await? {
+        // Rolling back a run that is still going would undo its work under it
+        Some(journal) if journal.is_running().await => return Err(journal.still_running()),
+        Some(journal) => {
+            result.command = Some(journal.command.clone());
+            let message = t_with_args("recovering", &[("command", &journal.command)]);
+            output.info(&output.format_with_highlights(&message, &[&journal.command]))?;
+            result.rolled_back = roll_back(git, &output, journal).await?;
+            output.info(&t_with_args("rollback-complete", &[]))?;
+        }
+        None => output.info(&t_with_args("nothing-to-recover", &[]))?,
+    }
+
+    // Older versions reapplied autostashes through a temporary branch
+    let temp_branches = git
+        .execute(vec![
+            "for-each-ref".to_string(),
+            "--format=%(refname:short)".to_string(),
+            "refs/heads/git-wippy-temp-*".to_string(),
+        ])
+        .await?;
+    for branch in temp_branches.lines().filter(|line| !line.is_empty()) {
+        git.delete_branch(branch, true).await?;
+        let message = t_with_args("deleted-temp-branch", &[("name", branch)]);
+        output.info(&output.format_with_highlights(&message, &[&format!("'{}'", branch)]))?;
+        result.deleted_branches.push(branch.to_string());
+    }
+
+    output.result(&result)
+}
+
+/// Rolls back a failed command and returns the error that made it fail.
+///
+/// If the rollback fails as well, its error is returned with the original error
+/// as the cause, and the remaining steps stay in the journal for `git wippy recover`.
+pub(crate) async fn abort(
+    git: &impl Git,
+    output: &Output,
+    journal: Journal,
+    error: anyhow::Error,
+) -> anyhow::Error {
+    if journal.steps.is_empty() {
+        // Nothing was done, so there is nothing to roll back
+        let _ = journal.finish().await;
+        return error;
+    }
+
+    // Reporting is best effort, the original error matters more
+    let _ = output.warning(&t_with_args("rolling-back", &[]));
+    match roll_back(git, output, journal).await {
+        Ok(_) => {
+            let _ = output.info(&t_with_args("rollback-complete", &[]));
+            error
+        }
+        Err(rollback_error) => error.context(format!("{:#}", rollback_error)),
+    }
+}
+
+/// Undoes the steps recorded in a journal, newest first.
+///
+/// Each undone step is reported and removed from the journal straight away, so
+/// if undoing a step fails the journal keeps exactly what is left for
+/// `git wippy recover`.
+///
+/// # Returns
+/// * `Ok(Vec<Step>)` - The steps that were undone, in the order they were undone
+/// * `Err` if a step could not be undone
+pub(crate) async fn roll_back(
+    git: &impl Git,
+    output: &Output,
+    mut journal: Journal,
+) -> Result<Vec<Step>> {
+    let mut undone = Vec::new();
+
+    while let Some(step) = journal.steps.last().cloned() {
+        let message = undo_step(git, &step).await.with_context(|| {
+            t_with_args("rollback-incomplete", &[("command", &journal.command)])
+        })?;
+        output.info(&message)?;
+        journal.forget_last().await?;
+        undone.push(step);
+    }
+
+    journal.finish().await?;
+    Ok(undone)
+}
+
+/// Undoes a single step and describes what was restored.
+async fn undo_step(git: &impl Git, step: &Step) -> Result<String> {
+    match step {
+        Step::CreatedBranch { branch } => {
+            if git.branch_exists(branch).await? {
+                git.delete_branch(branch, true).await?;
+            }
+            Ok(t_with_args(
+                "rolled-back-created-branch",
+                &[("name", branch)],
+            ))
+        }
+        Step::UpdatedBranch {
+            branch,
+            previous,
+            commit,
+        } => {
+            git.update_ref(&format!("refs/heads/{}", branch), previous, commit)
+                .await?;
+            Ok(t_with_args(
+                "rolled-back-updated-branch",
+                &[("name", branch)],
+            ))
+        }
+        Step::PushedUpdate {
+            remote,
+            branch,
+            previous,
+        } => {
+            git.execute(vec![
+                "push".to_string(),
+                "--force".to_string(),
+                remote.to_string(),
+                format!("{}:refs/heads/{}", previous, branch),
+            ])
+            .await?;
+            Ok(t_with_args(
+                "rolled-back-pushed-update",
+                &[("name", branch), ("remote", remote)],
+            ))
+        }
+        Step::PushedBranch { remote, branch } => {
+            git.delete_remote_branch(remote, branch).await?;
+            Ok(t_with_args(
+                "rolled-back-pushed-branch",
+                &[("name", branch), ("remote", remote)],
+            ))
+        }
+        Step::ClearedWorkingTree {
+            index_commit,
+            wip_commit,
+        } => {
+            // Start from a clean tree in case clearing stopped halfway
+            git.reset_hard().await?;
+            git.clean_untracked().await?;
+            restore_index_and_worktree(git, index_commit, wip_commit).await?;
+            Ok(t_with_args("rolled-back-cleared-changes", &[]))
+        }
+        Step::Stashed { message } => {
+            let stash_ref = git
+                .find_stash(message)
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("Could not find stash with name: {}", message))?;
+            git.execute(vec![
+                "stash".to_string(),
+                "pop".to_string(),
+                "--index".to_string(),
+                stash_ref,
+            ])
+            .await?;
+            Ok(t_with_args("rolled-back-stash", &[]))
+        }
+        Step::SwitchedBranch { from } => {
+            git.checkout(from).await?;
+            Ok(t_with_args(
+                "rolled-back-switched-branch",
+                &[("name", from)],
+            ))
+        }
+        Step::AppliedChanges => {
+            git.reset_hard().await?;
+            git.clean_untracked().await?;
+            Ok(t_with_args("rolled-back-applied-changes", &[]))
+        }
+        Step::DeletedBranch { branch, commit } => {
+            git.create_ref(&format!("refs/heads/{}", branch), commit)
+                .await?;
+            Ok(t_with_args(
+                "rolled-back-deleted-branch",
+                &[("name", branch)],
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::MockGit;
+    use mockall::predicate::eq;
+    use tempfile::TempDir;
+
+    /// Mocks a git directory in a temporary directory, holding `journal` if any
+    fn mock_git_dir(mock_git: &mut MockGit, journal: Option<&str>) -> TempDir {
+        let git_dir = TempDir::new().unwrap();
+        if let Some(journal) = journal {
+            std::fs::write(git_dir.path().join("wippy-journal.json"), journal).unwrap();
+        }
+        let path = git_dir.path().to_string_lossy().to_string();
+        mock_git
+            .expect_git_dir()
+            .returning(move || Ok(path.clone()));
+        git_dir
+    }
+
+    #[tokio::test]
+    async fn test_recover_rolls_back_interrupted_restore() -> Result<()> {
+        let mut mock_git = MockGit::new();
+        let mut seq = mockall::Sequence::new();
+
+        let git_dir = mock_git_dir(
+            &mut mock_git,
+            Some(
+                r#"{
+                    "command": "restore",
+                    "steps": [
+                        {"step": "stashed", "message": "git-wippy-autostash-main"},
+                        {"step": "switched-branch", "from": "feature"},
+                        {"step": "applied-changes"}
+                    ]
+                }"#,
+            ),
+        );
+
+        // Steps are undone newest first
+        mock_git
+            .expect_reset_hard()
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|| Ok("".to_string()));
+        mock_git
+            .expect_clean_untracked()
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|| Ok("".to_string()));
+        mock_git
+            .expect_checkout()
+            .with(eq("feature"))
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|_| Ok("".to_string()));
+        mock_git
+            .expect_find_stash()
+            .with(eq("git-wippy-autostash-main"))
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|_| Ok(Some("stash@{0}".to_string())));
+        mock_git
+            .expect_execute()
+            .with(eq(vec![
+                "stash".to_string(),
+                "pop".to_string(),
+                "--index".to_string(),
+                "stash@{0}".to_string(),
+            ]))
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|_| Ok("".to_string()));
+
+        // No leftover temporary branches
+        mock_git
+            .expect_execute()
+            .withf(|args| args[0] == "for-each-ref")
+            .returning(|_| Ok("".to_string()));
+
+        recover_with_git(
+            &mock_git,
+            RecoverOptions {
+                format: OutputFormat::Text,
+            },
+        )
+        .await?;
+
+        // Every step was undone, so the journal is gone
+        assert!(!git_dir.path().join("wippy-journal.json").exists());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_recover_leaves_running_save_alone() -> Result<()> {
+        let mut mock_git = MockGit::new();
+        // E.g. a snapshot `watch` is taking in the background
+        let mut other = tokio::process::Command::new("sleep")
+            .arg("10")
+            .kill_on_drop(true)
+            .spawn()?;
+        let journal = format!(
+            r#"{{"command": "save", "pid": {}, "steps": [{{"step": "created-branch", "branch": "wip/jane/2024-03-21-17-59-30"}}]}}"#,
+            other.id().unwrap()
+        );
+        let git_dir = mock_git_dir(&mut mock_git, Some(&journal));
+        mock_git.expect_delete_branch().never();
+
+        let error = recover_with_git(
+            &mock_git,
+            RecoverOptions {
+                format: OutputFormat::Text,
+            },
+        )
+        .await
+        .unwrap_err();
+
+        assert!(error.to_string().contains("is still running"));
+        assert!(git_dir.path().join("wippy-journal.json").exists());
+        other.kill().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_recover_deletes_leftover_temp_branches() -> Result<()> {
+        let mut mock_git = MockGit::new();
+
+        let _git_dir = mock_git_dir(&mut mock_git, None);
+        mock_git
+            .expect_execute()
+            .withf(|args| args[0] == "for-each-ref")
+            .returning(|_| Ok("git-wippy-temp-main".to_string()));
+        mock_git
+            .expect_delete_branch()
+            .with(eq("git-wippy-temp-main"), eq(true))
+            .times(1)
+            .returning(|_, _| Ok("".to_string()));
+
+        recover_with_git(
+            &mock_git,
+            RecoverOptions {
+                format: OutputFormat::Text,
+            },
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_roll_back_keeps_remaining_steps_on_failure() {
+        let mut mock_git = MockGit::new();
+
+        let journal = r#"{
+                    "command": "save",
+                    "steps": [
+                        {"step": "created-branch", "branch": "wip/jane/2024-03-21-17-59-30"},
+                        {"step": "pushed-branch", "remote": "origin", "branch": "wip/jane/2024-03-21-17-59-30"}
+                    ]
+                }"#;
+        let git_dir = mock_git_dir(&mut mock_git, Some(journal));
+        mock_git
+            .expect_delete_remote_branch()
+            .returning(|_, _| Err(anyhow::anyhow!("network is down")));
+
+        mock_git.expect_delete_branch().never();
+
+        let loaded = Journal::load(&mock_git).await.unwrap().unwrap();
+        let output = Output::new().await.unwrap();
+        let error = roll_back(&mock_git, &output, loaded).await.unwrap_err();
+        assert!(format!("{:#}", error).contains("network is down"));
+
+        // The failed step stays in the journal, so it is left as it was
+        let path = git_dir.path().join("wippy-journal.json");
+        assert_eq!(std::fs::read_to_string(path).unwrap(), journal);
+    }
+}
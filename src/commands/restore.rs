@@ -1,79 +1,164 @@
+use crate::commands::list::branch_label;
+use crate::commands::recover::abort;
 use crate::i18n::t_with_args;
-use crate::output::Output;
-use crate::utils::{git_username_with_git, parse_commit_message, Git, GitCommand};
+use crate::output::{Output, OutputFormat};
+use crate::utils::{
+    git_username_with_git, open_git, parent_or_empty_tree, parse_commit_message,
+    parse_snapshot_name, resolve_snapshot, short_commit, wip_remote_with_git, BranchTemplate, Git,
+    Head, Journal, Step, WipBranch, WipMetadata,
+};
 use anyhow::{Context, Result};
 use dialoguer::{theme::ColorfulTheme, Select};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::io::IsTerminal;
+
+/// Git config key that keeps the remote copy of a WIP branch when restoring it
+pub const KEEP_REMOTE_CONFIG_KEY: &str = "wippy.keepRemote";
 
 pub struct RestoreOptions {
     pub branch_name: Option<String>,
-    #[allow(dead_code)]
+    pub onto: Option<String>,
     pub force: bool,
     pub autostash: bool,
+    pub keep: bool,
+    pub keep_remote: bool,
+    pub remote: Option<String>,
+    pub format: OutputFormat,
+}
+
+/// The result of a restore, as printed by `--format json`.
+#[derive(Serialize, Default)]
+struct RestoreResult {
+    branch: Option<String>,
+    source_branch: Option<String>,
+    source_commit: Option<String>,
+    message: Option<String>,
+    created_source_branch: bool,
+    restored_onto: Option<String>,
+    conflicts: Vec<String>,
+    deleted_local: bool,
+    deleted_remote: Option<String>,
+}
+
+/// What happens to the WIP branch once its changes are restored.
+#[derive(Debug, PartialEq)]
+enum Cleanup {
+    /// Keep the branch, like `git stash apply`
+    KeepAll,
+    /// Delete the local branch, and the copy on this remote if there is one
+    Delete { remote: Option<String> },
+}
+
+/// Everything a restore decided before it starts changing the repository.
+struct RestorePlan {
+    /// The WIP branch to restore, which may only exist on a remote
+    branch: WipBranch,
+    /// The snapshot to restore, the branch itself for its latest one
+    snapshot: String,
+    metadata: WipMetadata,
+    /// Where HEAD was when the restore started
+    head: Head,
+    target: Target,
+    /// Stash local changes and reapply them afterwards
+    autostash: bool,
+    cleanup: Cleanup,
+}
+
+/// Where the saved changes are restored.
+#[derive(Debug, PartialEq)]
+enum Target {
+    /// Stay on the current branch or commit
+    Head,
+    /// Check out an existing branch
+    Branch(String),
+    /// Create a branch, at the commit the work was saved on if there is one
+    NewBranch {
+        branch: String,
+        start_point: Option<String>,
+    },
+    /// Check out the commit the work was saved on, without a branch
+    Detached(String),
 }
 
 /// Restores changes from a WIP branch back to its original source branch.
 ///
 /// # Arguments
 /// * `options` - Configuration for the restore operation
-///   - `branch_name`: Optional name of the branch to restore
+///   - `branch_name`: Optional name of the branch to restore, with `@{n}` to
+///     restore the snapshot n saves before the latest one
+///   - `onto`: Branch to restore onto instead of the one the work was saved on
 ///   - `force`: Skip confirmation prompts
 ///   - `autostash`: Automatically stash and reapply local changes
+///   - `keep`: Keep the WIP branch, locally and on the remote
+///   - `keep_remote`: Only delete the local WIP branch, also set by `wippy.keepRemote`
+///   - `remote`: Remote to delete the WIP branch from instead of `wippy.remote` or `origin`
+///   - `format`: Print progress messages or a JSON result
 ///
 /// # Details
 /// * Retrieves WIP branches for the current user
 /// * If multiple WIP branches exist, prompts user to select one
 /// * Extracts source branch and file states from the WIP commit message
-/// * Recreates the original file states (staged, changed, untracked)
-/// * Deletes the WIP branch both locally and remotely
+/// * Recreates the original file states, including partially staged files
+/// * Deletes the WIP branch locally and, if it was pushed, on the remote, unless
+///   it is kept or an older snapshot was restored
 ///
 /// # Flow
-/// 1. Get WIP branches and select one
+/// 1. Get WIP branches and select one. A WIP that only exists on a remote is
+///    restored from there, and fetched first if it isn't known locally yet
 /// 2. Extract information from commit message
-/// 3. Checkout WIP branch and unstage changes
-/// 4. Stash changes
-/// 5. Switch to source branch (create if needed)
-/// 6. Apply stashed changes
-/// 7. Recreate original file states
-/// 8. Clean up WIP branch
+/// 3. Stash local changes if autostash is enabled
+/// 4. Switch to the `--onto` branch or the source branch, or the source commit if the
+///    work was saved on a detached HEAD. A source branch that no longer exists is
+///    recreated, after asking for another target when run interactively
+/// 5. Restore the saved index and working tree, or merge the changes in with a
+///    three-way merge if the branch moved on since saving. On conflicts the WIP
+///    branch and the autostash are kept and the conflicted files are listed
+/// 6. Reapply stashed changes
+/// 7. Clean up WIP branch and drop the stash
+///
+/// Every step is recorded in a journal. If one fails, the completed steps are
+/// undone and the repository is left as it was before the restore.
 ///
 /// # Returns
 /// * `Ok(())` if restoration succeeds
+/// * `Err(RestoreConflicts)` if the changes were applied with conflicts
 /// * `Err` if any step fails
 pub async fn restore_wip_changes(options: RestoreOptions) -> Result<()> {
-    let git = GitCommand::new();
+    let git = open_git();
     restore_wip_changes_with_git(&git, options).await
 }
 
 /// Implementation that accepts a Git instance for better testability
 pub async fn restore_wip_changes_with_git(git: &impl Git, options: RestoreOptions) -> Result<()> {
-    let output = Output::new().await?;
-    let username = git_username_with_git(git).await?;
-    let wip_branches = git.get_user_wip_branches(&username).await?;
-
-    let selected_branch = if let Some(branch) = options.branch_name {
-        if !wip_branches.contains(&branch) {
-            let message = t_with_args("branch-not-found", &[("name", &branch)]);
-            output.info(&output.format_with_highlights(&message, &[&format!("'{}'", branch)]))?;
-            return Ok(());
-        }
-        branch
-    } else if wip_branches.len() > 1 {
-        get_user_selection(&wip_branches).await?
-    } else if let Some(branch) = wip_branches.first() {
-        branch.clone()
-    } else {
-        let message = t_with_args("no-wip-branches", &[("username", &username)]);
-        output.info(&output.format_with_highlights(&message, &[&username]))?;
-        return Ok(());
+    let output = Output::with_format(options.format).await?;
+    let (branch_name, snapshot_index) = match options.branch_name.as_deref() {
+        Some(name) => parse_snapshot_name(name).map(|(name, index)| (Some(name), index))?,
+        None => (None, 0),
+    };
+    let Some(selected_branch) = select_wip_branch(
+        git,
+        &output,
+        branch_name,
+        options.remote.as_deref(),
+        "Select a WIP branch to restore",
+    )
+    .await?
+    else {
+        return output.result(&RestoreResult::default());
     };
 
-    // Get the last commit message from the WIP branch
-    let commit_message = git.get_commit_message(&selected_branch).await?;
-    let (source_branch, staged_files, changed_files, untracked_files) =
-        parse_commit_message(&commit_message);
+    // Get the commit message of the snapshot being restored
+    let snapshot = resolve_snapshot(git, &selected_branch.reference(), snapshot_index).await?;
+    let commit_message = git.get_commit_message(&snapshot).await?;
+    let metadata = parse_commit_message(&commit_message);
 
-    let message = t_with_args("restoring-wip", &[("name", &selected_branch)]);
-    output.info(&output.format_with_highlights(&message, &[&format!("'{}'", selected_branch)]))?;
+    let name = &selected_branch.name;
+    let message = t_with_args("restoring-wip", &[("name", name)]);
+    output.info(&output.format_with_highlights(&message, &[&format!("'{}'", name)]))?;
+    if let Some(wip_message) = &metadata.message {
+        output.info(&t_with_args("wip-message", &[("message", wip_message)]))?;
+    }
 
     // Check for local changes
     let has_changes = !git.get_staged_files().await?.is_empty()
@@ -86,11 +171,108 @@ pub async fn restore_wip_changes_with_git(git: &impl Git, options: RestoreOption
         ));
     }
 
+    // Resolved up front so a missing branch or remote fails before anything changes
+    let head = git.get_head().await?;
+    let interactive = !options.force && !output.is_json() && std::io::stdin().is_terminal();
+    let target = resolve_target(git, &head, &metadata, options.onto.as_deref()).await?;
+    let target = match target {
+        Target::NewBranch {
+            branch,
+            start_point,
+        } if interactive => prompt_for_target(git, &head, branch, start_point).await?,
+        target => target,
+    };
+    let cleanup = if options.keep {
+        Cleanup::KeepAll
+    } else if snapshot_index > 0 {
+        // Deleting the branch would lose the newer snapshots
+        let name = &selected_branch.name;
+        let message = t_with_args("restore-older-snapshot", &[("name", name)]);
+        output.info(&output.format_with_highlights(&message, &[&format!("'{}'", name)]))?;
+        Cleanup::KeepAll
+    } else if options.keep_remote || keep_remote_configured(git).await? {
+        Cleanup::Delete { remote: None }
+    } else {
+        Cleanup::Delete {
+            remote: wip_remote_with_git(git, options.remote.as_deref()).await?,
+        }
+    };
+
+    let plan = RestorePlan {
+        branch: selected_branch,
+        snapshot,
+        metadata,
+        head,
+        target,
+        autostash: has_changes && options.autostash,
+        cleanup,
+    };
+
+    let mut journal = Journal::start(git, "restore").await?;
+    match restore_with_journal(git, &output, &mut journal, plan).await {
+        Ok(result) => {
+            journal.finish().await?;
+            output.result(&result)?;
+            // Like `git stash pop`, fail so scripts notice the conflict markers
+            match result.conflicts.len() {
+                0 => Ok(()),
+                count => Err(RestoreConflicts { count }.into()),
+            }
+        }
+        Err(error) => Err(abort(git, &output, journal, error).await),
+    }
+}
+
+/// The error a restore that left conflicts fails with, after its result was printed.
+#[derive(Debug)]
+pub struct RestoreConflicts {
+    pub count: usize,
+}
+
+impl std::fmt::Display for RestoreConflicts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "The restore left conflicts in {} file(s), the WIP branch was kept",
+            self.count
+        )
+    }
+}
+
+impl std::error::Error for RestoreConflicts {}
+
+/// Runs the restore, recording every change it makes so a failure can be rolled back.
+///
+/// The WIP branch and the autostash are only removed once everything else has
+/// succeeded, so a rollback can always bring both back.
+async fn restore_with_journal(
+    git: &impl Git,
+    output: &Output,
+    journal: &mut Journal,
+    plan: RestorePlan,
+) -> Result<RestoreResult> {
+    let RestorePlan {
+        branch,
+        snapshot,
+        metadata,
+        head,
+        target,
+        autostash,
+        cleanup,
+    } = plan;
+    let selected_branch = branch.name.clone();
+    let source_branch = metadata.source_branch.clone();
+    let source_commit = metadata.source_commit.clone();
+    let source_label = match (&source_branch, &source_commit) {
+        (branch, _) if !branch.is_empty() => branch.clone(),
+        (_, Some(commit)) => short_commit(commit).to_string(),
+        _ => head.describe(),
+    };
+    let stash_name = format!("git-wippy-autostash-{}", source_label);
+
     // Stash any existing changes if autostash is enabled
-    if has_changes && options.autostash {
+    if autostash {
         output.info(&t_with_args("stashing-existing-changes", &[]))?;
-        // Create a unique stash name for the local changes
-        let stash_name = format!("git-wippy-autostash-{}", source_branch);
         git.execute(vec![
             "stash".to_string(),
             "push".to_string(),
@@ -100,187 +282,363 @@ pub async fn restore_wip_changes_with_git(git: &impl Git, options: RestoreOption
         ])
         .await
         .context("Failed to stash changes")?;
+        journal
+            .record(Step::Stashed {
+                message: stash_name.clone(),
+            })
+            .await?;
     }
 
-    // Determine if the source branch exists, create it if not
-    if git.branch_exists(&source_branch).await? {
-        git.checkout(&source_branch).await?;
-        let message = t_with_args("checked-out-branch", &[("name", &source_branch)]);
-        output
-            .info(&output.format_with_highlights(&message, &[&format!("'{}'", source_branch)]))?;
-    } else {
-        git.create_branch(&source_branch).await?;
-        let message = t_with_args("created-branch", &[("name", &source_branch)]);
-        output
-            .info(&output.format_with_highlights(&message, &[&format!("'{}'", source_branch)]))?;
-    }
+    let mut result = RestoreResult {
+        branch: Some(selected_branch.clone()),
+        source_branch: Some(source_branch.clone()).filter(|branch| !branch.is_empty()),
+        source_commit: source_commit.clone(),
+        message: metadata.message.clone(),
+        ..Default::default()
+    };
 
-    // Get the list of files in the WIP branch
-    let files_output = git
-        .execute(vec![
-            "ls-tree".to_string(),
-            "-r".to_string(),
-            "--name-only".to_string(),
-            selected_branch.clone(),
-        ])
-        .await?;
-    let files: Vec<String> = files_output.lines().map(|s| s.to_string()).collect();
-
-    // For each file in the WIP branch, get its contents and write it
-    for file in files {
-        let _content = git
-            .execute(vec![
-                "show".to_string(),
-                format!("{}:{}", selected_branch, file),
-            ])
+    let switched = match &target {
+        Target::Head => false,
+        Target::Branch(branch) => {
+            git.checkout(branch).await?;
+            let message = t_with_args("checked-out-branch", &[("name", branch)]);
+            output.info(&output.format_with_highlights(&message, &[&format!("'{}'", branch)]))?;
+            true
+        }
+        Target::NewBranch {
+            branch,
+            start_point,
+        } => {
+            // Start the branch where the work was saved so the changes apply cleanly
+            match start_point {
+                Some(commit) => git.create_branch_at(branch, commit).await?,
+                None => git.create_branch(branch).await?,
+            };
+            journal
+                .record(Step::CreatedBranch {
+                    branch: branch.clone(),
+                })
+                .await?;
+            result.created_source_branch = true;
+            let message = t_with_args("created-branch", &[("name", branch)]);
+            output.info(&output.format_with_highlights(&message, &[&format!("'{}'", branch)]))?;
+            true
+        }
+        Target::Detached(commit) => {
+            git.checkout_detached(commit).await?;
+            let short = short_commit(commit);
+            let message = t_with_args("checked-out-commit", &[("commit", short)]);
+            output.info(&output.format_with_highlights(&message, &[&format!("'{}'", short)]))?;
+            true
+        }
+    };
+    result.restored_onto = match target {
+        Target::Head => head.branch.clone(),
+        Target::Branch(branch) | Target::NewBranch { branch, .. } => Some(branch),
+        Target::Detached(_) => None,
+    };
+    if let (true, Some(from)) = (switched, head.checkout_target()) {
+        journal
+            .record(Step::SwitchedBranch {
+                from: from.to_string(),
+            })
             .await?;
-        git.execute(vec![
-            "checkout".to_string(),
-            selected_branch.clone(),
-            "--".to_string(),
-            file.clone(),
-        ])
-        .await?;
     }
-    output.info(&t_with_args("applied-changes", &[]))?;
 
-    // Recreate the original state of files based on the parsed commit message
-    recreate_file_states(git, staged_files, changed_files, untracked_files).await?;
+    // Recorded up front, applying may fail halfway
+    journal.record(Step::AppliedChanges).await?;
+    let current = match git.get_head().await?.commit {
+        Some(commit) => commit,
+        None => git.empty_tree().await?,
+    };
+    let current_label = result
+        .restored_onto
+        .clone()
+        .unwrap_or_else(|| short_commit(&current).to_string());
+    result.conflicts = apply_wip(git, &metadata, &snapshot, &current, &current_label).await?;
+
+    if !result.conflicts.is_empty() {
+        // Like a conflicted `git stash pop`, keep everything needed to start over
+        let message = t_with_args(
+            "restore-conflicts",
+            &[("count", &result.conflicts.len().to_string())],
+        );
+        output.warning(&message)?;
+        for file in &result.conflicts {
+            output.warning(&format!("  {}", file))?;
+        }
+        if autostash {
+            output.warning(&t_with_args("kept-autostash", &[("name", &stash_name)]))?;
+        }
+        let message = t_with_args("kept-wip-branch-conflicts", &[("name", &selected_branch)]);
+        output.warning(
+            &output.format_with_highlights(&message, &[&format!("'{}'", selected_branch)]),
+        )?;
+        return Ok(result);
+    }
+    output.info(&t_with_args("applied-changes", &[]))?;
     output.info(&t_with_args("recreated-file-states", &[]))?;
 
-    // Pop any previously stashed changes if autostash was used
-    if has_changes && options.autostash {
+    // Reapply any previously stashed changes if autostash was used
+    if autostash {
         output.info(&t_with_args("restoring-existing-changes", &[]))?;
-        let stash_name = format!("git-wippy-autostash-{}", source_branch);
-
-        // Try to find the stash index by listing all stashes and searching for our name
-        let stash_list = git
-            .execute(vec!["stash".to_string(), "list".to_string()])
+        let stash_ref = git
+            .find_stash(&stash_name)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Could not find stash with name: {}", stash_name))?;
+        git.execute(vec!["stash".to_string(), "apply".to_string(), stash_ref])
             .await
-            .context("Failed to list stashes")?;
+            .context("Failed to apply stashed changes")?;
+    }
 
-        // Find the stash by looking for the message in the stash list
-        // The stash list format is: stash@{n}: WIP on branch: message
-        let stash_index = stash_list
-            .lines()
-            .position(|line| line.contains(&format!(": {}", stash_name)))
-            .ok_or_else(|| anyhow::anyhow!("Could not find stash with name: {}", stash_name))?;
-        let stash_ref = format!("stash@{{{}}}", stash_index);
+    // Now that we've successfully applied all changes, we can delete the WIP branch
+    match cleanup {
+        Cleanup::KeepAll => {
+            let message = t_with_args("kept-wip-branch", &[("name", &selected_branch)]);
+            output.info(
+                &output.format_with_highlights(&message, &[&format!("'{}'", selected_branch)]),
+            )?;
+        }
+        Cleanup::Delete { remote } => {
+            // A WIP restored straight from a remote has no local branch
+            if branch.local {
+                let wip_commit = git.rev_parse(&selected_branch).await?;
+                git.delete_branch(&selected_branch, true).await?;
+                journal
+                    .record(Step::DeletedBranch {
+                        branch: selected_branch.clone(),
+                        commit: wip_commit,
+                    })
+                    .await?;
+                result.deleted_local = true;
+                let message = t_with_args("deleted-local-branch", &[("name", &selected_branch)]);
+                output.info(
+                    &output.format_with_highlights(&message, &[&format!("'{}'", selected_branch)]),
+                )?;
+            }
 
-        // Create a temporary branch from the current state
-        let temp_branch = format!("git-wippy-temp-{}", source_branch);
-        git.execute(vec![
-            "checkout".to_string(),
-            "-b".to_string(),
-            temp_branch.clone(),
-        ])
-        .await
-        .context("Failed to create temporary branch")?;
-
-        // Apply the stash to the temporary branch
-        let apply_result = git
-            .execute(vec![
-                "stash".to_string(),
-                "apply".to_string(),
-                stash_ref.clone(),
-            ])
-            .await;
-
-        // Switch back to the target branch
-        git.execute(vec!["checkout".to_string(), source_branch.clone()])
-            .await
-            .context("Failed to switch back to source branch")?;
-
-        match apply_result {
-            Ok(_) => {
-                // Try to merge the temporary branch
-                let merge_result = git
-                    .execute(vec![
-                        "merge".to_string(),
-                        "--no-commit".to_string(), // Don't create a merge commit
-                        "--no-ff".to_string(),     // Always create a merge to handle conflicts
-                        temp_branch.clone(),
-                    ])
-                    .await;
-
-                // Clean up the temporary branch
-                git.execute(vec![
-                    "branch".to_string(),
-                    "-D".to_string(),
-                    temp_branch.clone(),
-                ])
-                .await
-                .context("Failed to delete temporary branch")?;
-
-                match merge_result {
-                    Ok(_) => {
-                        // Drop the stash if we successfully applied it
-                        git.execute(vec![
-                            "stash".to_string(),
-                            "drop".to_string(),
-                            stash_ref.clone(),
-                        ])
-                        .await
-                        .context("Failed to drop stash")?;
-                        output.info(&t_with_args("applied-stash", &[]))?;
-                    }
-                    Err(e) => {
-                        // Don't fail on conflicts, let the user handle them
-                        if !e.to_string().contains("conflict") {
-                            return Err(anyhow::anyhow!(
-                                "Failed to restore existing changes: {}",
-                                e
-                            ));
-                        }
-                    }
+            // Only delete a copy that was pushed, WIPs saved with --local have none
+            if let Some(remote) = remote {
+                let remote_ref = format!("refs/remotes/{}/{}", remote, selected_branch);
+                if git.branch_exists(&remote_ref).await? {
+                    git.delete_remote_branch(&remote, &selected_branch).await?;
+                    result.deleted_remote = Some(remote);
+                    let message =
+                        t_with_args("deleted-remote-branch", &[("name", &selected_branch)]);
+                    output.info(
+                        &output
+                            .format_with_highlights(&message, &[&format!("'{}'", selected_branch)]),
+                    )?;
                 }
             }
-            Err(e) => {
-                // Clean up the temporary branch
-                git.execute(vec![
-                    "branch".to_string(),
-                    "-D".to_string(),
-                    temp_branch.clone(),
-                ])
+        }
+    }
+
+    // The stash is only dropped last, a rollback before this point pops it instead
+    if autostash {
+        if let Some(stash_ref) = git.find_stash(&stash_name).await? {
+            git.execute(vec!["stash".to_string(), "drop".to_string(), stash_ref])
                 .await
-                .context("Failed to delete temporary branch")?;
-                return Err(anyhow::anyhow!("Failed to apply stashed changes: {}", e));
-            }
+                .context("Failed to drop stash")?;
         }
+        output.info(&t_with_args("applied-stash", &[]))?;
     }
 
-    // Now that we've successfully applied all changes, we can delete the WIP branch
-    git.delete_branch(&selected_branch, true).await?;
-    let message = t_with_args("deleted-local-branch", &[("name", &selected_branch)]);
+    let message = t_with_args("restore-complete", &[("name", &selected_branch)]);
     output.info(&output.format_with_highlights(&message, &[&format!("'{}'", selected_branch)]))?;
 
-    // Delete the remote branch if it exists
-    let remotes = git.get_remotes().await?;
-    if remotes.contains(&"origin".to_string()) {
-        git.delete_remote_branch("origin", &selected_branch).await?;
-        let message = t_with_args("deleted-remote-branch", &[("name", &selected_branch)]);
-        output
-            .info(&output.format_with_highlights(&message, &[&format!("'{}'", selected_branch)]))?;
+    Ok(result)
+}
+
+/// Works out where the saved changes go.
+///
+/// An `--onto` branch must exist. Otherwise the work goes back where it was
+/// saved: the source branch, recreated if it no longer exists, or the exact
+/// commit of a detached HEAD. Work saved without either is restored onto HEAD.
+async fn resolve_target(
+    git: &impl Git,
+    head: &Head,
+    metadata: &WipMetadata,
+    onto: Option<&str>,
+) -> Result<Target> {
+    let branch_target = |branch: &str| {
+        if head.branch.as_deref() == Some(branch) {
+            Target::Head
+        } else {
+            Target::Branch(branch.to_string())
+        }
+    };
+
+    if let Some(onto) = onto {
+        if !git.branch_exists(&format!("refs/heads/{}", onto)).await? {
+            anyhow::bail!("Branch '{}' given with --onto does not exist", onto);
+        }
+        return Ok(branch_target(onto));
     }
 
-    let message = t_with_args("restore-complete", &[("name", &selected_branch)]);
-    output.info(&output.format_with_highlights(&message, &[&format!("'{}'", selected_branch)]))?;
+    let source_branch = &metadata.source_branch;
+    if !source_branch.is_empty() {
+        // Being on the source branch also covers an unborn one
+        if head.branch.as_deref() == Some(source_branch.as_str())
+            || git.branch_exists(source_branch).await?
+        {
+            return Ok(branch_target(source_branch));
+        }
+        return Ok(Target::NewBranch {
+            branch: source_branch.clone(),
+            start_point: metadata.source_commit.clone(),
+        });
+    }
 
-    Ok(())
+    match &metadata.source_commit {
+        Some(commit) if head.branch.is_some() || head.commit.as_ref() != Some(commit) => {
+            Ok(Target::Detached(commit.clone()))
+        }
+        _ => Ok(Target::Head),
+    }
+}
+
+/// Asks where to restore work whose source branch no longer exists, e.g. because
+/// it was renamed or merged and deleted.
+///
+/// Offers to recreate the branch first, followed by the local branches that
+/// aren't WIP branches.
+async fn prompt_for_target(
+    git: &impl Git,
+    head: &Head,
+    branch: String,
+    start_point: Option<String>,
+) -> Result<Target> {
+    let template = BranchTemplate::load(git).await?;
+    let branches: Vec<String> = git
+        .get_local_branches()
+        .await?
+        .into_iter()
+        .filter(|name| template.match_name(name).is_none())
+        .collect();
+
+    let mut labels = vec![match &start_point {
+        Some(commit) => t_with_args(
+            "restore-target-recreate",
+            &[("name", &branch), ("commit", short_commit(commit))],
+        ),
+        None => t_with_args("restore-target-create", &[("name", &branch)]),
+    }];
+    labels.extend(branches.iter().map(|name| {
+        if head.branch.as_deref() == Some(name.as_str()) {
+            t_with_args("restore-target-current", &[("name", name)])
+        } else {
+            name.clone()
+        }
+    }));
+
+    let selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt(t_with_args("restore-target-prompt", &[("name", &branch)]))
+        .items(&labels)
+        .default(0)
+        .interact()
+        .context("Failed to select a branch to restore onto")?;
+
+    Ok(match selection {
+        0 => Target::NewBranch {
+            branch,
+            start_point,
+        },
+        i if head.branch.as_ref() == Some(&branches[i - 1]) => Target::Head,
+        i => Target::Branch(branches[i - 1].clone()),
+    })
+}
+
+/// Whether `wippy.keepRemote` asks to keep the remote copy of restored WIPs.
+async fn keep_remote_configured(git: &impl Git) -> Result<bool> {
+    let value = git.get_config_value(KEEP_REMOTE_CONFIG_KEY).await?;
+    Ok(value.is_some_and(|value| {
+        matches!(
+            value.trim().to_ascii_lowercase().as_str(),
+            "true" | "yes" | "on" | "1"
+        )
+    }))
+}
+
+/// Picks the WIP branch of the current user that a command works on.
+///
+/// A given branch name must be one of the user's WIP branches. One that isn't
+/// known here yet, e.g. because it was pushed from another machine, is fetched
+/// from `remote`, `wippy.remote` or `origin` first. Without a name, a single WIP
+/// branch is picked as is and several are offered in a prompt.
+///
+/// # Returns
+/// * `Ok(Some(WipBranch))` - The selected branch, read it through
+///   [`WipBranch::reference`] as it may only exist on a remote
+/// * `Ok(None)` if the branch wasn't found or the user has no WIP branches,
+///   which has been reported already
+pub(crate) async fn select_wip_branch(
+    git: &impl Git,
+    output: &Output,
+    branch_name: Option<String>,
+    remote: Option<&str>,
+    prompt: &str,
+) -> Result<Option<WipBranch>> {
+    let username = git_username_with_git(git).await?;
+    let mut wip_branches = git.get_user_wip_branches(&username).await?;
+
+    if let Some(name) = branch_name {
+        if !wip_branches.iter().any(|branch| branch.name == name) {
+            if let Some(remote) = wip_remote_with_git(git, remote).await? {
+                if git.fetch_branch(&remote, &name).await? {
+                    let message = t_with_args(
+                        "fetched-wip-branch",
+                        &[("name", &name), ("remote", &remote)],
+                    );
+                    output.info(
+                        &output.format_with_highlights(&message, &[&format!("'{}'", name)]),
+                    )?;
+                    wip_branches = git.get_user_wip_branches(&username).await?;
+                }
+            }
+        }
+        match wip_branches.into_iter().find(|branch| branch.name == name) {
+            Some(branch) => Ok(Some(branch)),
+            None => {
+                let message = t_with_args("branch-not-found", &[("name", &name)]);
+                output.info(&output.format_with_highlights(&message, &[&format!("'{}'", name)]))?;
+                Ok(None)
+            }
+        }
+    } else if wip_branches.len() > 1 {
+        output.ensure_can_prompt("pass the name of the WIP branch")?;
+        let references: Vec<String> = wip_branches.iter().map(WipBranch::reference).collect();
+        let mut labels = Vec::with_capacity(references.len());
+        for reference in &references {
+            labels.push(branch_label(git, reference).await);
+        }
+        let selected = get_user_selection(&references, &labels, prompt).await?;
+        Ok(wip_branches
+            .into_iter()
+            .find(|branch| branch.reference() == selected))
+    } else if let Some(branch) = wip_branches.pop() {
+        Ok(Some(branch))
+    } else {
+        let message = t_with_args("no-wip-branches", &[("username", &username)]);
+        output.info(&output.format_with_highlights(&message, &[&username]))?;
+        Ok(None)
+    }
 }
 
 /// Prompts the user to select a WIP branch from a list.
 ///
 /// # Arguments
 /// * `options` - List of branch names to choose from
+/// * `labels` - Display label for each branch, in the same order
+/// * `prompt` - The question shown above the list
 ///
 /// # Returns
 /// * `Ok(String)` - The selected branch name
 /// * `Err` if user interaction fails
-async fn get_user_selection(options: &[String]) -> Result<String> {
+async fn get_user_selection(options: &[String], labels: &[String], prompt: &str) -> Result<String> {
     let selection = Select::with_theme(&ColorfulTheme::default())
-        .with_prompt("Select a WIP branch to restore")
-        .items(&options)
+        .with_prompt(prompt)
+        .items(labels)
         .default(0)
         .interact()
         .context("Failed to select a WIP branch")?;
@@ -288,6 +646,109 @@ async fn get_user_selection(options: &[String]) -> Result<String> {
     Ok(options[selection].clone())
 }
 
+/// Applies the saved changes onto the current commit.
+///
+/// If the current commit is the one the work was saved on, the index and working
+/// tree are restored exactly. Otherwise the source branch moved on, or the work
+/// goes onto another branch, so the changes from the base to the WIP are merged
+/// in with a three-way merge and nothing committed since is overwritten.
+///
+/// # Arguments
+/// * `current` - The commit being restored onto, or the empty tree on an unborn branch
+/// * `current_label` - How the current side is named in conflict markers
+///
+/// # Returns
+/// * `Ok(Vec<String>)` - Files left with conflict markers, empty if the merge was clean
+async fn apply_wip(
+    git: &impl Git,
+    metadata: &WipMetadata,
+    wip_branch: &str,
+    current: &str,
+    current_label: &str,
+) -> Result<Vec<String>> {
+    let base = match &metadata.index_commit {
+        Some(index_commit) => {
+            let base = parent_or_empty_tree(git, index_commit).await?;
+            if base == current {
+                restore_index_and_worktree(git, index_commit, wip_branch).await?;
+                return Ok(Vec::new());
+            }
+            base
+        }
+        // WIPs saved before the index was recorded separately sit right on their base
+        None => parent_or_empty_tree(git, wip_branch).await?,
+    };
+
+    let conflicts = git
+        .merge_into_worktree(&base, current, current_label, wip_branch, wip_branch)
+        .await
+        .context("Failed to merge the saved changes")?;
+    if !conflicts.is_empty() {
+        return Ok(conflicts);
+    }
+
+    // The merge staged everything. Move the index back to the current commit without
+    // touching the files, then restage what was staged when saving. Staged hunks
+    // can't be carried over a merge, so partially staged files end up unstaged.
+    let merged = git.write_tree().await?;
+    git.read_tree_merge(&merged, current, false).await?;
+    // Staged paths the merge left as they are on the current commit, e.g. a file
+    // deleted on both sides, have nothing to stage and would fail `git add`
+    let mut differing: HashSet<String> = git.get_changed_files().await?.into_iter().collect();
+    differing.extend(git.get_untracked_files().await?);
+    let staged_files = metadata
+        .staged_files
+        .iter()
+        .filter(|file| differing.contains(*file))
+        .cloned()
+        .collect();
+    recreate_file_states(
+        git,
+        staged_files,
+        metadata.changed_files.clone(),
+        metadata.untracked_files.clone(),
+    )
+    .await?;
+    Ok(Vec::new())
+}
+
+/// Restores the saved index and working tree on top of the current branch.
+///
+/// The WIP branch is `base -> index commit -> worktree commit`. Each step is applied
+/// with a two-tree `read-tree` merge, so only paths the WIP touched are updated and
+/// git refuses to overwrite anything that changed on the current branch since.
+///
+/// # Arguments
+/// * `git` - Git implementation to use for commands
+/// * `index_commit` - The commit recording the index at save time
+/// * `wip_branch` - The WIP branch whose tip records the working tree
+///
+/// # Details
+/// * Applies `base -> index` to both the index and the working tree
+/// * Applies `index -> worktree` to both, so unstaged hunks land in the files
+/// * Moves the index back from `worktree` to `index` without touching the files,
+///   leaving unstaged hunks unstaged and untracked files untracked
+pub(crate) async fn restore_index_and_worktree(
+    git: &impl Git,
+    index_commit: &str,
+    wip_branch: &str,
+) -> Result<()> {
+    // The index commit sits on the commit the work was saved on, or is a root
+    // commit if it was saved on an unborn branch
+    let base = parent_or_empty_tree(git, index_commit).await?;
+    git.read_tree_merge(&base, index_commit, true)
+        .await
+        .context("Failed to restore staged changes")?;
+    git.read_tree_merge(index_commit, wip_branch, true)
+        .await
+        .context("Failed to restore unstaged changes")?;
+    git.read_tree_merge(wip_branch, index_commit, false)
+        .await
+        .context("Failed to restore the index")?;
+
+    Ok(())
+}
+
 /// Recreates the original state of files in the working directory.
 ///
 /// # Arguments
@@ -297,8 +758,8 @@ async fn get_user_selection(options: &[String]) -> Result<String> {
 /// * `untracked_files` - Files that should be untracked
 ///
 /// # Details
-/// * Stages files using `git add`
-/// * Unstages files using `git reset HEAD`
+/// * Stages files using `git add`, one process for all files
+/// * Unstages files using `git reset`, one process for all files
 /// * Ensures correct tracking status for each file
 async fn recreate_file_states(
     git: &impl Git,
@@ -320,6 +781,114 @@ mod tests {
     use crate::utils::MockGit;
     use anyhow::Result;
 
+    fn on_branch(branch: &str) -> Head {
+        Head {
+            branch: Some(branch.to_string()),
+            commit: Some("head-sha".to_string()),
+        }
+    }
+
+    fn saved_on(branch: &str) -> WipMetadata {
+        WipMetadata {
+            source_branch: branch.to_string(),
+            source_commit: Some("base-sha".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resolve_target_onto() -> Result<()> {
+        let mut mock_git = MockGit::new();
+        mock_git
+            .expect_branch_exists()
+            .withf(|branch| branch == "refs/heads/renamed")
+            .returning(|_| Ok(true));
+        mock_git
+            .expect_branch_exists()
+            .withf(|branch| branch == "refs/heads/missing")
+            .returning(|_| Ok(false));
+
+        // --onto wins over the source branch
+        let target = resolve_target(
+            &mock_git,
+            &on_branch("main"),
+            &saved_on("feature"),
+            Some("renamed"),
+        )
+        .await?;
+        assert_eq!(target, Target::Branch("renamed".to_string()));
+        let target = resolve_target(
+            &mock_git,
+            &on_branch("renamed"),
+            &saved_on("feature"),
+            Some("renamed"),
+        )
+        .await?;
+        assert_eq!(target, Target::Head);
+
+        let error = resolve_target(
+            &mock_git,
+            &on_branch("main"),
+            &saved_on("feature"),
+            Some("missing"),
+        )
+        .await
+        .unwrap_err();
+        assert!(error.to_string().contains("'missing' given with --onto"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_resolve_target_source() -> Result<()> {
+        let mut mock_git = MockGit::new();
+        mock_git
+            .expect_branch_exists()
+            .withf(|branch| branch == "feature")
+            .returning(|_| Ok(true));
+        mock_git
+            .expect_branch_exists()
+            .withf(|branch| branch == "merged")
+            .returning(|_| Ok(false));
+
+        let target =
+            resolve_target(&mock_git, &on_branch("main"), &saved_on("feature"), None).await?;
+        assert_eq!(target, Target::Branch("feature".to_string()));
+
+        // A deleted source branch is recreated where the work was saved
+        let target =
+            resolve_target(&mock_git, &on_branch("main"), &saved_on("merged"), None).await?;
+        assert_eq!(
+            target,
+            Target::NewBranch {
+                branch: "merged".to_string(),
+                start_point: Some("base-sha".to_string()),
+            }
+        );
+
+        // Work saved on a detached HEAD goes back to its commit
+        let target = resolve_target(&mock_git, &on_branch("main"), &saved_on(""), None).await?;
+        assert_eq!(target, Target::Detached("base-sha".to_string()));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_keep_remote_configured() -> Result<()> {
+        for (value, expected) in [
+            (None, false),
+            (Some("true"), true),
+            (Some("Yes"), true),
+            (Some("false"), false),
+        ] {
+            let mut mock_git = MockGit::new();
+            mock_git
+                .expect_get_config_value()
+                .withf(|key| key == KEEP_REMOTE_CONFIG_KEY)
+                .returning(move |_| Ok(value.map(String::from)));
+            assert_eq!(keep_remote_configured(&mock_git).await?, expected);
+        }
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_recreate_file_states() -> Result<()> {
         let mut mock_git = MockGit::new();
@@ -362,4 +931,207 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_restore_index_and_worktree() -> Result<()> {
+        let mut mock_git = MockGit::new();
+        let mut seq = mockall::Sequence::new();
+
+        mock_git
+            .expect_get_parents()
+            .with(mockall::predicate::eq("index-sha"))
+            .returning(|_| Ok(vec!["base-sha".to_string()]));
+
+        // Staged changes go to the index and the working tree first
+        mock_git
+            .expect_read_tree_merge()
+            .with(
+                mockall::predicate::eq("base-sha"),
+                mockall::predicate::eq("index-sha"),
+                mockall::predicate::eq(true),
+            )
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|_, _, _| Ok("".to_string()));
+
+        // Then the unstaged changes on top of them
+        mock_git
+            .expect_read_tree_merge()
+            .with(
+                mockall::predicate::eq("index-sha"),
+                mockall::predicate::eq("wip/test-user/2024-03-21-17-59-30"),
+                mockall::predicate::eq(true),
+            )
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|_, _, _| Ok("".to_string()));
+
+        // Finally the index is moved back without touching the files
+        mock_git
+            .expect_read_tree_merge()
+            .with(
+                mockall::predicate::eq("wip/test-user/2024-03-21-17-59-30"),
+                mockall::predicate::eq("index-sha"),
+                mockall::predicate::eq(false),
+            )
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|_, _, _| Ok("".to_string()));
+
+        restore_index_and_worktree(&mock_git, "index-sha", "wip/test-user/2024-03-21-17-59-30")
+            .await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_apply_wip_merges_onto_moved_branch() -> Result<()> {
+        let mut mock_git = MockGit::new();
+        let mut seq = mockall::Sequence::new();
+        let metadata = WipMetadata {
+            index_commit: Some("index-sha".to_string()),
+            staged_files: vec!["a.txt".to_string()],
+            ..Default::default()
+        };
+
+        mock_git
+            .expect_get_parents()
+            .with(mockall::predicate::eq("index-sha"))
+            .returning(|_| Ok(vec!["base-sha".to_string()]));
+
+        // The branch moved on, so the WIP is merged in instead of copied over
+        mock_git
+            .expect_merge_into_worktree()
+            .withf(|base, ours, ours_label, theirs, _| {
+                base == "base-sha" && ours == "tip-sha" && ours_label == "main" && theirs == "wip"
+            })
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|_, _, _, _, _| Ok(Vec::new()));
+        mock_git
+            .expect_write_tree()
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|| Ok("merged-tree".to_string()));
+        mock_git
+            .expect_read_tree_merge()
+            .with(
+                mockall::predicate::eq("merged-tree"),
+                mockall::predicate::eq("tip-sha"),
+                mockall::predicate::eq(false),
+            )
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|_, _, _| Ok("".to_string()));
+        mock_git
+            .expect_get_changed_files()
+            .returning(|| Ok(vec!["a.txt".to_string()]));
+        mock_git
+            .expect_get_untracked_files()
+            .returning(|| Ok(Vec::new()));
+        mock_git
+            .expect_stage_files()
+            .withf(|files| files == ["a.txt".to_string()])
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|_| Ok(()));
+        mock_git.expect_unstage_files().returning(|_| Ok(()));
+
+        let conflicts = apply_wip(&mock_git, &metadata, "wip", "tip-sha", "main").await?;
+        assert!(conflicts.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_apply_wip_skips_deletion_made_on_both_sides() -> Result<()> {
+        let mut mock_git = MockGit::new();
+        // The WIP staged the deletion of gone.txt, and the branch deleted it too
+        let metadata = WipMetadata {
+            index_commit: Some("index-sha".to_string()),
+            staged_files: vec![
+                "gone.txt".to_string(),
+                "a.txt".to_string(),
+                "new.txt".to_string(),
+            ],
+            ..Default::default()
+        };
+        mock_git
+            .expect_get_parents()
+            .returning(|_| Ok(vec!["base-sha".to_string()]));
+        mock_git
+            .expect_merge_into_worktree()
+            .returning(|_, _, _, _, _| Ok(Vec::new()));
+        mock_git
+            .expect_write_tree()
+            .returning(|| Ok("merged-tree".to_string()));
+        mock_git
+            .expect_read_tree_merge()
+            .returning(|_, _, _| Ok("".to_string()));
+        // gone.txt is in neither the merged tree nor the index
+        mock_git
+            .expect_get_changed_files()
+            .returning(|| Ok(vec!["a.txt".to_string()]));
+        mock_git
+            .expect_get_untracked_files()
+            .returning(|| Ok(vec!["new.txt".to_string()]));
+        mock_git
+            .expect_stage_files()
+            .withf(|files| files == ["a.txt".to_string(), "new.txt".to_string()])
+            .times(1)
+            .returning(|_| Ok(()));
+        mock_git.expect_unstage_files().returning(|_| Ok(()));
+
+        let conflicts = apply_wip(&mock_git, &metadata, "wip", "tip-sha", "main").await?;
+        assert!(conflicts.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_apply_wip_conflicts() -> Result<()> {
+        let mut mock_git = MockGit::new();
+        mock_git
+            .expect_get_parents()
+            .returning(|_| Ok(vec!["base-sha".to_string()]));
+        mock_git
+            .expect_merge_into_worktree()
+            .returning(|_, _, _, _, _| Ok(vec!["a.txt".to_string()]));
+
+        // Conflicted files keep their unmerged entries
+        mock_git.expect_read_tree_merge().never();
+        mock_git.expect_stage_files().never();
+
+        let conflicts =
+            apply_wip(&mock_git, &WipMetadata::default(), "wip", "tip-sha", "main").await?;
+        assert_eq!(conflicts, vec!["a.txt".to_string()]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_restore_index_and_worktree_root_commit() -> Result<()> {
+        let mut mock_git = MockGit::new();
+
+        // Work saved on an unborn branch is applied on top of the empty tree
+        mock_git.expect_get_parents().returning(|_| Ok(vec![]));
+        mock_git
+            .expect_empty_tree()
+            .returning(|| Ok("empty-tree".to_string()));
+        mock_git
+            .expect_read_tree_merge()
+            .with(
+                mockall::predicate::eq("empty-tree"),
+                mockall::predicate::eq("index-sha"),
+                mockall::predicate::eq(true),
+            )
+            .times(1)
+            .returning(|_, _, _| Ok("".to_string()));
+        mock_git
+            .expect_read_tree_merge()
+            .times(2)
+            .returning(|_, _, _| Ok("".to_string()));
+
+        restore_index_and_worktree(&mock_git, "index-sha", "wip/test-user/2024-03-21-17-59-30")
+            .await?;
+
+        Ok(())
+    }
 }
@@ -1,255 +1,786 @@
-use crate::i18n::t;
-use crate::output::Output;
-use crate::utils::{formatted_datetime, git_username_with_git, Git, GitCommand};
+use crate::commands::list::{sort_newest_first, WipSummary};
+use crate::commands::recover::abort;
+use crate::i18n::{t, t_with_args};
+use crate::output::{Output, OutputFormat};
+use crate::utils::{
+    check_branch_name, check_formatted_datetime, copy_file, format_commit_message,
+    formatted_datetime, git_username_with_git, hostname, open_git, remove_file, sanitize_component,
+    sanitize_path, slugify, username_component, wip_remote_with_git, BranchFields, BranchTemplate,
+    Git, Head, Journal, Step, WipBranch, WipMetadata, METADATA_VERSION, TEMPLATE_CONFIG_KEY,
+};
 use anyhow::Result;
+use serde::Serialize;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-pub async fn save_wip_changes(
-    local: bool,
-    username: Option<String>,
-    datetime: Option<String>,
-) -> Result<()> {
-    let git = GitCommand::new();
-    save_wip_changes_with_git(&git, local, username, datetime).await
+pub struct SaveOptions {
+    pub local: bool,
+    pub remote: Option<String>,
+    pub keep: bool,
+    pub username: Option<String>,
+    pub datetime: Option<String>,
+    pub message: Option<String>,
+    pub update: Option<Option<String>>,
+    pub format: OutputFormat,
 }
 
-pub async fn save_wip_changes_with_git(
+/// The result of a save, as printed by `--format json`.
+#[derive(Serialize)]
+pub(crate) struct SaveResult {
+    pub(crate) branch: String,
+    pub(crate) source_branch: Option<String>,
+    pub(crate) source_commit: Option<String>,
+    message: Option<String>,
+    pushed_to: Option<String>,
+    kept_changes: bool,
+    updated: bool,
+}
+
+/// An existing WIP branch that a `save --update` adds a snapshot to.
+struct UpdateTarget {
+    branch: WipBranch,
+    /// The latest snapshot, which becomes the new one's previous snapshot
+    previous: String,
+    metadata: WipMetadata,
+}
+
+/// Saves the current changes to a new WIP branch.
+///
+/// The WIP commits are built with plumbing, so `HEAD` is never switched and no
+/// hooks run. The branch is `base -> index commit -> worktree commit`, the same
+/// shape `git stash` uses to keep staged and unstaged changes apart. If any step
+/// fails, the branch, the push and the cleared working tree are rolled back.
+///
+/// # Arguments
+/// * `options` - Configuration for the save operation
+///   - `local`: Don't push the WIP branch to the remote
+///   - `remote`: Remote to push to instead of `wippy.remote` or `origin`
+///   - `keep`: Leave the changes in the working tree after saving
+///   - `username`: Optional username to use in the branch name
+///   - `datetime`: Optional timestamp to use in the branch name
+///   - `message`: Optional message describing the saved work
+///   - `update`: Add a snapshot to an existing WIP branch instead of creating
+///     one: the given one, or the newest saved from the current branch. A new
+///     branch is created if there is none
+///   - `format`: Print progress messages or a JSON result
+///
+/// # Details
+/// * The branch is named after `wippy.branchTemplate`, `wip/{user}/{date}` by
+///   default
+pub async fn save_wip_changes(options: SaveOptions) -> Result<()> {
+    let git = open_git();
+    save_wip_changes_with_git(&git, options).await
+}
+
+pub async fn save_wip_changes_with_git(git: &impl Git, options: SaveOptions) -> Result<()> {
+    let output = Output::with_format(options.format).await?;
+    let result = save_changes(git, &output, options).await?;
+    output.result(&result)
+}
+
+/// Runs a save, rolling back everything it did if it fails.
+pub(crate) async fn save_changes(
     git: &impl Git,
-    local: bool,
-    username: Option<String>,
-    datetime: Option<String>,
-) -> Result<()> {
-    let output = Output::new().await?;
+    output: &Output,
+    options: SaveOptions,
+) -> Result<SaveResult> {
+    let mut journal = Journal::start(git, "save").await?;
+
+    match save_with_journal(git, output, &mut journal, options).await {
+        Ok(result) => {
+            journal.finish().await?;
+            Ok(result)
+        }
+        Err(error) => Err(abort(git, output, journal, error).await),
+    }
+}
 
+/// Runs the save, recording every change it makes so a failure can be rolled back.
+async fn save_with_journal(
+    git: &impl Git,
+    output: &Output,
+    journal: &mut Journal,
+    options: SaveOptions,
+) -> Result<SaveResult> {
     // Use provided values or get them from functions
-    let username = match username {
-        Some(u) => u,
+    let username = match options.username {
+        Some(u) => username_component(&u)?,
         None => git_username_with_git(git).await?,
     };
-    let datetime = match datetime {
-        Some(d) => d,
+    let datetime = match options.datetime {
+        Some(d) => {
+            check_formatted_datetime(&d)?;
+            d
+        }
         None => formatted_datetime(),
     };
 
-    let branch_name = format!("wip/{}/{}", username, datetime);
-
-    // Store the current branch name before switching
-    let original_branch = git.get_current_branch().await?;
+    let head = git.get_head().await?;
+    let update = match options.update {
+        Some(branch) => find_update_target(git, &head, &username, branch).await?,
+        None => None,
+    };
+    // Snapshots keep the message of the WIP unless a new one is given
+    let message = options.message.or_else(|| {
+        update
+            .as_ref()
+            .and_then(|update| update.metadata.message.clone())
+    });
+    let branch_name = match &update {
+        Some(update) => update.branch.name.clone(),
+        None => branch_name(git, &head, username, datetime, message.as_deref()).await?,
+    };
 
     output.info(&t("saving-wip"))?;
 
-    // Generate the detailed commit message
-    let commit_message = generate_commit_message(git).await?;
+    // Collect the metadata before anything is recorded
+    let mut metadata = collect_metadata(git, &head, message.as_deref()).await?;
+    metadata.previous_snapshot = update.as_ref().map(|update| update.previous.clone());
 
-    // Create and switch to the new branch
-    git.create_branch(&branch_name).await?;
-    output.info(
-        &output.format_with_highlights(&t("created-branch"), &[&format!("'{}'", branch_name)]),
-    )?;
+    // Like `git commit`, refuse to save a WIP without any changes
+    let (index_tree, worktree_tree) = snapshot_trees(git).await?;
+    let head_tree = match &head.commit {
+        Some(commit) => git.rev_parse(&format!("{}^{{tree}}", commit)).await?,
+        None => git.empty_tree().await?,
+    };
+    if index_tree == head_tree && worktree_tree == index_tree && metadata.untracked_files.is_empty()
+    {
+        anyhow::bail!("No local changes to save");
+    }
 
-    git.stage_all().await?;
-    output.info(&t("staged-all-changes"))?;
+    // Record the index on its own commit so partially staged files can be restored exactly.
+    // On an unborn branch there is nothing to build on, so it becomes a root commit.
+    let parents: Vec<String> = head.commit.iter().cloned().collect();
+    let index_commit = git
+        .commit_tree(
+            &index_tree,
+            &parents,
+            &format!("index on {}", head.describe()),
+        )
+        .await?;
+    metadata.index_commit = Some(index_commit.clone());
+    output.info(&t("recorded-changes"))?;
 
-    git.commit(&commit_message).await?;
+    // Snapshots also have the previous one as a parent, so it is pushed and kept with them
+    let mut parents = vec![index_commit.clone()];
+    parents.extend(metadata.previous_snapshot.clone());
+    let wip_commit = git
+        .commit_tree(&worktree_tree, &parents, &format_commit_message(&metadata))
+        .await?;
     output.info(&t("committed-changes"))?;
 
-    if !local {
-        // Check if there are any remotes configured
-        let remotes = git.get_remotes().await?;
-        if !remotes.is_empty() {
-            git.push("origin", &branch_name).await?;
-            output.info(&t("pushed-changes"))?;
-        } else {
-            output.info(&t("skipped-push-no-remote"))?;
+    let reference = format!("refs/heads/{}", branch_name);
+    match &update {
+        Some(update) if update.branch.local => {
+            git.update_ref(&reference, &wip_commit, &update.previous)
+                .await?;
+            journal
+                .record(Step::UpdatedBranch {
+                    branch: branch_name.clone(),
+                    previous: update.previous.clone(),
+                    commit: wip_commit.clone(),
+                })
+                .await?;
+            let message = t_with_args("updated-branch", &[("name", &branch_name)]);
+            output
+                .info(&output.format_with_highlights(&message, &[&format!("'{}'", branch_name)]))?;
+        }
+        // A WIP that was only pushed from another machine gets a local branch
+        _ => {
+            git.create_ref(&reference, &wip_commit).await?;
+            journal
+                .record(Step::CreatedBranch {
+                    branch: branch_name.clone(),
+                })
+                .await?;
+            output.info(
+                &output
+                    .format_with_highlights(&t("created-branch"), &[&format!("'{}'", branch_name)]),
+            )?;
         }
     }
 
-    git.checkout(&original_branch).await?;
-    output.info(
-        &output.format_with_highlights(&t("switched-back"), &[&format!("'{}'", original_branch)]),
-    )?;
-
-    output.info(
-        &output.format_with_highlights(&t("wip-branch-created"), &[&format!("'{}'", branch_name)]),
-    )?;
-    Ok(())
-}
-
-async fn generate_commit_message(git: &impl Git) -> Result<String> {
-    let staged = git.get_staged_files().await?;
-    let changed = git.get_changed_files().await?;
-    let untracked = git.get_untracked_files().await?;
-    let source_branch = git.get_current_branch().await?;
+    let mut pushed_to = None;
+    if !options.local {
+        match wip_remote_with_git(git, options.remote.as_deref()).await? {
+            Some(remote) => {
+                // Snapshots build on the previous one, so the push is a fast-forward
+                let pushed_before = update
+                    .as_ref()
+                    .is_some_and(|update| update.branch.remotes.contains(&remote));
+                let step = if pushed_before {
+                    let remote_ref = format!("refs/remotes/{}/{}", remote, branch_name);
+                    Step::PushedUpdate {
+                        remote: remote.clone(),
+                        branch: branch_name.clone(),
+                        previous: git.rev_parse(&remote_ref).await?,
+                    }
+                } else {
+                    Step::PushedBranch {
+                        remote: remote.clone(),
+                        branch: branch_name.clone(),
+                    }
+                };
+                git.push(&remote, &branch_name).await?;
+                journal.record(step).await?;
+                pushed_to = Some(remote);
+                output.info(&t("pushed-changes"))?;
+            }
+            None => output.info(&t("skipped-push-no-remote"))?,
+        }
+    }
 
-    let staged_section = if !staged.is_empty() {
-        format!("\nStaged changes:\n\t{}", staged.replace("\n", "\n\t"))
+    if options.keep {
+        output.info(&t("kept-changes"))?;
     } else {
-        String::new()
-    };
+        // Clear the saved changes, like `git stash --include-untracked` does
+        journal
+            .record(Step::ClearedWorkingTree {
+                index_commit,
+                wip_commit,
+            })
+            .await?;
+        git.reset_hard().await?;
+        if !metadata.untracked_files.is_empty() {
+            git.clean_untracked().await?;
+        }
+        output.info(&t("cleared-changes"))?;
+    }
 
-    let changed_section = if !changed.is_empty() {
-        format!("\nChanges:\n\t{}", changed.replace("\n", "\n\t"))
+    let done = if update.is_some() {
+        t_with_args("wip-snapshot-added", &[("name", &branch_name)])
     } else {
-        String::new()
+        t("wip-branch-created")
     };
+    output.info(&output.format_with_highlights(&done, &[&format!("'{}'", branch_name)]))?;
+    Ok(SaveResult {
+        branch: branch_name,
+        source_branch: head.branch,
+        source_commit: head.commit,
+        message,
+        pushed_to,
+        kept_changes: options.keep,
+        updated: update.is_some(),
+    })
+}
 
-    let untracked_section = if !untracked.is_empty() {
-        format!("\nUntracked:\n\t{}", untracked.replace("\n", "\n\t"))
-    } else {
-        String::new()
+/// Finds the WIP branch a `save --update` adds a snapshot to.
+///
+/// # Arguments
+/// * `branch` - The WIP branch to update, or `None` for the user's newest WIP
+///   saved from the current branch, or from the current commit on a detached HEAD
+///
+/// # Returns
+/// * `Ok(Some(UpdateTarget))` - The branch and its latest snapshot
+/// * `Ok(None)` if no WIP was saved from the current branch yet
+/// * `Err` if the given branch isn't one of the user's WIP branches
+async fn find_update_target(
+    git: &impl Git,
+    head: &Head,
+    username: &str,
+    branch: Option<String>,
+) -> Result<Option<UpdateTarget>> {
+    let wip_branches = git.get_user_wip_branches(username).await?;
+
+    let summary = match branch {
+        Some(name) => {
+            let Some(branch) = wip_branches.into_iter().find(|branch| branch.name == name) else {
+                anyhow::bail!("No WIP branch '{}' to update", name);
+            };
+            WipSummary::new(git, branch).await
+        }
+        None => {
+            let mut summaries = Vec::with_capacity(wip_branches.len());
+            for branch in wip_branches {
+                summaries.push(WipSummary::new(git, branch).await);
+            }
+            sort_newest_first(&mut summaries);
+            let source_branch = head.branch.clone().unwrap_or_default();
+            let Some(summary) = summaries.into_iter().find(|summary| {
+                summary.metadata.source_branch == source_branch
+                    && (!source_branch.is_empty() || summary.metadata.source_commit == head.commit)
+            }) else {
+                return Ok(None);
+            };
+            summary
+        }
     };
 
-    let message = format!(
-        "chore: saving work in progress\n\nSource branch: {}{}{}{}",
-        source_branch, staged_section, changed_section, untracked_section
+    let previous = git.rev_parse(&summary.branch.reference()).await?;
+    Ok(Some(UpdateTarget {
+        branch: summary.branch,
+        previous,
+        metadata: summary.metadata,
+    }))
+}
+
+/// Builds the name of the WIP branch from `wippy.branchTemplate`.
+///
+/// Every placeholder value is sanitized, and the final name is checked against
+/// git's rules before anything is written.
+async fn branch_name(
+    git: &impl Git,
+    head: &Head,
+    username: String,
+    datetime: String,
+    message: Option<&str>,
+) -> Result<String> {
+    let name = BranchTemplate::load(git).await?.render(&BranchFields {
+        user: username,
+        date: datetime,
+        source: sanitize_path(&head.describe()),
+        slug: message
+            .map(slugify)
+            .filter(|slug| !slug.is_empty())
+            .unwrap_or_else(|| "untitled".to_string()),
+        host: sanitize_component(&hostname()),
+    });
+
+    // The placeholders are safe on their own, so anything left is down to the
+    // template itself, e.g. a value ending in `.` at the end of it
+    check_branch_name(&name).map_err(|reason| {
+        anyhow::anyhow!(
+            "'{}' is not a valid branch name, {}. Check {}",
+            name,
+            reason,
+            TEMPLATE_CONFIG_KEY
+        )
+    })?;
+    Ok(name)
+}
+
+/// Counts the copies of the index this process has made, so each gets its own file.
+static TEMP_INDEXES: AtomicUsize = AtomicUsize::new(0);
+
+/// Writes trees of the index and of the whole working tree, including untracked files.
+///
+/// Both are written from a private copy of the index, so the repository's own
+/// index is never locked or modified and a `watch` can't get in the way of
+/// other git commands. The copy keeps the cached file stats of the index, so
+/// unchanged files aren't hashed again. It is named after the process and the
+/// call, so a `watch` running in the background and a `save` or `diff` never
+/// share one.
+///
+/// # Returns
+/// * `Ok((String, String))` - The ids of the index tree and the working tree tree
+pub(crate) async fn snapshot_trees(git: &impl Git) -> Result<(String, String)> {
+    let git_dir = git.git_dir().await?;
+    let index_file = format!(
+        "{}/wippy-index-{}-{}",
+        git_dir,
+        std::process::id(),
+        TEMP_INDEXES.fetch_add(1, Ordering::Relaxed)
     );
+    let env = vec![("GIT_INDEX_FILE".to_string(), index_file.clone())];
+
+    let trees = async {
+        // Before anything is staged there is no index, which git reads as empty.
+        // The copy keeps the time the index was written, which git compares file
+        // times against to tell whether files changed since.
+        copy_file(&format!("{}/index", git_dir), &index_file).await?;
+        let index_tree = git
+            .execute_with_env(vec!["write-tree".to_string()], env.clone())
+            .await?;
+        git.execute_with_env(vec!["add".to_string(), "-A".to_string()], env.clone())
+            .await?;
+        let worktree_tree = git
+            .execute_with_env(vec!["write-tree".to_string()], env)
+            .await?;
+        Ok((index_tree, worktree_tree))
+    }
+    .await;
 
-    Ok(message)
+    // The copy has served its purpose whether or not the trees were written
+    let _ = remove_file(&index_file).await;
+    trees
+}
+
+/// Describes the changes in the working tree the way a WIP commit records them.
+pub(crate) async fn collect_metadata(
+    git: &impl Git,
+    head: &Head,
+    message: Option<&str>,
+) -> Result<WipMetadata> {
+    Ok(WipMetadata {
+        version: METADATA_VERSION,
+        source_branch: head.branch.clone().unwrap_or_default(),
+        source_commit: head.commit.clone(),
+        // Keep the message on a single line so it fits in one trailer
+        message: message.map(normalize_message).filter(|m| !m.is_empty()),
+        index_commit: None,
+        previous_snapshot: None,
+        dropped_snapshots: 0,
+        staged_files: git.get_staged_files().await?,
+        changed_files: git.get_changed_files().await?,
+        untracked_files: git.get_untracked_files().await?,
+    })
+}
+
+/// Collapses a possibly multi-line message into a single trimmed line.
+fn normalize_message(message: &str) -> String {
+    message
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::utils::MockGit;
+    use mockall::predicate::{eq, function};
+    use tempfile::TempDir;
 
-    #[tokio::test]
-    async fn test_save_wip_changes_local() -> Result<()> {
-        let mut mock_git = MockGit::new();
-
-        // Mock username lookup
-        mock_git
-            .expect_execute()
-            .with(mockall::predicate::eq(vec![
-                "config".to_string(),
-                "user.name".to_string(),
-            ]))
-            .returning(|_| Ok("test-user".to_string()));
-
-        // Mock get_current_branch
-        mock_git
-            .expect_get_current_branch()
-            .returning(|| Ok("main".to_string()));
-
-        // Mock getting staged files
+    /// Mocks reading the file lists for the metadata.
+    fn expect_file_lists(
+        mock_git: &mut MockGit,
+        staged: &[&str],
+        changed: &[&str],
+        untracked: &[&str],
+    ) {
+        let files =
+            |files: &[&str]| -> Vec<String> { files.iter().map(|f| f.to_string()).collect() };
+        let (staged, changed, untracked) = (files(staged), files(changed), files(untracked));
         mock_git
             .expect_get_staged_files()
-            .returning(|| Ok("file1.txt".to_string()));
-
-        // Mock getting changed files
+            .returning(move || Ok(staged.clone()));
         mock_git
             .expect_get_changed_files()
-            .returning(|| Ok("file2.txt".to_string()));
-
-        // Mock getting untracked files
+            .returning(move || Ok(changed.clone()));
         mock_git
             .expect_get_untracked_files()
-            .returning(|| Ok("file3.txt".to_string()));
+            .returning(move || Ok(untracked.clone()));
+    }
 
-        // Mock create_branch
+    /// Mocks a git directory in a temporary directory, where the journal that
+    /// records the steps of the save is written.
+    fn expect_journal(mock_git: &mut MockGit) -> TempDir {
+        let git_dir = TempDir::new().unwrap();
+        let path = git_dir.path().to_string_lossy().to_string();
+        mock_git
+            .expect_git_dir()
+            .returning(move || Ok(path.clone()));
+        git_dir
+    }
+
+    /// Mocks writing the index as `index-tree` and the working tree as
+    /// `worktree-tree` through a copy of the index in `git_dir`.
+    fn expect_trees(mock_git: &mut MockGit, git_dir: &TempDir) {
+        let index_prefix = format!(
+            "{}/wippy-index-{}-",
+            git_dir.path().display(),
+            std::process::id()
+        );
+        let uses_copy = move |env: &Vec<(String, String)>| {
+            env.len() == 1 && env[0].0 == "GIT_INDEX_FILE" && env[0].1.starts_with(&index_prefix)
+        };
+        let mut seq = mockall::Sequence::new();
+        let check = uses_copy.clone();
+        mock_git
+            .expect_execute_with_env()
+            .withf(move |args, env| *args == ["write-tree"] && check(env))
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|_, _| Ok("index-tree".to_string()));
+        let check = uses_copy.clone();
+        mock_git
+            .expect_execute_with_env()
+            .withf(move |args, env| *args == ["add", "-A"] && check(env))
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|_, _| Ok("".to_string()));
         mock_git
-            .expect_create_branch()
-            .with(mockall::predicate::function(|branch: &str| {
-                branch.starts_with("wip/test-user/")
-            }))
-            .returning(|_| Ok("Created branch".to_string()));
+            .expect_execute_with_env()
+            .withf(move |args, env| *args == ["write-tree"] && uses_copy(env))
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|_, _| Ok("worktree-tree".to_string()));
+    }
 
-        // Mock stage_all
+    /// Mocks recording the index as `index-sha` on top of `base-sha`, and
+    /// writing the working tree as `worktree-tree`.
+    fn expect_index_commit(mock_git: &mut MockGit, git_dir: &TempDir) {
+        mock_git
+            .expect_get_config_value()
+            .with(eq("wippy.branchTemplate"))
+            .returning(|_| Ok(None));
+        mock_git.expect_get_head().returning(|| {
+            Ok(Head {
+                branch: Some("main".to_string()),
+                commit: Some("base-sha".to_string()),
+            })
+        });
+        expect_trees(mock_git, git_dir);
+        mock_git
+            .expect_rev_parse()
+            .with(eq("base-sha^{tree}"))
+            .returning(|_| Ok("base-tree".to_string()));
         mock_git
-            .expect_stage_all()
-            .returning(|| Ok("Changes staged".to_string()));
+            .expect_commit_tree()
+            .withf(|tree, parents, message| {
+                tree == "index-tree"
+                    && parents == ["base-sha".to_string()]
+                    && message == "index on main"
+            })
+            .returning(|_, _, _| Ok("index-sha".to_string()));
+    }
 
-        // Mock commit
+    /// Mocks recording the index and working tree and creating the branch.
+    ///
+    /// `check_message` is called with the message of the worktree commit.
+    fn expect_snapshot(
+        mock_git: &mut MockGit,
+        git_dir: &TempDir,
+        branch_prefix: &'static str,
+        check_message: fn(&str) -> bool,
+    ) {
+        expect_index_commit(mock_git, git_dir);
+        mock_git
+            .expect_commit_tree()
+            .withf(move |tree, parents, message| {
+                tree == "worktree-tree"
+                    && parents == ["index-sha".to_string()]
+                    && check_message(message)
+            })
+            .returning(|_, _, _| Ok("wip-sha".to_string()));
         mock_git
-            .expect_commit()
-            .with(mockall::predicate::function(|msg: &str| {
-                msg.contains("Source branch: main")
-                    && msg.contains("Staged changes:\n\tfile1.txt")
-                    && msg.contains("Changes:\n\tfile2.txt")
-                    && msg.contains("Untracked:\n\tfile3.txt")
-            }))
-            .returning(|_| Ok("Created commit".to_string()));
+            .expect_create_ref()
+            .withf(move |reference, target| {
+                reference.starts_with(&format!("refs/heads/{}", branch_prefix))
+                    && target == "wip-sha"
+            })
+            .returning(|_, _| Ok("".to_string()));
+    }
 
-        // Mock checkout back to original branch
+    #[tokio::test]
+    async fn test_snapshot_trees_use_copy_of_index() -> Result<()> {
+        let mut mock_git = MockGit::new();
+        let git_dir = expect_journal(&mut mock_git);
+        let index = git_dir.path().join("index");
+        std::fs::write(&index, "the index")?;
+        let written =
+            std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000);
+        std::fs::File::options()
+            .write(true)
+            .open(&index)?
+            .set_modified(written)?;
+        let index_files = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = index_files.clone();
+        mock_git.expect_execute().never();
         mock_git
-            .expect_checkout()
-            .with(mockall::predicate::eq("main"))
-            .returning(|_| Ok("Switched back to branch 'main'".to_string()));
+            .expect_execute_with_env()
+            .returning(move |args, env| {
+                // Every command works on the copy, never on the index itself
+                assert_eq!(std::fs::read_to_string(&env[0].1).unwrap(), "the index");
+                // Racily clean files are only noticed if the copy is as old as the index
+                let modified = std::fs::metadata(&env[0].1).unwrap().modified().unwrap();
+                assert_eq!(modified, written);
+                if args == ["add", "-A"] {
+                    recorded.lock().unwrap().push(env[0].1.clone());
+                }
+                Ok("tree".to_string())
+            });
+
+        snapshot_trees(&mock_git).await?;
+        snapshot_trees(&mock_git).await?;
 
-        save_wip_changes_with_git(&mock_git, true, None, None).await?;
+        // A concurrent `watch` and `save` can't remove each other's copy
+        let index_files = index_files.lock().unwrap();
+        assert_eq!(index_files.len(), 2);
+        assert_ne!(index_files[0], index_files[1]);
+        assert!(index_files[0].starts_with(&format!(
+            "{}/wippy-index-{}-",
+            git_dir.path().display(),
+            std::process::id()
+        )));
+        assert!(!std::path::Path::new(&index_files[0]).exists());
+        assert_eq!(std::fs::read_to_string(&index)?, "the index");
         Ok(())
     }
 
     #[tokio::test]
-    async fn test_save_wip_changes_with_remote() -> Result<()> {
+    async fn test_save_wip_changes_local() -> Result<()> {
         let mut mock_git = MockGit::new();
 
         // Mock username lookup
         mock_git
             .expect_execute()
-            .with(mockall::predicate::eq(vec![
-                "config".to_string(),
-                "user.name".to_string(),
-            ]))
+            .with(eq(vec!["config".to_string(), "user.name".to_string()]))
             .returning(|_| Ok("test-user".to_string()));
 
-        // Mock get_current_branch
-        mock_git
-            .expect_get_current_branch()
-            .times(2)
-            .returning(|| Ok("main".to_string()));
+        expect_file_lists(
+            &mut mock_git,
+            &["file1.txt"],
+            &["file2.txt"],
+            &["file3.txt"],
+        );
+        let git_dir = expect_journal(&mut mock_git);
 
-        // Mock getting staged files
-        mock_git
-            .expect_get_staged_files()
-            .returning(|| Ok("file1.txt".to_string()));
+        // The metadata ends up in the worktree commit
+        expect_snapshot(&mut mock_git, &git_dir, "wip/test-user/", |msg| {
+            msg.contains("Wippy-Version: 1\n")
+                && msg.contains("Wippy-Source-Branch: main\n")
+                && msg.contains("Wippy-Source-Commit: base-sha\n")
+                && msg.contains("Wippy-Index: index-sha\n")
+                && msg.contains("Wippy-Staged: file1.txt\n")
+                && msg.contains("Wippy-Changed: file2.txt\n")
+                && msg.ends_with("Wippy-Untracked: file3.txt")
+        });
 
-        // Mock getting changed files
+        // The saved changes are cleared from the working tree
         mock_git
-            .expect_get_changed_files()
-            .returning(|| Ok("file2.txt".to_string()));
-
-        // Mock getting untracked files
+            .expect_reset_hard()
+            .times(1)
+            .returning(|| Ok("".to_string()));
         mock_git
-            .expect_get_untracked_files()
-            .returning(|| Ok("file3.txt".to_string()));
+            .expect_clean_untracked()
+            .times(1)
+            .returning(|| Ok("".to_string()));
 
-        // Mock create_branch
-        mock_git
-            .expect_create_branch()
-            .with(mockall::predicate::function(|branch: &str| {
-                branch.starts_with("wip/test-user/")
-            }))
-            .returning(|_| Ok("Created branch".to_string()));
+        save_wip_changes_with_git(
+            &mock_git,
+            SaveOptions {
+                local: true,
+                remote: None,
+                keep: false,
+                username: None,
+                datetime: None,
+                message: None,
+                update: None,
+                format: OutputFormat::Text,
+            },
+        )
+        .await?;
+        Ok(())
+    }
 
-        // Mock stage_all
-        mock_git
-            .expect_stage_all()
-            .returning(|| Ok("Changes staged".to_string()));
+    #[tokio::test]
+    async fn test_save_wip_changes_with_remote() -> Result<()> {
+        let mut mock_git = MockGit::new();
 
-        // Mock commit
+        // Mock username lookup
         mock_git
-            .expect_commit()
-            .returning(|_| Ok("Created commit".to_string()));
+            .expect_execute()
+            .with(eq(vec!["config".to_string(), "user.name".to_string()]))
+            .returning(|_| Ok("test-user".to_string()));
+
+        expect_file_lists(&mut mock_git, &["file1.txt"], &["file2.txt"], &[]);
+        let git_dir = expect_journal(&mut mock_git);
+        expect_snapshot(&mut mock_git, &git_dir, "wip/test-user/", |_| true);
 
         // Mock get_remotes
         mock_git
             .expect_get_remotes()
             .returning(|| Ok(vec!["origin".to_string()]));
+        mock_git.expect_get_config_value().returning(|_| Ok(None));
 
         // Mock push
         mock_git
             .expect_push()
+            .with(
+                eq("origin"),
+                function(|branch: &str| branch.starts_with("wip/test-user/")),
+            )
+            .times(1)
             .returning(|_, _| Ok("Pushed changes".to_string()));
 
-        // Mock checkout back to original branch
+        // Nothing untracked was saved, so there is nothing to clean
         mock_git
-            .expect_checkout()
-            .with(mockall::predicate::eq("main"))
-            .returning(|_| Ok("Switched back to branch 'main'".to_string()));
+            .expect_reset_hard()
+            .times(1)
+            .returning(|| Ok("".to_string()));
+        mock_git.expect_clean_untracked().never();
 
-        save_wip_changes_with_git(&mock_git, false, None, None).await?;
+        save_wip_changes_with_git(
+            &mock_git,
+            SaveOptions {
+                local: false,
+                remote: None,
+                keep: false,
+                username: None,
+                datetime: None,
+                message: None,
+                update: None,
+                format: OutputFormat::Text,
+            },
+        )
+        .await?;
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_save_wip_changes_configured_remote() -> Result<()> {
+        let mut mock_git = MockGit::new();
+
+        expect_file_lists(&mut mock_git, &[], &["file2.txt"], &[]);
+        let git_dir = expect_journal(&mut mock_git);
+        expect_snapshot(&mut mock_git, &git_dir, "wip/test-user/", |_| true);
+
+        // wippy.remote picks the remote even though origin exists
+        mock_git
+            .expect_get_remotes()
+            .returning(|| Ok(vec!["origin".to_string(), "me".to_string()]));
+        mock_git
+            .expect_get_config_value()
+            .with(eq("wippy.remote"))
+            .returning(|_| Ok(Some("me".to_string())));
+        mock_git
+            .expect_push()
+            .with(
+                eq("me"),
+                function(|branch: &str| branch.starts_with("wip/test-user/")),
+            )
+            .times(1)
+            .returning(|_, _| Ok("".to_string()));
+        mock_git
+            .expect_reset_hard()
+            .returning(|| Ok("".to_string()));
+
+        save_wip_changes_with_git(
+            &mock_git,
+            SaveOptions {
+                local: false,
+                remote: None,
+                keep: false,
+                username: Some("test-user".to_string()),
+                datetime: None,
+                message: None,
+                update: None,
+                format: OutputFormat::Text,
+            },
+        )
+        .await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_save_wip_changes_invalid_datetime() {
+        let mut mock_git = MockGit::new();
+        let _git_dir = expect_journal(&mut mock_git);
+
+        // Nothing is written for a name that can't be used
+        mock_git.expect_execute_with_env().never();
+        mock_git.expect_create_ref().never();
+
+        let error = save_wip_changes_with_git(
+            &mock_git,
+            SaveOptions {
+                local: true,
+                remote: None,
+                keep: false,
+                username: Some("Jane Doe".to_string()),
+                datetime: Some("yesterday".to_string()),
+                message: None,
+                update: None,
+                format: OutputFormat::Text,
+            },
+        )
+        .await
+        .unwrap_err();
+        assert!(error.to_string().contains("--datetime 'yesterday'"));
+    }
+
     #[tokio::test]
     async fn test_save_wip_changes_no_remote() -> Result<()> {
         let mut mock_git = MockGit::new();
@@ -257,61 +788,302 @@ mod tests {
         // Mock username lookup
         mock_git
             .expect_execute()
-            .with(mockall::predicate::eq(vec![
-                "config".to_string(),
-                "user.name".to_string(),
-            ]))
+            .with(eq(vec!["config".to_string(), "user.name".to_string()]))
             .returning(|_| Ok("test-user".to_string()));
 
-        // Mock get_current_branch
-        mock_git
-            .expect_get_current_branch()
-            .times(2)
-            .returning(|| Ok("main".to_string()));
+        expect_file_lists(
+            &mut mock_git,
+            &["file1.txt"],
+            &["file2.txt"],
+            &["file3.txt"],
+        );
+        let git_dir = expect_journal(&mut mock_git);
+        expect_snapshot(&mut mock_git, &git_dir, "wip/test-user/", |_| true);
+
+        // Mock get_remotes - return empty list
+        mock_git.expect_get_remotes().returning(|| Ok(vec![]));
+        mock_git.expect_get_config_value().returning(|_| Ok(None));
+        mock_git.expect_push().never();
 
-        // Mock getting staged files
         mock_git
-            .expect_get_staged_files()
-            .returning(|| Ok("file1.txt".to_string()));
+            .expect_reset_hard()
+            .returning(|| Ok("".to_string()));
+        mock_git
+            .expect_clean_untracked()
+            .returning(|| Ok("".to_string()));
+
+        save_wip_changes_with_git(
+            &mock_git,
+            SaveOptions {
+                local: false,
+                remote: None,
+                keep: false,
+                username: None,
+                datetime: None,
+                message: None,
+                update: None,
+                format: OutputFormat::Text,
+            },
+        )
+        .await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_save_wip_changes_with_message() -> Result<()> {
+        let mut mock_git = MockGit::new();
+
+        expect_file_lists(&mut mock_git, &[], &["file2.txt"], &[]);
+        let git_dir = expect_journal(&mut mock_git);
+
+        // The message must be stored as a single trailer
+        expect_snapshot(
+            &mut mock_git,
+            &git_dir,
+            "wip/test-user/2024-03-21-17-59-30",
+            |msg| {
+                msg.contains(
+                    "Wippy-Source-Commit: base-sha\nWippy-Message: Feature work in progress\n",
+                ) && msg.ends_with("Wippy-Changed: file2.txt")
+            },
+        );
 
-        // Mock getting changed files
         mock_git
-            .expect_get_changed_files()
-            .returning(|| Ok("file2.txt".to_string()));
+            .expect_reset_hard()
+            .returning(|| Ok("".to_string()));
 
-        // Mock getting untracked files
+        save_wip_changes_with_git(
+            &mock_git,
+            SaveOptions {
+                local: true,
+                remote: None,
+                keep: false,
+                username: Some("test-user".to_string()),
+                datetime: Some("2024-03-21-17-59-30".to_string()),
+                message: Some("Feature work\n  in progress".to_string()),
+                update: None,
+                format: OutputFormat::Text,
+            },
+        )
+        .await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_save_wip_changes_clean_tree() {
+        let mut mock_git = MockGit::new();
         mock_git
-            .expect_get_untracked_files()
-            .returning(|| Ok("file3.txt".to_string()));
+            .expect_execute()
+            .with(eq(vec!["config".to_string(), "user.name".to_string()]))
+            .returning(|_| Ok("test-user".to_string()));
+        expect_file_lists(&mut mock_git, &[], &[], &[]);
+        let _git_dir = expect_journal(&mut mock_git);
+        mock_git.expect_get_config_value().returning(|_| Ok(None));
+        mock_git.expect_get_head().returning(|| {
+            Ok(Head {
+                branch: Some("main".to_string()),
+                commit: Some("base-sha".to_string()),
+            })
+        });
+        // The index, the working tree and HEAD all hold the same tree
+        mock_git
+            .expect_rev_parse()
+            .returning(|_| Ok("base-tree".to_string()));
+        mock_git
+            .expect_execute_with_env()
+            .returning(|_, _| Ok("base-tree".to_string()));
+        mock_git.expect_commit_tree().never();
+        mock_git.expect_create_ref().never();
+        mock_git.expect_push().never();
+
+        let error = save_wip_changes_with_git(
+            &mock_git,
+            SaveOptions {
+                local: false,
+                remote: None,
+                keep: false,
+                username: None,
+                datetime: None,
+                message: None,
+                update: None,
+                format: OutputFormat::Text,
+            },
+        )
+        .await
+        .unwrap_err();
+        assert!(error.to_string().contains("No local changes"));
+    }
+
+    #[tokio::test]
+    async fn test_save_wip_changes_keep() -> Result<()> {
+        let mut mock_git = MockGit::new();
 
-        // Mock create_branch
+        expect_file_lists(&mut mock_git, &["file1.txt"], &[], &["file3.txt"]);
+        let git_dir = expect_journal(&mut mock_git);
+        expect_snapshot(&mut mock_git, &git_dir, "wip/test-user/", |_| true);
+
+        // With --keep the working tree is left alone
+        mock_git.expect_reset_hard().never();
+        mock_git.expect_clean_untracked().never();
+
+        save_wip_changes_with_git(
+            &mock_git,
+            SaveOptions {
+                local: true,
+                remote: None,
+                keep: true,
+                username: Some("test-user".to_string()),
+                datetime: None,
+                message: None,
+                update: None,
+                format: OutputFormat::Text,
+            },
+        )
+        .await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_save_wip_changes_rolls_back_failed_push() {
+        let mut mock_git = MockGit::new();
+
+        expect_file_lists(&mut mock_git, &["file1.txt"], &[], &[]);
+        let git_dir = expect_journal(&mut mock_git);
+        expect_snapshot(&mut mock_git, &git_dir, "wip/test-user/", |_| true);
+
+        mock_git
+            .expect_get_remotes()
+            .returning(|| Ok(vec!["origin".to_string()]));
+        mock_git.expect_get_config_value().returning(|_| Ok(None));
         mock_git
-            .expect_create_branch()
-            .with(mockall::predicate::function(|branch: &str| {
-                branch.starts_with("wip/test-user/")
-            }))
-            .returning(|_| Ok("Created branch".to_string()));
+            .expect_push()
+            .returning(|_, _| Err(anyhow::anyhow!("rejected")));
 
-        // Mock stage_all
+        // The branch that was created is deleted again and the working tree is left alone
+        mock_git
+            .expect_branch_exists()
+            .with(eq("wip/test-user/2024-03-21-17-59-30"))
+            .returning(|_| Ok(true));
         mock_git
-            .expect_stage_all()
-            .returning(|| Ok("Changes staged".to_string()));
+            .expect_delete_branch()
+            .with(eq("wip/test-user/2024-03-21-17-59-30"), eq(true))
+            .times(1)
+            .returning(|_, _| Ok("".to_string()));
+        mock_git.expect_reset_hard().never();
 
-        // Mock commit
+        let error = save_wip_changes_with_git(
+            &mock_git,
+            SaveOptions {
+                local: false,
+                remote: None,
+                keep: false,
+                username: Some("test-user".to_string()),
+                datetime: Some("2024-03-21-17-59-30".to_string()),
+                message: None,
+                update: None,
+                format: OutputFormat::Text,
+            },
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(error.to_string(), "rejected");
+    }
+
+    #[tokio::test]
+    async fn test_save_wip_changes_update() -> Result<()> {
+        const BRANCH: &str = "wip/test-user/2024-03-21-17-59-30";
+        let mut mock_git = MockGit::new();
         mock_git
-            .expect_commit()
-            .returning(|_| Ok("Created commit".to_string()));
+            .expect_execute()
+            .with(eq(vec!["config".to_string(), "user.name".to_string()]))
+            .returning(|_| Ok("test-user".to_string()));
+        expect_file_lists(&mut mock_git, &[], &["file2.txt"], &[]);
+        let git_dir = expect_journal(&mut mock_git);
+        expect_index_commit(&mut mock_git, &git_dir);
+        // No new branch is created
+        mock_git.expect_create_ref().never();
+        mock_git
+            .expect_get_remotes()
+            .returning(|| Ok(vec!["origin".to_string()]));
+        mock_git.expect_get_config_value().returning(|_| Ok(None));
 
-        // Mock get_remotes - return empty list
-        mock_git.expect_get_remotes().returning(|| Ok(vec![]));
+        // The newest WIP saved from main is updated, not the one from another branch
+        mock_git.expect_get_user_wip_branches().returning(|_| {
+            Ok(["2024-03-21-17-59-30", "2024-03-22-09-00-00"]
+                .iter()
+                .map(|timestamp| WipBranch {
+                    name: format!("wip/test-user/{}", timestamp),
+                    user: "test-user".to_string(),
+                    timestamp: timestamp.to_string(),
+                    local: true,
+                    remotes: vec!["origin".to_string()],
+                })
+                .collect())
+        });
+        for (branch, source) in [
+            (BRANCH, "main"),
+            ("wip/test-user/2024-03-22-09-00-00", "feature"),
+        ] {
+            let message = format!(
+                "chore: saving work in progress\n\nWippy-Version: 1\nWippy-Source-Branch: {}\nWippy-Message: Login form",
+                source
+            );
+            mock_git
+                .expect_get_commit_message()
+                .with(eq(branch))
+                .returning(move |_| Ok(message.clone()));
+        }
+        mock_git
+            .expect_rev_parse()
+            .with(eq(BRANCH))
+            .returning(|_| Ok("previous-sha".to_string()));
+        mock_git
+            .expect_rev_parse()
+            .with(eq(format!("refs/remotes/origin/{}", BRANCH)))
+            .returning(|_| Ok("previous-sha".to_string()));
 
-        // Mock checkout back to original branch
+        // The snapshot builds on the previous one and keeps its message
         mock_git
-            .expect_checkout()
-            .with(mockall::predicate::eq("main"))
-            .returning(|_| Ok("Switched back to branch 'main'".to_string()));
+            .expect_commit_tree()
+            .withf(|tree, parents, message| {
+                tree == "worktree-tree"
+                    && parents == ["index-sha".to_string(), "previous-sha".to_string()]
+                    && message.contains("Wippy-Message: Login form\n")
+                    && message.contains("Wippy-Previous: previous-sha\n")
+            })
+            .returning(|_, _, _| Ok("wip-sha".to_string()));
+        mock_git
+            .expect_update_ref()
+            .with(
+                eq(format!("refs/heads/{}", BRANCH)),
+                eq("wip-sha"),
+                eq("previous-sha"),
+            )
+            .times(1)
+            .returning(|_, _, _| Ok(String::new()));
+        mock_git
+            .expect_push()
+            .with(eq("origin"), eq(BRANCH))
+            .times(1)
+            .returning(|_, _| Ok(String::new()));
+        mock_git
+            .expect_reset_hard()
+            .times(1)
+            .returning(|| Ok("".to_string()));
 
-        save_wip_changes_with_git(&mock_git, false, None, None).await?;
-        Ok(())
+        save_wip_changes_with_git(
+            &mock_git,
+            SaveOptions {
+                local: false,
+                remote: None,
+                keep: false,
+                username: None,
+                datetime: None,
+                message: None,
+                update: Some(None),
+                format: OutputFormat::Text,
+            },
+        )
+        .await
     }
 }
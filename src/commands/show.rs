@@ -0,0 +1,271 @@
+use crate::commands::restore::select_wip_branch;
+use crate::i18n::t_with_args;
+use crate::output::{Output, OutputFormat};
+use crate::utils::{
+    open_git, parent_or_empty_tree, parse_commit_message, parse_formatted_datetime,
+    parse_snapshot_name, resolve_snapshot, short_commit, Git, WipMetadata,
+};
+use anyhow::Result;
+use serde::Serialize;
+
+pub struct ShowOptions {
+    pub branch_name: Option<String>,
+    pub patch: bool,
+    pub format: OutputFormat,
+}
+
+/// A WIP branch as printed by `show --format json`.
+#[derive(Serialize, Default)]
+struct ShowResult {
+    branch: Option<String>,
+    source_branch: Option<String>,
+    source_commit: Option<String>,
+    timestamp: Option<String>,
+    message: Option<String>,
+    staged_files: Vec<String>,
+    changed_files: Vec<String>,
+    untracked_files: Vec<String>,
+    base: Option<String>,
+    diff: Option<String>,
+}
+
+/// Shows what a WIP branch holds without restoring it.
+///
+/// # Arguments
+/// * `options` - Configuration for the show operation
+///   - `branch_name`: Optional name of the branch to show, with `@{n}` to show
+///     the snapshot n saves before the latest one
+///   - `patch`: Show the full patch instead of a diffstat
+///   - `format`: Print the details or a JSON record
+///
+/// # Details
+/// * Prints the saved metadata and the files grouped by their state
+/// * Diffs the WIP against the commit it was saved on, through git's pager and colors
+pub async fn show_wip_branch(options: ShowOptions) -> Result<()> {
+    let git = open_git();
+    show_wip_branch_with_git(&git, options).await
+}
+
+pub async fn show_wip_branch_with_git(git: &impl Git, options: ShowOptions) -> Result<()> {
+    let output = Output::with_format(options.format).await?;
+    let (branch_name, snapshot_index) = match options.branch_name.as_deref() {
+        Some(name) => parse_snapshot_name(name).map(|(name, index)| (Some(name), index))?,
+        None => (None, 0),
+    };
+    let Some(wip_branch) = select_wip_branch(
+        git,
+        &output,
+        branch_name,
+        None,
+        "Select a WIP branch to show",
+    )
+    .await?
+    else {
+        return output.result(&ShowResult::default());
+    };
+    let reference = resolve_snapshot(git, &wip_branch.reference(), snapshot_index).await?;
+    let branch = match snapshot_index {
+        0 => wip_branch.name.clone(),
+        index => format!("{}@{{{}}}", wip_branch.name, index),
+    };
+
+    let metadata = parse_commit_message(&git.get_commit_message(&reference).await?);
+    // Branches saved by older versions may have a free-form date
+    let timestamp = Some(match parse_formatted_datetime(&wip_branch.timestamp) {
+        Some(saved_at) => saved_at.format("%Y-%m-%d %H:%M:%S").to_string(),
+        None => wip_branch.timestamp.clone(),
+    });
+
+    // Older WIP branches have no index commit and sit right on the source commit
+    let base = match &metadata.index_commit {
+        Some(index_commit) => parent_or_empty_tree(git, index_commit).await?,
+        None => parent_or_empty_tree(git, &reference).await?,
+    };
+    let diff_args = vec![
+        "diff".to_string(),
+        if options.patch { "--patch" } else { "--stat" }.to_string(),
+        base.clone(),
+        reference,
+    ];
+
+    if output.is_json() {
+        let diff = git.execute(diff_args).await?;
+        return output.result(&ShowResult {
+            branch: Some(branch),
+            source_branch: Some(metadata.source_branch.clone()).filter(|b| !b.is_empty()),
+            source_commit: metadata.source_commit.clone(),
+            timestamp,
+            message: metadata.message.clone(),
+            staged_files: metadata.staged_files,
+            changed_files: metadata.changed_files,
+            untracked_files: metadata.untracked_files,
+            base: Some(base),
+            diff: Some(diff),
+        });
+    }
+
+    print_details(&output, &branch, &metadata, timestamp.as_deref())?;
+    output.info("")?;
+    git.execute_in_terminal(diff_args).await
+}
+
+/// Prints the metadata of a WIP branch and its files grouped by state.
+fn print_details(
+    output: &Output,
+    branch: &str,
+    metadata: &WipMetadata,
+    timestamp: Option<&str>,
+) -> Result<()> {
+    let heading = t_with_args("show-heading", &[("name", branch)]);
+    output.info(&output.format_with_highlights(&heading, &[&format!("'{}'", branch)]))?;
+
+    let source = match (metadata.source_branch.as_str(), &metadata.source_commit) {
+        ("", Some(commit)) => short_commit(commit).to_string(),
+        ("", None) => "?".to_string(),
+        (branch, Some(commit)) => format!("{} ({})", branch, short_commit(commit)),
+        (branch, None) => branch.to_string(),
+    };
+    output.info(&format!(
+        "  {}",
+        t_with_args("show-source", &[("source", &source)])
+    ))?;
+    if let Some(timestamp) = timestamp {
+        output.info(&format!(
+            "  {}",
+            t_with_args("show-saved", &[("timestamp", timestamp)])
+        ))?;
+    }
+    if let Some(message) = &metadata.message {
+        output.info(&format!(
+            "  {}",
+            t_with_args("wip-message", &[("message", message)])
+        ))?;
+    }
+
+    for (key, files) in [
+        ("show-staged-files", &metadata.staged_files),
+        ("show-changed-files", &metadata.changed_files),
+        ("show-untracked-files", &metadata.untracked_files),
+    ] {
+        if files.is_empty() {
+            continue;
+        }
+        output.info("")?;
+        output.info(&t_with_args(key, &[]))?;
+        for file in files {
+            output.info(&format!("  {}", file))?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{MockGit, WipBranch};
+    use mockall::predicate::eq;
+
+    const BRANCH: &str = "wip/test-user/2024-03-21-17-59-30";
+
+    /// Mocks a WIP branch, or one that was only pushed from another machine
+    fn mock_wip_branch(local: bool) -> MockGit {
+        let mut mock_git = MockGit::new();
+        mock_git
+            .expect_execute()
+            .with(eq(vec!["config".to_string(), "user.name".to_string()]))
+            .returning(|_| Ok("test-user".to_string()));
+        mock_git.expect_get_user_wip_branches().returning(move |_| {
+            Ok(vec![WipBranch {
+                name: BRANCH.to_string(),
+                user: "test-user".to_string(),
+                timestamp: "2024-03-21-17-59-30".to_string(),
+                local,
+                remotes: if local {
+                    Vec::new()
+                } else {
+                    vec!["origin".to_string()]
+                },
+            }])
+        });
+        mock_git.expect_get_config_value().returning(|_| Ok(None));
+        mock_git
+            .expect_get_commit_message()
+            .with(eq(if local {
+                BRANCH.to_string()
+            } else {
+                format!("origin/{}", BRANCH)
+            }))
+            .returning(|_| {
+                Ok("chore: saving work in progress
+
+Wippy-Version: 1
+Wippy-Source-Branch: main
+Wippy-Source-Commit: base-sha
+Wippy-Index: index-sha
+Wippy-Staged: a.txt
+Wippy-Untracked: b.txt"
+                    .to_string())
+            });
+        mock_git
+            .expect_get_parents()
+            .with(eq("index-sha"))
+            .returning(|_| Ok(vec!["base-sha".to_string()]));
+        mock_git
+    }
+
+    #[tokio::test]
+    async fn test_show_diffstat_in_terminal() -> Result<()> {
+        let mut mock_git = mock_wip_branch(true);
+
+        // The diff is left to git so it is paged and colored
+        mock_git
+            .expect_execute_in_terminal()
+            .with(eq(vec![
+                "diff".to_string(),
+                "--stat".to_string(),
+                "base-sha".to_string(),
+                BRANCH.to_string(),
+            ]))
+            .times(1)
+            .returning(|_| Ok(()));
+
+        show_wip_branch_with_git(
+            &mock_git,
+            ShowOptions {
+                branch_name: None,
+                patch: false,
+                format: OutputFormat::Text,
+            },
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_show_remote_only_patch_as_json() -> Result<()> {
+        let mut mock_git = mock_wip_branch(false);
+
+        // The branch is read from the remote, nothing is created locally
+        mock_git
+            .expect_execute()
+            .with(eq(vec![
+                "diff".to_string(),
+                "--patch".to_string(),
+                "base-sha".to_string(),
+                format!("origin/{}", BRANCH),
+            ]))
+            .times(1)
+            .returning(|_| Ok("diff --git a/a.txt b/a.txt".to_string()));
+        mock_git.expect_execute_in_terminal().never();
+
+        show_wip_branch_with_git(
+            &mock_git,
+            ShowOptions {
+                branch_name: Some(BRANCH.to_string()),
+                patch: true,
+                format: OutputFormat::Json,
+            },
+        )
+        .await
+    }
+}
@@ -0,0 +1,354 @@
+use crate::i18n::t_with_args;
+use crate::output::{Output, OutputFormat};
+use crate::utils::{
+    git_username_with_git, open_git, wip_remote_with_git, BranchTemplate, Git, WipBranch,
+};
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+pub struct SyncOptions {
+    pub remote: Option<String>,
+    pub format: OutputFormat,
+}
+
+/// What a sync found or did for a WIP branch.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum SyncStatus {
+    /// The local and the remote copy are the same
+    UpToDate,
+    /// Only existed locally and was pushed
+    Pushed,
+    /// Only exists on the remote, e.g. because it was saved on another machine
+    RemoteOnly,
+    /// Was deleted on the remote, e.g. restored on another machine, so the
+    /// local copy was deleted too
+    Pruned,
+    /// The local copy differs from the remote one, or changed since the remote
+    /// one was deleted, and was left alone
+    Diverged,
+}
+
+impl SyncStatus {
+    fn label(self) -> String {
+        let key = match self {
+            Self::UpToDate => "sync-up-to-date",
+            Self::Pushed => "sync-pushed",
+            Self::RemoteOnly => "sync-remote-only",
+            Self::Pruned => "sync-pruned",
+            Self::Diverged => "sync-diverged",
+        };
+        t_with_args(key, &[])
+    }
+}
+
+/// The result of a sync, as printed by `--format json`.
+#[derive(Serialize, Default)]
+struct SyncResult {
+    remote: Option<String>,
+    branches: Vec<SyncedBranch>,
+}
+
+#[derive(Serialize)]
+struct SyncedBranch {
+    branch: String,
+    status: SyncStatus,
+}
+
+/// Reconciles the current user's WIP branches with the remote.
+///
+/// # Arguments
+/// * `options` - Configuration for the sync operation
+///   - `remote`: Remote to sync with instead of `wippy.remote` or `origin`
+///   - `format`: Print a summary or a JSON record per branch
+///
+/// # Details
+/// * Fetches the user's WIP branches from the remote
+/// * Pushes WIP branches that were only saved locally
+/// * Deletes local copies of WIP branches that were deleted on the remote,
+///   unless they changed locally since
+/// * Reports WIP branches that only exist on the remote, and ones whose local
+///   and remote copies differ, without touching them
+pub async fn sync_wip_branches(options: SyncOptions) -> Result<()> {
+    let git = open_git();
+    sync_wip_branches_with_git(&git, options).await
+}
+
+pub async fn sync_wip_branches_with_git(git: &impl Git, options: SyncOptions) -> Result<()> {
+    let output = Output::with_format(options.format).await?;
+    let Some(remote) = wip_remote_with_git(git, options.remote.as_deref()).await? else {
+        anyhow::bail!("No remote to sync with, use --remote or set wippy.remote");
+    };
+    let username = git_username_with_git(git).await?;
+
+    // What the remote had at the last fetch tells WIPs deleted there apart from
+    // ones that were never pushed
+    let mut tracked = BTreeMap::new();
+    for branch in git.get_user_wip_branches(&username).await? {
+        if branch.remotes.contains(&remote) {
+            let commit = git.rev_parse(&remote_ref(&remote, &branch.name)).await?;
+            tracked.insert(branch.name, commit);
+        }
+    }
+
+    let message = t_with_args("sync-fetching", &[("remote", &remote)]);
+    output.info(&output.format_with_highlights(&message, &[&format!("'{}'", remote)]))?;
+    let prefix = BranchTemplate::load(git).await?.user_prefix(&username);
+    git.fetch_branches(&remote, &prefix).await?;
+
+    let mut result = SyncResult {
+        remote: Some(remote.clone()),
+        branches: Vec::new(),
+    };
+    for branch in git.get_user_wip_branches(&username).await? {
+        let status = sync_branch(git, &remote, &branch, tracked.get(&branch.name)).await?;
+        result.branches.push(SyncedBranch {
+            branch: branch.name,
+            status,
+        });
+    }
+
+    if result.branches.is_empty() {
+        let message = t_with_args("no-wip-branches", &[("username", &username)]);
+        output.info(&output.format_with_highlights(&message, &[&username]))?;
+        return output.result(&result);
+    }
+
+    let mut rows = vec![vec![
+        t_with_args("sync-branch-column", &[]),
+        t_with_args("sync-status-column", &[]),
+    ]];
+    rows.extend(
+        result
+            .branches
+            .iter()
+            .map(|synced| vec![synced.branch.clone(), synced.status.label()]),
+    );
+    output.info("")?;
+    for line in output.format_table(&rows) {
+        output.info(&line)?;
+    }
+
+    output.result(&result)
+}
+
+/// Brings a single WIP branch in line with the remote, after fetching.
+///
+/// # Arguments
+/// * `tracked` - The commit the remote had for the branch before fetching, if any
+async fn sync_branch(
+    git: &impl Git,
+    remote: &str,
+    branch: &WipBranch,
+    tracked: Option<&String>,
+) -> Result<SyncStatus> {
+    if !branch.local {
+        return Ok(SyncStatus::RemoteOnly);
+    }
+    let local = git
+        .rev_parse(&format!("refs/heads/{}", branch.name))
+        .await?;
+
+    if branch.remotes.iter().any(|name| name == remote) {
+        let remote_commit = git.rev_parse(&remote_ref(remote, &branch.name)).await?;
+        return Ok(if local == remote_commit {
+            SyncStatus::UpToDate
+        } else {
+            SyncStatus::Diverged
+        });
+    }
+
+    match tracked {
+        None => {
+            git.push(remote, &branch.name).await?;
+            Ok(SyncStatus::Pushed)
+        }
+        Some(commit) if *commit == local => {
+            git.delete_branch(&branch.name, true).await?;
+            Ok(SyncStatus::Pruned)
+        }
+        Some(_) => Ok(SyncStatus::Diverged),
+    }
+}
+
+/// The remote-tracking ref of a branch.
+fn remote_ref(remote: &str, branch: &str) -> String {
+    format!("refs/remotes/{}/{}", remote, branch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::MockGit;
+    use mockall::predicate::eq;
+
+    fn wip_branch(name: &str, local: bool, remotes: &[&str]) -> WipBranch {
+        WipBranch {
+            name: name.to_string(),
+            user: "test-user".to_string(),
+            timestamp: name.rsplit('/').next().unwrap().to_string(),
+            local,
+            remotes: remotes.iter().map(|remote| remote.to_string()).collect(),
+        }
+    }
+
+    fn expect_commit(mock_git: &mut MockGit, reference: &str, commit: &str) {
+        let commit = commit.to_string();
+        mock_git
+            .expect_rev_parse()
+            .with(eq(reference.to_string()))
+            .returning(move |_| Ok(commit.clone()));
+    }
+
+    #[tokio::test]
+    async fn test_sync_branch() -> Result<()> {
+        let mut mock_git = MockGit::new();
+        expect_commit(
+            &mut mock_git,
+            "refs/heads/wip/test-user/2024-03-06-09-00-00",
+            "a",
+        );
+        expect_commit(
+            &mut mock_git,
+            "refs/remotes/origin/wip/test-user/2024-03-06-09-00-00",
+            "a",
+        );
+        expect_commit(
+            &mut mock_git,
+            "refs/heads/wip/test-user/2024-03-04-09-00-00",
+            "a",
+        );
+        expect_commit(
+            &mut mock_git,
+            "refs/remotes/origin/wip/test-user/2024-03-04-09-00-00",
+            "b",
+        );
+        expect_commit(
+            &mut mock_git,
+            "refs/heads/wip/test-user/2024-03-05-09-00-00",
+            "c",
+        );
+        expect_commit(
+            &mut mock_git,
+            "refs/heads/wip/test-user/2024-03-03-09-00-00",
+            "d",
+        );
+        expect_commit(
+            &mut mock_git,
+            "refs/heads/wip/test-user/2024-03-01-09-00-00",
+            "e",
+        );
+        mock_git
+            .expect_push()
+            .with(eq("origin"), eq("wip/test-user/2024-03-05-09-00-00"))
+            .times(1)
+            .returning(|_, _| Ok(String::new()));
+        mock_git
+            .expect_delete_branch()
+            .with(eq("wip/test-user/2024-03-03-09-00-00"), eq(true))
+            .times(1)
+            .returning(|_, _| Ok(String::new()));
+
+        let d = "d".to_string();
+        for (branch, tracked, status) in [
+            (
+                wip_branch("wip/test-user/2024-03-06-09-00-00", true, &["origin"]),
+                None,
+                SyncStatus::UpToDate,
+            ),
+            (
+                wip_branch("wip/test-user/2024-03-04-09-00-00", true, &["origin"]),
+                None,
+                SyncStatus::Diverged,
+            ),
+            (
+                wip_branch("wip/test-user/2024-03-02-09-00-00", false, &["origin"]),
+                None,
+                SyncStatus::RemoteOnly,
+            ),
+            // Never pushed, or only to another remote
+            (
+                wip_branch("wip/test-user/2024-03-05-09-00-00", true, &["fork"]),
+                None,
+                SyncStatus::Pushed,
+            ),
+            // Deleted on the remote since the last fetch
+            (
+                wip_branch("wip/test-user/2024-03-03-09-00-00", true, &[]),
+                Some(&d),
+                SyncStatus::Pruned,
+            ),
+            (
+                wip_branch("wip/test-user/2024-03-01-09-00-00", true, &[]),
+                Some(&d),
+                SyncStatus::Diverged,
+            ),
+        ] {
+            assert_eq!(
+                sync_branch(&mock_git, "origin", &branch, tracked).await?,
+                status,
+                "{}",
+                branch.name
+            );
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sync_fetches_user_branches() -> Result<()> {
+        let mut mock_git = MockGit::new();
+        mock_git
+            .expect_get_remotes()
+            .returning(|| Ok(vec!["origin".to_string()]));
+        mock_git.expect_get_config_value().returning(|_| Ok(None));
+        mock_git
+            .expect_execute()
+            .with(eq(vec!["config".to_string(), "user.name".to_string()]))
+            .returning(|_| Ok("test-user".to_string()));
+        mock_git.expect_get_user_wip_branches().returning(|_| {
+            Ok(vec![wip_branch(
+                "wip/test-user/2024-03-02-09-00-00",
+                false,
+                &["origin"],
+            )])
+        });
+        expect_commit(
+            &mut mock_git,
+            "refs/remotes/origin/wip/test-user/2024-03-02-09-00-00",
+            "a",
+        );
+        mock_git
+            .expect_fetch_branches()
+            .with(eq("origin"), eq("wip/test-user/"))
+            .times(1)
+            .returning(|_, _| Ok(String::new()));
+
+        sync_wip_branches_with_git(
+            &mock_git,
+            SyncOptions {
+                remote: None,
+                format: OutputFormat::Json,
+            },
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_sync_needs_a_remote() {
+        let mut mock_git = MockGit::new();
+        mock_git.expect_get_remotes().returning(|| Ok(Vec::new()));
+        mock_git.expect_get_config_value().returning(|_| Ok(None));
+
+        let error = sync_wip_branches_with_git(
+            &mock_git,
+            SyncOptions {
+                remote: None,
+                format: OutputFormat::Text,
+            },
+        )
+        .await
+        .unwrap_err();
+        assert!(error.to_string().contains("No remote to sync with"));
+    }
+}
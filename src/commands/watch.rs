@@ -0,0 +1,503 @@
+use crate::commands::save::{save_changes, snapshot_trees, SaveOptions};
+use crate::i18n::{t, t_with_args};
+use crate::output::{Output, OutputFormat};
+use crate::utils::{open_git, parse_age, trim_snapshots, Git, Head, Journal};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::future::Future;
+use std::time::Duration;
+use tokio::time::Instant;
+
+pub struct WatchOptions {
+    pub interval: String,
+    pub quiet_period: Option<String>,
+    pub keep_last: usize,
+    pub message: Option<String>,
+    pub background: bool,
+    pub format: OutputFormat,
+}
+
+/// Set for the process `--background` starts, which does the actual watching
+const DETACHED_ENV: &str = "WIPPY_WATCH_DETACHED";
+
+/// Where a background watch writes its output, in the git directory
+const LOG_FILE: &str = "wippy-watch.log";
+
+/// A snapshot taken by `watch`, as printed by `--format json`.
+#[derive(Serialize, Debug, PartialEq)]
+struct WatchSnapshot {
+    branch: String,
+    source_branch: Option<String>,
+    source_commit: Option<String>,
+    /// How many of the oldest snapshots were dropped to stay within `--keep-last`
+    dropped_snapshots: usize,
+}
+
+/// A background watch that was started, as printed by `--format json`.
+#[derive(Serialize)]
+struct WatchProcess {
+    pid: u32,
+    log: String,
+}
+
+/// The index and the working tree, as a snapshot records them.
+#[derive(Debug, Clone, PartialEq)]
+struct Fingerprint {
+    index_tree: String,
+    worktree_tree: String,
+}
+
+/// When `watch` takes snapshots.
+#[derive(Debug, Clone, Copy)]
+struct Schedule {
+    interval: Duration,
+    quiet_period: Option<Duration>,
+}
+
+impl Schedule {
+    fn parse(interval: &str, quiet_period: Option<&str>) -> Result<Self> {
+        let duration = |age: &str| -> Result<Duration> {
+            let duration = parse_age(age)?.to_std().unwrap_or_default();
+            if duration.is_zero() {
+                anyhow::bail!("Invalid age '{}', it must be longer than 0s", age);
+            }
+            Ok(duration)
+        };
+        Ok(Self {
+            interval: duration(interval)?,
+            quiet_period: quiet_period.map(duration).transpose()?,
+        })
+    }
+
+    /// How long to wait between looks at the working tree
+    fn poll(&self) -> Duration {
+        match self.quiet_period {
+            // Look often enough to notice when the files stop changing
+            Some(quiet_period) => (quiet_period / 4).clamp(Duration::from_secs(1), self.interval),
+            None => self.interval,
+        }
+    }
+
+    /// Whether changes that aren't in a snapshot yet should be saved now.
+    ///
+    /// # Arguments
+    /// * `changed_at` - When the working tree last changed
+    /// * `saved_at` - When the last snapshot was taken, or the watch started
+    fn due(&self, now: Instant, changed_at: Instant, saved_at: Instant) -> bool {
+        match self.quiet_period {
+            None => true,
+            // Files that never stop changing are still saved every interval
+            Some(quiet_period) => {
+                now - changed_at >= quiet_period || now - saved_at >= self.interval
+            }
+        }
+    }
+}
+
+/// Takes snapshots of the working tree into a WIP branch of its own.
+struct Watcher {
+    schedule: Schedule,
+    keep_last: usize,
+    message: Option<String>,
+    /// The WIP branch snapshots are added to, and the branch it was saved from
+    branch: Option<(String, String)>,
+    /// What the last snapshot recorded
+    saved: Option<Fingerprint>,
+    saved_at: Instant,
+    /// Changes that aren't in a snapshot yet, and when they were last made
+    pending: Option<(Fingerprint, Instant)>,
+}
+
+impl Watcher {
+    fn new(schedule: Schedule, keep_last: usize, message: Option<String>, now: Instant) -> Self {
+        Self {
+            schedule,
+            keep_last,
+            message,
+            branch: None,
+            saved: None,
+            saved_at: now,
+            pending: None,
+        }
+    }
+
+    /// Looks at the working tree, and takes a snapshot if one is due.
+    ///
+    /// # Returns
+    /// * `Ok(Some(WatchSnapshot))` - The snapshot that was taken
+    /// * `Ok(None)` if nothing changed since the last snapshot, it's too early,
+    ///   or another `save` or `restore` is running
+    async fn tick(&mut self, git: &impl Git, now: Instant) -> Result<Option<WatchSnapshot>> {
+        let head = git.get_head().await?;
+        let fingerprint = fingerprint(git).await?;
+        let head_tree = match &head.commit {
+            Some(commit) => git.rev_parse(&format!("{}^{{tree}}", commit)).await?,
+            None => git.empty_tree().await?,
+        };
+        let clean = fingerprint.index_tree == head_tree && fingerprint.worktree_tree == head_tree;
+        if clean || self.saved.as_ref() == Some(&fingerprint) {
+            self.pending = None;
+            return Ok(None);
+        }
+
+        let changed_at = match &self.pending {
+            Some((pending, changed_at)) if *pending == fingerprint => *changed_at,
+            _ => now,
+        };
+        self.pending = Some((fingerprint.clone(), changed_at));
+        if !self.schedule.due(now, changed_at, self.saved_at) {
+            return Ok(None);
+        }
+        // A `save` or `restore` is under way, the snapshot is taken once it finished
+        if let Some(journal) = Journal::load(git).await? {
+            if journal.is_running().await {
+                return Ok(None);
+            }
+        }
+
+        let snapshot = self.snapshot(git, &head).await?;
+        self.saved = Some(fingerprint);
+        self.saved_at = now;
+        self.pending = None;
+        Ok(Some(snapshot))
+    }
+
+    /// Saves the working tree as the latest snapshot of the watch's WIP branch,
+    /// and drops the oldest ones beyond `keep_last`.
+    async fn snapshot(&mut self, git: &impl Git, head: &Head) -> Result<WatchSnapshot> {
+        let source = head.describe();
+        // Switching branches, or restoring or deleting the WIP branch, starts a new one
+        let update = match &self.branch {
+            Some((branch, saved_from)) if *saved_from == source => git
+                .branch_exists(&format!("refs/heads/{}", branch))
+                .await?
+                .then(|| Some(branch.clone())),
+            _ => None,
+        };
+
+        // The progress messages of every save would drown out the watch's own
+        let quiet = Output::with_format(OutputFormat::Json).await?;
+        let result = save_changes(
+            git,
+            &quiet,
+            SaveOptions {
+                local: true,
+                remote: None,
+                keep: true,
+                username: None,
+                datetime: None,
+                message: self.message.clone(),
+                update,
+                format: OutputFormat::Json,
+            },
+        )
+        .await?;
+        let dropped_snapshots = trim_snapshots(git, &result.branch, self.keep_last).await?;
+
+        self.branch = Some((result.branch.clone(), source));
+        Ok(WatchSnapshot {
+            branch: result.branch,
+            source_branch: result.source_branch,
+            source_commit: result.source_commit,
+            dropped_snapshots,
+        })
+    }
+}
+
+/// Reads the index and working tree the way a snapshot records them, without
+/// locking the repository's own index.
+async fn fingerprint(git: &impl Git) -> Result<Fingerprint> {
+    let (index_tree, worktree_tree) = snapshot_trees(git).await?;
+    Ok(Fingerprint {
+        index_tree,
+        worktree_tree,
+    })
+}
+
+/// Takes snapshots of the working tree while you work, until stopped.
+///
+/// # Arguments
+/// * `options` - Configuration for the watch
+///   - `interval`: How often to look for changes, e.g. `5m`
+///   - `quiet_period`: Take a snapshot once the files stopped changing for this
+///     long instead, and at least every `interval` while they keep changing
+///   - `keep_last`: How many snapshots to keep, older ones are dropped
+///   - `message`: Optional message describing the saved work
+///   - `background`: Start a detached process that does the watching and return
+///   - `format`: Print a message or a JSON record per snapshot
+///
+/// # Details
+/// * Snapshots are saved with `save --keep --local`, the first one to a new WIP
+///   branch and the rest on top of it with `save --update`, so `log` lists them
+///   and `restore` brings any of them back
+/// * `HEAD`, the index and the working tree are never changed
+/// * Nothing is saved while the working tree is clean or unchanged since the
+///   last snapshot
+/// * Checking out another branch starts a new WIP branch
+/// * Ctrl-C or `kill` stop the watch after the snapshot in progress, if any
+pub async fn watch_worktree(options: WatchOptions) -> Result<()> {
+    let git = open_git();
+    if options.background && std::env::var_os(DETACHED_ENV).is_none() {
+        return start_in_background(&git, options.format).await;
+    }
+    let stop = stop_signal()?;
+    watch_worktree_with_git(&git, options, stop).await
+}
+
+/// Runs the watch until `stop` resolves.
+pub async fn watch_worktree_with_git(
+    git: &impl Git,
+    options: WatchOptions,
+    stop: impl Future<Output = ()>,
+) -> Result<()> {
+    let output = Output::with_format(options.format).await?;
+    let schedule = Schedule::parse(&options.interval, options.quiet_period.as_deref())?;
+    if options.keep_last == 0 {
+        anyhow::bail!("--keep-last must keep at least 1 snapshot");
+    }
+
+    let started = match &options.quiet_period {
+        Some(quiet_period) => t_with_args(
+            "watch-started-quiet-period",
+            &[("quiet_period", quiet_period)],
+        ),
+        None => t_with_args("watch-started", &[("interval", &options.interval)]),
+    };
+    output.info(&started)?;
+
+    let mut watcher = Watcher::new(schedule, options.keep_last, options.message, Instant::now());
+    tokio::pin!(stop);
+    loop {
+        match watcher.tick(git, Instant::now()).await {
+            Ok(Some(snapshot)) => {
+                let branch = format!("'{}'", snapshot.branch);
+                let message = t_with_args("watch-snapshot", &[("name", &snapshot.branch)]);
+                output.info(&output.format_with_highlights(&message, &[&branch]))?;
+                if snapshot.dropped_snapshots > 0 {
+                    let keep = watcher.keep_last.to_string();
+                    let message = t_with_args(
+                        "watch-dropped-snapshots",
+                        &[("name", &snapshot.branch), ("keep", &keep)],
+                    );
+                    output.info(&output.format_with_highlights(&message, &[&branch]))?;
+                }
+                output.result(&snapshot)?;
+            }
+            Ok(None) => {}
+            // A failed save was rolled back, and the next one may well work,
+            // e.g. once a `save` running at the same time finished
+            Err(error) => {
+                let error = format!("{:#}", error);
+                output.error(&t_with_args("watch-failed", &[("error", &error)]))?;
+            }
+        }
+
+        tokio::select! {
+            _ = &mut stop => break,
+            _ = tokio::time::sleep(schedule.poll()) => {}
+        }
+    }
+
+    output.info(&t("watch-stopped"))?;
+    Ok(())
+}
+
+/// Starts the same `watch` in a detached process that writes its output to
+/// the log file in the git directory.
+async fn start_in_background(git: &impl Git, format: OutputFormat) -> Result<()> {
+    let output = Output::with_format(format).await?;
+    let log = format!("{}/{}", git.git_dir().await?, LOG_FILE);
+    let log_file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&log)
+        .with_context(|| format!("Failed to open '{}'", log))?;
+
+    let mut command = std::process::Command::new(std::env::current_exe()?);
+    command
+        .args(std::env::args_os().skip(1))
+        .env(DETACHED_ENV, "1")
+        .stdin(std::process::Stdio::null())
+        .stdout(log_file.try_clone()?)
+        .stderr(log_file);
+    // Keep Ctrl-C in the terminal from reaching the background process
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut command, 0);
+    let child = command
+        .spawn()
+        .context("Failed to start the watch in the background")?;
+
+    let pid = child.id().to_string();
+    let message = t_with_args("watch-background", &[("pid", &pid), ("log", &log)]);
+    output.info(&output.format_with_highlights(&message, &[&pid]))?;
+    output.result(&WatchProcess {
+        pid: child.id(),
+        log,
+    })
+}
+
+/// Waits until the process is asked to stop, with Ctrl-C or, on Unix, `kill`.
+///
+/// The handlers are installed right away, so a signal that arrives during a
+/// snapshot stops the watch once it's saved instead of interrupting it.
+fn stop_signal() -> Result<impl Future<Output = ()>> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut interrupt = signal(SignalKind::interrupt())?;
+        let mut terminate = signal(SignalKind::terminate())?;
+        Ok(async move {
+            tokio::select! {
+                _ = interrupt.recv() => {}
+                _ = terminate.recv() => {}
+            }
+        })
+    }
+    #[cfg(not(unix))]
+    Ok(async {
+        let _ = tokio::signal::ctrl_c().await;
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::MockGit;
+    use mockall::predicate::eq;
+
+    fn fingerprint(index_tree: &str, worktree_tree: &str) -> Fingerprint {
+        Fingerprint {
+            index_tree: index_tree.to_string(),
+            worktree_tree: worktree_tree.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_schedule() -> Result<()> {
+        let start = Instant::now();
+        let minutes = |count: u64| start + Duration::from_secs(count * 60);
+
+        let every = Schedule::parse("5m", None)?;
+        assert_eq!(every.poll(), Duration::from_secs(5 * 60));
+        assert!(every.due(minutes(5), minutes(5), start));
+
+        let quiet = Schedule::parse("10m", Some("2m"))?;
+        assert_eq!(quiet.poll(), Duration::from_secs(30));
+        assert!(!quiet.due(minutes(3), minutes(2), start));
+        assert!(quiet.due(minutes(4), minutes(2), start));
+        // Changes that keep coming are saved once the interval is up
+        assert!(quiet.due(minutes(10), minutes(10), start));
+
+        assert_eq!(
+            Schedule::parse("1m", Some("1s"))?.poll(),
+            Duration::from_secs(1)
+        );
+        for (interval, quiet_period) in [("0s", None), ("5m", Some("0m")), ("soon", None)] {
+            assert!(Schedule::parse(interval, quiet_period).is_err());
+        }
+        Ok(())
+    }
+
+    /// Mocks a working tree whose index and worktree trees are `trees`, on top
+    /// of a HEAD whose tree is `head-tree`
+    fn expect_trees(mock_git: &mut MockGit, trees: Fingerprint) {
+        mock_git.expect_get_head().returning(|| {
+            Ok(Head {
+                branch: Some("main".to_string()),
+                commit: Some("head".to_string()),
+            })
+        });
+        mock_git
+            .expect_rev_parse()
+            .with(eq("head^{tree}"))
+            .returning(|_| Ok("head-tree".to_string()));
+        mock_git
+            .expect_git_dir()
+            .returning(|| Ok("/tmp/no-such-git-dir".to_string()));
+        // The index tree is written before the working tree is added
+        let mut added = false;
+        mock_git
+            .expect_execute_with_env()
+            .returning(move |args, _| {
+                Ok(match args[0].as_str() {
+                    "write-tree" if added => {
+                        added = false;
+                        trees.worktree_tree.clone()
+                    }
+                    "write-tree" => trees.index_tree.clone(),
+                    _ => {
+                        added = true;
+                        String::new()
+                    }
+                })
+            });
+    }
+
+    #[tokio::test]
+    async fn test_tick_skips_clean_working_tree() -> Result<()> {
+        let mut mock_git = MockGit::new();
+        expect_trees(&mut mock_git, fingerprint("head-tree", "head-tree"));
+        mock_git.expect_get_user_wip_branches().never();
+
+        let start = Instant::now();
+        let schedule = Schedule::parse("1m", None)?;
+        let mut watcher = Watcher::new(schedule, 5, None, start);
+        assert_eq!(watcher.tick(&mock_git, start).await?, None);
+        assert_eq!(watcher.pending, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tick_skips_unchanged_working_tree() -> Result<()> {
+        let mut mock_git = MockGit::new();
+        expect_trees(&mut mock_git, fingerprint("head-tree", "changed-tree"));
+        mock_git.expect_get_user_wip_branches().never();
+
+        let start = Instant::now();
+        let schedule = Schedule::parse("1m", None)?;
+        let mut watcher = Watcher::new(schedule, 5, None, start);
+        watcher.saved = Some(fingerprint("head-tree", "changed-tree"));
+        assert_eq!(watcher.tick(&mock_git, start).await?, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tick_waits_for_quiet_period() -> Result<()> {
+        let mut mock_git = MockGit::new();
+        expect_trees(&mut mock_git, fingerprint("head-tree", "changed-tree"));
+        mock_git.expect_get_user_wip_branches().never();
+
+        let start = Instant::now();
+        let schedule = Schedule::parse("10m", Some("1m"))?;
+        let mut watcher = Watcher::new(schedule, 5, None, start);
+        assert_eq!(watcher.tick(&mock_git, start).await?, None);
+        let later = start + Duration::from_secs(30);
+        assert_eq!(watcher.tick(&mock_git, later).await?, None);
+        // The changes are still pending since they were first seen
+        assert_eq!(
+            watcher.pending,
+            Some((fingerprint("head-tree", "changed-tree"), start))
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_watch_needs_a_snapshot_to_keep() {
+        let mock_git = MockGit::new();
+        let error = watch_worktree_with_git(
+            &mock_git,
+            WatchOptions {
+                interval: "5m".to_string(),
+                quiet_period: None,
+                keep_last: 0,
+                message: None,
+                background: false,
+                format: OutputFormat::Text,
+            },
+            async {},
+        )
+        .await
+        .unwrap_err();
+        assert!(error.to_string().contains("--keep-last"), "{}", error);
+    }
+}
@@ -6,39 +6,158 @@ mod utils;
 
 use crate::cli::{Cli, Commands};
 use crate::commands::{
-    delete::delete_wip_branches, delete::DeleteOptions, list::list_wip_branches,
-    restore::restore_wip_changes, restore::RestoreOptions, save::save_wip_changes,
+    delete::delete_wip_branches, delete::DeleteOptions, diff::diff_wip_branch, diff::DiffOptions,
+    list::list_wip_branches, list::ListOptions, log::log_wip_branch, log::LogOptions,
+    prune::prune_wip_branches, prune::PruneOptions, recover::recover, recover::RecoverOptions,
+    restore::restore_wip_changes, restore::RestoreConflicts, restore::RestoreOptions,
+    save::save_wip_changes, save::SaveOptions, show::show_wip_branch, show::ShowOptions,
+    sync::sync_wip_branches, sync::SyncOptions, watch::watch_worktree, watch::WatchOptions,
 };
+use crate::output::OutputFormat;
 use anyhow::Result;
+use serde::Serialize;
+
+/// An error as printed by `--format json`.
+#[derive(Serialize)]
+struct ErrorReport {
+    message: String,
+    causes: Vec<String>,
+}
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::new();
+    let format = cli.format;
+
+    match run(cli).await {
+        // The result listing the conflicts was printed already
+        Err(error) if format == OutputFormat::Json && error.is::<RestoreConflicts>() => {
+            std::process::exit(1);
+        }
+        Err(error) if format == OutputFormat::Json => {
+            // Report errors as JSON on stdout so scripts only have one stream to parse
+            let report = serde_json::json!({
+                "error": ErrorReport {
+                    message: error.to_string(),
+                    causes: error.chain().skip(1).map(|e| e.to_string()).collect(),
+                }
+            });
+            println!("{}", serde_json::to_string_pretty(&report)?);
+            std::process::exit(1);
+        }
+        result => result,
+    }
+}
+
+async fn run(cli: Cli) -> Result<()> {
+    let format = cli.format;
 
     match cli.command {
         Commands::Save(options) => {
-            save_wip_changes(options.local, options.username, options.datetime).await?;
+            save_wip_changes(SaveOptions {
+                local: options.local,
+                remote: options.remote,
+                keep: options.keep,
+                username: options.username,
+                datetime: options.datetime,
+                message: options.message,
+                update: options.update,
+                format,
+            })
+            .await?;
         }
-        Commands::List => {
-            list_wip_branches().await?;
+        Commands::List(options) => {
+            list_wip_branches(ListOptions {
+                all: options.all,
+                remote: options.remote,
+                format,
+            })
+            .await?;
         }
         Commands::Delete(options) => {
             delete_wip_branches(DeleteOptions {
-                branch_name: options.branch,
+                branch_names: options.branch,
                 all: options.all,
+                all_users: false,
                 force: options.force,
                 local_only: options.local,
+                remote: options.remote,
+                format,
             })
             .await?;
         }
         Commands::Restore(options) => {
             restore_wip_changes(RestoreOptions {
                 branch_name: options.branch,
+                onto: options.onto,
                 force: options.force,
                 autostash: options.autostash,
+                keep: options.keep,
+                keep_remote: options.keep_remote,
+                remote: options.remote,
+                format,
+            })
+            .await?;
+        }
+        Commands::Show(options) => {
+            show_wip_branch(ShowOptions {
+                branch_name: options.branch,
+                patch: options.patch,
+                format,
+            })
+            .await?;
+        }
+        Commands::Log(options) => {
+            log_wip_branch(LogOptions {
+                branch_name: options.branch,
+                format,
+            })
+            .await?;
+        }
+        Commands::Diff(options) => {
+            diff_wip_branch(DiffOptions {
+                branch_name: options.branch,
+                other_branch: options.other,
+                stat: options.stat,
+                format,
+            })
+            .await?;
+        }
+        Commands::Sync(options) => {
+            sync_wip_branches(SyncOptions {
+                remote: options.remote,
+                format,
             })
             .await?;
         }
+        Commands::Prune(options) => {
+            prune_wip_branches(PruneOptions {
+                older_than: options.older_than,
+                source_merged: options.source_merged,
+                keep_last: options.keep_last,
+                all_users: options.all_users,
+                dry_run: options.dry_run,
+                force: options.force,
+                local_only: options.local,
+                remote: options.remote,
+                format,
+            })
+            .await?;
+        }
+        Commands::Watch(options) => {
+            watch_worktree(WatchOptions {
+                interval: options.interval,
+                quiet_period: options.quiet_period,
+                keep_last: options.keep_last,
+                message: options.message,
+                background: options.background,
+                format,
+            })
+            .await?;
+        }
+        Commands::Recover => {
+            recover(RecoverOptions { format }).await?;
+        }
     }
 
     Ok(())
@@ -1,5 +1,16 @@
 use crate::utils::{Color, ColorConfig};
 use anyhow::Result;
+use serde::Serialize;
+
+/// The format used for command output.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Localized, human readable text
+    #[default]
+    Text,
+    /// Machine-readable JSON written to stdout
+    Json,
+}
 
 /// A formatter for terminal output with color support.
 ///
@@ -7,6 +18,7 @@ use anyhow::Result;
 /// - Consistent color formatting based on Git's color settings
 /// - Support for highlighting specific parts of messages
 /// - Standard output types (info, warning, error)
+/// - Machine-readable results when the JSON format is selected
 ///
 /// # Examples
 ///
@@ -23,16 +35,51 @@ use anyhow::Result;
 /// ```
 pub(crate) struct Output {
     color: ColorConfig,
+    format: OutputFormat,
 }
 
 impl Output {
     /// Creates a new Output instance with color settings determined from Git config.
     pub async fn new() -> Result<Self> {
+        Self::with_format(OutputFormat::Text).await
+    }
+
+    /// Creates a new Output instance for the given output format.
+    ///
+    /// In JSON mode informational messages are suppressed so that stdout only
+    /// contains the document written by [`Output::result`].
+    pub async fn with_format(format: OutputFormat) -> Result<Self> {
         Ok(Self {
             color: ColorConfig::new().await,
+            format,
         })
     }
 
+    /// Returns true when the output should be machine-readable JSON.
+    pub fn is_json(&self) -> bool {
+        self.format == OutputFormat::Json
+    }
+
+    /// Fails in JSON mode instead of prompting, as a script reading the output
+    /// can't answer and would wait forever.
+    ///
+    /// # Arguments
+    /// * `hint` - How to run the command without the prompt
+    pub fn ensure_can_prompt(&self, hint: &str) -> Result<()> {
+        if self.is_json() {
+            anyhow::bail!("Can't prompt for an answer with --format json, {}", hint);
+        }
+        Ok(())
+    }
+
+    /// Prints the result of a command as JSON. In text mode this is a no-op.
+    pub fn result<T: Serialize>(&self, result: &T) -> Result<()> {
+        if self.is_json() {
+            println!("{}", serde_json::to_string_pretty(result)?);
+        }
+        Ok(())
+    }
+
     /// Normalize text by removing bidirectional control characters
     fn normalize_text(&self, text: &str) -> String {
         text.replace('\u{2068}', "").replace('\u{2069}', "")
@@ -40,16 +87,15 @@ impl Output {
 
     /// Prints an informational message in green.
     pub fn info(&self, message: &str) -> Result<()> {
-        if !message.is_empty() {
+        if !message.is_empty() && !self.is_json() {
             print!("{}\n", self.normalize_text(message));
         }
         Ok(())
     }
 
     /// Prints a warning message in yellow.
-    #[allow(dead_code)]
     pub fn warning(&self, message: &str) -> Result<()> {
-        if !message.is_empty() {
+        if !message.is_empty() && !self.is_json() {
             print!(
                 "{}\n",
                 self.color
@@ -122,6 +168,46 @@ impl Output {
         self.normalize_text(&result)
     }
 
+    /// Formats rows as a table with aligned columns.
+    ///
+    /// The first row is the header. The first column of the remaining rows is
+    /// highlighted, and the last column is left unpadded.
+    pub fn format_table(&self, rows: &[Vec<String>]) -> Vec<String> {
+        let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+        let widths: Vec<usize> = (0..columns)
+            .map(|column| {
+                rows.iter()
+                    .filter_map(|row| row.get(column))
+                    .map(|cell| cell.chars().count())
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+
+        rows.iter()
+            .enumerate()
+            .map(|(index, row)| {
+                let cells: Vec<String> = row
+                    .iter()
+                    .enumerate()
+                    .map(|(column, cell)| {
+                        let padding = if column + 1 < row.len() {
+                            " ".repeat(widths[column] - cell.chars().count())
+                        } else {
+                            String::new()
+                        };
+                        if column == 0 && index > 0 {
+                            format!("{}{}", self.highlight(cell), padding)
+                        } else {
+                            format!("{}{}", self.normalize_text(cell), padding)
+                        }
+                    })
+                    .collect();
+                cells.join("  ").trim_end().to_string()
+            })
+            .collect()
+    }
+
     /// Prints a warning message in yellow.
     #[allow(dead_code)]
     pub fn warn(&self, message: &str) -> Result<()> {
@@ -0,0 +1,366 @@
+use crate::utils::{parse_formatted_datetime, Git};
+use anyhow::Result;
+
+/// Git config key that sets the template WIP branches are named with
+pub const TEMPLATE_CONFIG_KEY: &str = "wippy.branchTemplate";
+
+/// The template used when none is configured
+pub const DEFAULT_TEMPLATE: &str = "wip/{user}/{date}";
+
+/// A value that can be filled into a branch name.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Placeholder {
+    /// The user saving the work
+    User,
+    /// The timestamp of the save
+    Date,
+    /// The branch the work was saved on, or the commit on a detached HEAD
+    Source,
+    /// The message, reduced to lowercase words joined by dashes
+    Slug,
+    /// The name of the machine the work was saved on
+    Host,
+}
+
+impl Placeholder {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "user" => Some(Self::User),
+            "date" => Some(Self::Date),
+            "source" => Some(Self::Source),
+            "slug" => Some(Self::Slug),
+            "host" => Some(Self::Host),
+            _ => None,
+        }
+    }
+
+    /// Only the source can span several path components, e.g. `feature/login`
+    fn allows_slash(self) -> bool {
+        self == Self::Source
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Literal(String),
+    Placeholder(Placeholder),
+}
+
+/// The values filled into a branch name, or read back from one.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BranchFields {
+    pub user: String,
+    pub date: String,
+    pub source: String,
+    pub slug: String,
+    pub host: String,
+}
+
+impl BranchFields {
+    fn get(&self, placeholder: Placeholder) -> &str {
+        match placeholder {
+            Placeholder::User => &self.user,
+            Placeholder::Date => &self.date,
+            Placeholder::Source => &self.source,
+            Placeholder::Slug => &self.slug,
+            Placeholder::Host => &self.host,
+        }
+    }
+
+    fn set(&mut self, placeholder: Placeholder, value: &str) {
+        let field = match placeholder {
+            Placeholder::User => &mut self.user,
+            Placeholder::Date => &mut self.date,
+            Placeholder::Source => &mut self.source,
+            Placeholder::Slug => &mut self.slug,
+            Placeholder::Host => &mut self.host,
+        };
+        *field = value.to_string();
+    }
+}
+
+/// How WIP branches are named, e.g. `wip/{user}/{date}`.
+///
+/// Templates must contain `{user}` and `{date}` so branches can be told apart
+/// per user and by age, and start with a fixed prefix so other branches aren't
+/// taken for WIP branches. `{source}`, `{slug}` and `{host}` are optional.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BranchTemplate {
+    segments: Vec<Segment>,
+}
+
+impl Default for BranchTemplate {
+    fn default() -> Self {
+        Self::parse(DEFAULT_TEMPLATE).expect("the default template is valid")
+    }
+}
+
+impl BranchTemplate {
+    /// Reads the template from `wippy.branchTemplate`, falling back to the default.
+    pub async fn load(git: &(impl Git + ?Sized)) -> Result<Self> {
+        match git.get_config_value(TEMPLATE_CONFIG_KEY).await? {
+            Some(template) if !template.is_empty() => Self::parse(&template)
+                .map_err(|e| anyhow::anyhow!("Invalid {}: {}", TEMPLATE_CONFIG_KEY, e)),
+            _ => Ok(Self::default()),
+        }
+    }
+
+    /// Parses a template like `wip/{user}/{date}`.
+    pub fn parse(template: &str) -> Result<Self> {
+        let mut segments = Vec::new();
+        let mut rest = template;
+
+        while !rest.is_empty() {
+            let Some(start) = rest.find('{') else {
+                segments.push(Segment::Literal(rest.to_string()));
+                break;
+            };
+            if start > 0 {
+                segments.push(Segment::Literal(rest[..start].to_string()));
+            }
+            let end = rest[start..]
+                .find('}')
+                .map(|end| start + end)
+                .ok_or_else(|| anyhow::anyhow!("unclosed placeholder in '{}'", template))?;
+            let name = &rest[start + 1..end];
+            let placeholder = Placeholder::from_name(name)
+                .ok_or_else(|| anyhow::anyhow!("unknown placeholder '{{{}}}'", name))?;
+            if let Some(Segment::Placeholder(_)) = segments.last() {
+                anyhow::bail!("placeholders must be separated, e.g. '{{user}}/{{date}}'");
+            }
+            segments.push(Segment::Placeholder(placeholder));
+            rest = &rest[end + 1..];
+        }
+
+        for required in [Placeholder::User, Placeholder::Date] {
+            if !segments.contains(&Segment::Placeholder(required)) {
+                anyhow::bail!("'{}' must contain {{user}} and {{date}}", template);
+            }
+        }
+        // Without a fixed start, ordinary branches like `feature/login` could match
+        if !matches!(segments.first(), Some(Segment::Literal(_))) {
+            anyhow::bail!("'{}' must start with a fixed prefix, e.g. 'wip/'", template);
+        }
+
+        Ok(Self { segments })
+    }
+
+    /// Builds a branch name from the template.
+    pub fn render(&self, fields: &BranchFields) -> String {
+        self.segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Literal(text) => text.as_str(),
+                Segment::Placeholder(placeholder) => fields.get(*placeholder),
+            })
+            .collect()
+    }
+
+    /// The start shared by the names of all of a user's WIP branches, e.g.
+    /// `wip/jane/`, up to the first placeholder other than `{user}`.
+    pub fn user_prefix(&self, user: &str) -> String {
+        let mut prefix = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(text) => prefix.push_str(text),
+                Segment::Placeholder(Placeholder::User) => prefix.push_str(user),
+                Segment::Placeholder(_) => break,
+            }
+        }
+        prefix
+    }
+
+    /// Reads the fields back from a branch name.
+    ///
+    /// Returns `None` for names that weren't built from this template.
+    pub fn match_name(&self, name: &str) -> Option<BranchFields> {
+        let mut fields = BranchFields::default();
+        match_segments(&self.segments, name, true, &mut fields).then_some(fields)
+    }
+
+    /// Reads the fields back from a name older versions could have given a
+    /// branch with a free-form `--datetime`, e.g. `wip/jane/before-release`.
+    ///
+    /// Those versions always used the default template, so other templates
+    /// never match. Such a name alone doesn't make a WIP branch, its tip has
+    /// to be a WIP commit too.
+    pub fn match_legacy_name(&self, name: &str) -> Option<BranchFields> {
+        if *self != Self::default() {
+            return None;
+        }
+        let mut fields = BranchFields::default();
+        match_segments(&self.segments, name, false, &mut fields).then_some(fields)
+    }
+}
+
+/// Matches a name against template segments, backtracking over where each
+/// placeholder ends. With `dated`, the date has to be a timestamp `save` could
+/// have written.
+fn match_segments(
+    segments: &[Segment],
+    name: &str,
+    dated: bool,
+    fields: &mut BranchFields,
+) -> bool {
+    let Some((segment, rest)) = segments.split_first() else {
+        return name.is_empty();
+    };
+
+    match segment {
+        Segment::Literal(text) => name
+            .strip_prefix(text.as_str())
+            .is_some_and(|name| match_segments(rest, name, dated, fields)),
+        Segment::Placeholder(placeholder) => {
+            // Try every end of the value, shortest first. Values can't contain a
+            // slash unless the placeholder allows it.
+            let max_end = match name.find('/') {
+                Some(slash) if !placeholder.allows_slash() => slash,
+                _ => name.len(),
+            };
+            let ends = (1..=max_end).filter(|end| {
+                name.is_char_boundary(*end)
+                    && (!dated
+                        || *placeholder != Placeholder::Date
+                        || parse_formatted_datetime(&name[..*end]).is_some())
+            });
+            for end in ends {
+                fields.set(*placeholder, &name[..end]);
+                if match_segments(rest, &name[end..], dated, fields) {
+                    return true;
+                }
+            }
+            false
+        }
+    }
+}
+
+/// Reduces a message to lowercase words joined by dashes, e.g. `login-form`.
+pub fn slugify(message: &str) -> String {
+    let slug = message
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_ascii_lowercase)
+        .collect::<Vec<_>>()
+        .join("-");
+
+    // Keep branch names readable
+    match slug.char_indices().nth(40) {
+        Some((end, _)) => slug[..end].trim_end_matches('-').to_string(),
+        None => slug,
+    }
+}
+
+/// The name of this machine, for the `{host}` placeholder.
+pub fn hostname() -> String {
+    std::env::var("HOSTNAME")
+        .or_else(|_| std::env::var("COMPUTERNAME"))
+        .ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|host| host.trim().to_string())
+        .filter(|host| !host.is_empty())
+        .unwrap_or_else(|| "localhost".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields() -> BranchFields {
+        BranchFields {
+            user: "jane".to_string(),
+            date: "2024-03-21-17-59-30".to_string(),
+            source: "feature/login".to_string(),
+            slug: "login-form".to_string(),
+            host: "laptop".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_default_template() {
+        let template = BranchTemplate::default();
+        let name = template.render(&fields());
+        assert_eq!(name, "wip/jane/2024-03-21-17-59-30");
+
+        let parsed = template.match_name(&name).unwrap();
+        assert_eq!(parsed.user, "jane");
+        assert_eq!(parsed.date, "2024-03-21-17-59-30");
+
+        assert_eq!(template.match_name("feature/login"), None);
+        assert_eq!(template.match_name("wip/jane"), None);
+        assert_eq!(template.match_name("wip//2024"), None);
+        assert_eq!(template.match_name("wip/jane/2024/extra"), None);
+        // The date has to be a timestamp
+        assert_eq!(template.match_name("wip/jane/login"), None);
+        assert_eq!(template.match_name("wip/jane/2024-13-21-17-59-30"), None);
+    }
+
+    #[test]
+    fn test_legacy_name() {
+        let template = BranchTemplate::default();
+        let parsed = template
+            .match_legacy_name("wip/jane/before-release")
+            .unwrap();
+        assert_eq!(parsed.user, "jane");
+        assert_eq!(parsed.date, "before-release");
+        assert_eq!(
+            template
+                .match_legacy_name("wip/jane/2024-03-21-175930")
+                .unwrap()
+                .date,
+            "2024-03-21-175930"
+        );
+
+        assert_eq!(template.match_legacy_name("wip/jane"), None);
+        assert_eq!(template.match_legacy_name("wip/jane/"), None);
+        assert_eq!(template.match_legacy_name("wip/jane/login/extra"), None);
+        // Only the default template was used before templates existed
+        let template = BranchTemplate::parse("mine/{user}/{date}").unwrap();
+        assert_eq!(template.match_legacy_name("mine/jane/before-release"), None);
+    }
+
+    #[test]
+    fn test_custom_template_round_trip() {
+        let template = BranchTemplate::parse("wip-{host}/{source}/{user}/{date}-{slug}").unwrap();
+        let name = template.render(&fields());
+        assert_eq!(
+            name,
+            "wip-laptop/feature/login/jane/2024-03-21-17-59-30-login-form"
+        );
+        assert_eq!(template.match_name(&name), Some(fields()));
+    }
+
+    #[test]
+    fn test_user_prefix() {
+        assert_eq!(BranchTemplate::default().user_prefix("jane"), "wip/jane/");
+        let template = BranchTemplate::parse("wip/{user}-{date}/{source}").unwrap();
+        assert_eq!(template.user_prefix("jane"), "wip/jane-");
+        let template = BranchTemplate::parse("wip/{host}/{user}/{date}").unwrap();
+        assert_eq!(template.user_prefix("jane"), "wip/");
+    }
+
+    #[test]
+    fn test_invalid_templates() {
+        for template in [
+            "wip/{user}",
+            "wip/{date}",
+            "wip/{user}/{date}/{branch}",
+            "wip/{user}/{date",
+            "wip/{user}{date}",
+            // Would match ordinary branches like `feature/login`
+            "{user}/{date}",
+            "{host}/{user}/{date}",
+        ] {
+            assert!(BranchTemplate::parse(template).is_err(), "{}", template);
+        }
+    }
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(slugify("Fix the Login form!"), "fix-the-login-form");
+        assert_eq!(slugify("  --  "), "");
+        assert_eq!(
+            slugify("a very long message that goes on and on and on forever"),
+            "a-very-long-message-that-goes-on-and-on"
+        );
+    }
+}
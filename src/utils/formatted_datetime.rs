@@ -1,4 +1,8 @@
-use chrono::Local;
+use anyhow::Result;
+use chrono::{Local, NaiveDateTime, TimeDelta};
+
+/// The format used for timestamps in WIP branch names.
+const BRANCH_DATETIME_FORMAT: &str = "%Y-%m-%d-%H-%M-%S";
 
 /// Returns the current datetime formatted for use in branch names.
 ///
@@ -27,7 +31,65 @@ use chrono::Local;
 /// This function is typically used to create unique WIP branch names by combining
 /// with the username: `wip/{username}/{formatted_datetime}`
 pub fn formatted_datetime() -> String {
-    Local::now().format("%Y-%m-%d-%H-%M-%S").to_string()
+    Local::now().format(BRANCH_DATETIME_FORMAT).to_string()
+}
+
+/// Parses a datetime produced by [`formatted_datetime`].
+///
+/// Returns `None` if the text is not in the `YYYY-MM-DD-HH-mm-SS` format,
+/// e.g. for branches saved with a free-form `--datetime` by older versions.
+pub fn parse_formatted_datetime(datetime: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(datetime, BRANCH_DATETIME_FORMAT).ok()
+}
+
+/// Checks that a datetime given with `--datetime` has the `YYYY-MM-DD-HH-mm-SS` format.
+pub fn check_formatted_datetime(datetime: &str) -> Result<()> {
+    if parse_formatted_datetime(datetime).is_none() {
+        anyhow::bail!(
+            "Invalid --datetime '{}', expected YYYY-MM-DD-HH-mm-SS like {}",
+            datetime,
+            formatted_datetime()
+        );
+    }
+    Ok(())
+}
+
+/// Formats an age as a compact relative duration, e.g. `45s`, `12m`, `3h` or `2d`.
+///
+/// Negative ages (timestamps in the future) are shown as `0s`.
+pub fn format_age(age: TimeDelta) -> String {
+    let seconds = age.num_seconds().max(0);
+    match seconds {
+        s if s < 60 => format!("{}s", s),
+        s if s < 60 * 60 => format!("{}m", s / 60),
+        s if s < 24 * 60 * 60 => format!("{}h", s / (60 * 60)),
+        s => format!("{}d", s / (24 * 60 * 60)),
+    }
+}
+
+/// Parses an age given on the command line, e.g. `45s`, `12m`, `3h`, `30d` or `2w`.
+///
+/// Uses the units of [`format_age`], plus weeks.
+pub fn parse_age(age: &str) -> Result<TimeDelta> {
+    let invalid = || {
+        anyhow::anyhow!(
+            "Invalid age '{}', expected a number followed by s, m, h, d or w, e.g. 30d",
+            age
+        )
+    };
+    let unit_start = age
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(invalid)?;
+    let count: i64 = age[..unit_start].parse().map_err(|_| invalid())?;
+    let age = match &age[unit_start..] {
+        "s" => TimeDelta::try_seconds(count),
+        "m" => TimeDelta::try_minutes(count),
+        "h" => TimeDelta::try_hours(count),
+        "d" => TimeDelta::try_days(count),
+        "w" => TimeDelta::try_weeks(count),
+        _ => None,
+    };
+    age.ok_or_else(invalid)
 }
 
 #[cfg(test)]
@@ -42,4 +104,48 @@ mod tests {
         let re = Regex::new(r"^\d{4}-\d{2}-\d{2}-\d{2}-\d{2}-\d{2}$").unwrap();
         assert!(re.is_match(&datetime));
     }
+
+    /// Tests that formatted datetimes can be parsed back
+    #[test]
+    fn test_parse_formatted_datetime() {
+        let parsed = parse_formatted_datetime("2024-03-21-17-59-30").unwrap();
+        assert_eq!(parsed.to_string(), "2024-03-21 17:59:30");
+
+        assert!(parse_formatted_datetime(&formatted_datetime()).is_some());
+        assert!(parse_formatted_datetime("yesterday").is_none());
+    }
+
+    /// Tests that only datetimes in the branch format are accepted
+    #[test]
+    fn test_check_formatted_datetime() {
+        assert!(check_formatted_datetime("2024-03-21-17-59-30").is_ok());
+        for datetime in ["yesterday", "2024-03-21 17:59:30", "2024-13-01-00-00-00"] {
+            let error = check_formatted_datetime(datetime).unwrap_err();
+            assert!(error.to_string().contains(&format!("'{}'", datetime)));
+        }
+    }
+
+    /// Tests the compact age formatting
+    #[test]
+    fn test_format_age() {
+        assert_eq!(format_age(TimeDelta::seconds(-5)), "0s");
+        assert_eq!(format_age(TimeDelta::seconds(45)), "45s");
+        assert_eq!(format_age(TimeDelta::minutes(12)), "12m");
+        assert_eq!(format_age(TimeDelta::hours(3)), "3h");
+        assert_eq!(format_age(TimeDelta::days(2) + TimeDelta::hours(5)), "2d");
+    }
+
+    /// Tests that ages from the command line are parsed like format_age prints them
+    #[test]
+    fn test_parse_age() {
+        assert_eq!(parse_age("45s").unwrap(), TimeDelta::seconds(45));
+        assert_eq!(parse_age("12m").unwrap(), TimeDelta::minutes(12));
+        assert_eq!(parse_age("3h").unwrap(), TimeDelta::hours(3));
+        assert_eq!(parse_age("30d").unwrap(), TimeDelta::days(30));
+        assert_eq!(parse_age("2w").unwrap(), TimeDelta::weeks(2));
+        for age in ["", "30", "d", "-1d", "1.5d", "30 days", "99999999999999w"] {
+            let error = parse_age(age).unwrap_err();
+            assert!(error.to_string().contains(&format!("'{}'", age)), "{}", age);
+        }
+    }
 }
@@ -0,0 +1,37 @@
+use anyhow::Result;
+use tokio::fs;
+
+/// Reads a file, returning `None` if it doesn't exist
+pub async fn read_file(file: &str) -> Result<Option<String>> {
+    match fs::read_to_string(file).await {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Writes content to a file
+pub async fn write_file(file: &str, content: &str) -> Result<()> {
+    fs::write(file, content).await.map_err(|e| e.into())
+}
+
+/// Removes a file, succeeding if it is already gone
+pub async fn remove_file(file: &str) -> Result<()> {
+    match fs::remove_file(file).await {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Copies a file along with its modification time, doing nothing if it doesn't exist
+pub async fn copy_file(from: &str, to: &str) -> Result<()> {
+    let modified = match fs::metadata(from).await {
+        Ok(metadata) => metadata.modified()?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+    fs::copy(from, to).await?;
+    let file = fs::OpenOptions::new().write(true).open(to).await?;
+    file.into_std().await.set_modified(modified)?;
+    Ok(())
+}
@@ -1,7 +1,10 @@
 use crate::output::Output;
+use crate::utils::{parse_wip_refs, BranchTemplate, WipBranch};
 use anyhow::{Context, Result};
 use async_trait::async_trait;
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
 use tokio::process::Command;
 
 /// A trait that abstracts Git operations used throughout the application.
@@ -57,6 +60,43 @@ pub trait Git: Send + Sync {
     /// - The output cannot be parsed as UTF-8
     async fn execute(&self, args: Vec<String>) -> Result<String>;
 
+    /// Executes a Git command with extra environment variables set.
+    ///
+    /// Used for plumbing that has to run against something other than the
+    /// repository's own index, e.g. with `GIT_INDEX_FILE` pointing at a
+    /// temporary index.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - Vector of command arguments to pass to git
+    /// * `env` - Environment variables to set for the command
+    async fn execute_with_env(
+        &self,
+        args: Vec<String>,
+        env: Vec<(String, String)>,
+    ) -> Result<String>;
+
+    /// Executes a Git command with `input` written to its standard input.
+    ///
+    /// Used to pass any number of paths in one invocation, e.g. with
+    /// `--pathspec-from-file=-`, instead of one process per path.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - Vector of command arguments to pass to git
+    /// * `input` - What the command reads from standard input
+    async fn execute_with_stdin(&self, args: Vec<String>, input: Vec<u8>) -> Result<String>;
+
+    /// Executes a Git command attached to the terminal.
+    ///
+    /// The output isn't captured, so git pages and colors it the way the user
+    /// configured, e.g. for `diff`.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - Vector of command arguments to pass to git
+    async fn execute_in_terminal(&self, args: Vec<String>) -> Result<()>;
+
     /// Gets all Git configuration as key-value pairs.
     ///
     /// # Returns
@@ -102,7 +142,7 @@ pub trait Git: Send + Sync {
         {
             Ok(value) => Ok(Some(value)),
             Err(e) => {
-                if e.to_string().contains("exit code: 1") {
+                if e.to_string().contains("exit code: 1)") {
                     // Config key not found (git returns exit code 1)
                     Ok(None)
                 } else {
@@ -113,6 +153,9 @@ pub trait Git: Send + Sync {
     }
 
     /// Gets the name of the current git branch
+    ///
+    /// Returns `HEAD` on a detached checkout, use `get_head` where that matters.
+    #[allow(dead_code)]
     async fn get_current_branch(&self) -> Result<String> {
         self.execute(vec![
             "rev-parse".to_string(),
@@ -122,7 +165,35 @@ pub trait Git: Send + Sync {
         .await
     }
 
+    /// Gets the branch and commit HEAD points to
+    ///
+    /// Works on detached checkouts, which have no branch, and on unborn branches,
+    /// which have no commit yet.
+    async fn get_head(&self) -> Result<Head> {
+        // Both commands exit with an error when there is nothing to report
+        let branch = self
+            .execute(vec![
+                "symbolic-ref".to_string(),
+                "--quiet".to_string(),
+                "--short".to_string(),
+                "HEAD".to_string(),
+            ])
+            .await
+            .ok();
+        let commit = self
+            .execute(vec![
+                "rev-parse".to_string(),
+                "--verify".to_string(),
+                "--quiet".to_string(),
+                "HEAD^{commit}".to_string(),
+            ])
+            .await
+            .ok();
+        Ok(Head { branch, commit })
+    }
+
     /// Stages all changes in the working directory
+    #[allow(dead_code)]
     async fn stage_all(&self) -> Result<String> {
         self.execute(vec!["add".to_string(), "-A".to_string()])
             .await
@@ -132,6 +203,7 @@ pub trait Git: Send + Sync {
     ///
     /// # Arguments
     /// * `message` - The commit message to use
+    #[allow(dead_code)]
     async fn commit(&self, message: &str) -> Result<String> {
         self.execute(vec![
             "commit".to_string(),
@@ -163,76 +235,382 @@ pub trait Git: Send + Sync {
         .await
     }
 
-    /// Pushes a branch to a remote repository
+    /// Creates and checks out a new branch starting at the given commit
     ///
     /// # Arguments
-    /// * `remote` - Name of the remote (e.g., "origin")
-    /// * `branch` - Name of the branch to push
-    async fn push(&self, remote: &str, branch: &str) -> Result<String> {
+    /// * `branch` - Name of the new branch to create
+    /// * `start_point` - The commit the branch starts at
+    async fn create_branch_at(&self, branch: &str, start_point: &str) -> Result<String> {
         self.execute(vec![
-            "push".to_string(),
-            remote.to_string(),
+            "checkout".to_string(),
+            "-b".to_string(),
             branch.to_string(),
+            start_point.to_string(),
         ])
         .await
     }
 
-    /// Gets a list of staged files
-    async fn get_staged_files(&self) -> Result<String> {
+    /// Checks out a commit without a branch
+    ///
+    /// # Arguments
+    /// * `commit` - The commit to check out
+    async fn checkout_detached(&self, commit: &str) -> Result<String> {
+        self.execute(vec![
+            "checkout".to_string(),
+            "--detach".to_string(),
+            commit.to_string(),
+        ])
+        .await
+    }
+
+    /// Gets the parents of a commit, empty for a root commit
+    async fn get_parents(&self, commit: &str) -> Result<Vec<String>> {
+        self.execute(vec!["rev-parse".to_string(), format!("{}^@", commit)])
+            .await
+            .map(|output| output.lines().map(String::from).collect())
+    }
+
+    /// Gets the id of the empty tree in the repository's hash format
+    async fn empty_tree(&self) -> Result<String> {
+        // With nothing on stdin, mktree writes the empty tree
+        self.execute(vec!["mktree".to_string()]).await
+    }
+
+    /// Resolves a revision to its full object name
+    ///
+    /// # Arguments
+    /// * `revision` - Any revision git understands, e.g. `HEAD` or `main^{tree}`
+    async fn rev_parse(&self, revision: &str) -> Result<String> {
+        self.execute(vec![
+            "rev-parse".to_string(),
+            "--verify".to_string(),
+            revision.to_string(),
+        ])
+        .await
+    }
+
+    /// Writes the current index as a tree object and returns its id
+    async fn write_tree(&self) -> Result<String> {
+        self.execute(vec!["write-tree".to_string()]).await
+    }
+
+    /// Creates a commit object for a tree without touching any refs
+    ///
+    /// # Arguments
+    /// * `tree` - The tree the commit records
+    /// * `parents` - Parent commits, in order
+    /// * `message` - The commit message
+    async fn commit_tree(&self, tree: &str, parents: &[String], message: &str) -> Result<String> {
+        let mut args = vec!["commit-tree".to_string(), tree.to_string()];
+        for parent in parents {
+            args.push("-p".to_string());
+            args.push(parent.clone());
+        }
+        args.push("-m".to_string());
+        args.push(message.to_string());
+        self.execute(args).await
+    }
+
+    /// Creates a commit object like [`Git::commit_tree`], dated at the given time
+    /// instead of now
+    ///
+    /// # Arguments
+    /// * `time` - The author and committer date, as a Unix timestamp
+    async fn commit_tree_at(
+        &self,
+        tree: &str,
+        parents: &[String],
+        message: &str,
+        time: i64,
+    ) -> Result<String> {
+        let mut args = vec!["commit-tree".to_string(), tree.to_string()];
+        for parent in parents {
+            args.push("-p".to_string());
+            args.push(parent.clone());
+        }
+        args.push("-m".to_string());
+        args.push(message.to_string());
+        let date = format!("@{} +0000", time);
+        self.execute_with_env(
+            args,
+            vec![
+                ("GIT_AUTHOR_DATE".to_string(), date.clone()),
+                ("GIT_COMMITTER_DATE".to_string(), date),
+            ],
+        )
+        .await
+    }
+
+    /// Creates a ref pointing at the given commit, failing if it already exists
+    ///
+    /// # Arguments
+    /// * `reference` - The full name of the ref to create, e.g. `refs/heads/wip/jane/...`
+    /// * `target` - The commit the ref should point to
+    async fn create_ref(&self, reference: &str, target: &str) -> Result<String> {
+        self.execute(vec![
+            "update-ref".to_string(),
+            reference.to_string(),
+            target.to_string(),
+            // An empty old value makes git refuse to overwrite an existing ref
+            String::new(),
+        ])
+        .await
+    }
+
+    /// Moves a ref to another commit, if it still points to `previous`
+    ///
+    /// # Arguments
+    /// * `reference` - The full name of the ref to move, e.g. `refs/heads/wip/jane/...`
+    /// * `target` - The commit the ref should point to
+    /// * `previous` - The commit the ref is expected to point to now
+    async fn update_ref(&self, reference: &str, target: &str, previous: &str) -> Result<String> {
+        self.execute(vec![
+            "update-ref".to_string(),
+            reference.to_string(),
+            target.to_string(),
+            previous.to_string(),
+        ])
+        .await
+    }
+
+    /// Gets the absolute path of the repository's git directory
+    async fn git_dir(&self) -> Result<String> {
+        self.execute(vec![
+            "rev-parse".to_string(),
+            "--absolute-git-dir".to_string(),
+        ])
+        .await
+    }
+
+    /// Removes untracked files and directories from the whole working tree
+    ///
+    /// Ignored files are left alone, like `git stash --include-untracked` does.
+    async fn clean_untracked(&self) -> Result<String> {
+        self.execute(vec![
+            "clean".to_string(),
+            "-f".to_string(),
+            "-d".to_string(),
+            "-q".to_string(),
+            "--".to_string(),
+            ":/".to_string(),
+        ])
+        .await
+    }
+
+    /// Moves the index from one tree to another with a two-tree merge
+    ///
+    /// Only paths that differ between `from` and `to` are touched, and git refuses
+    /// to overwrite entries that don't match `from`.
+    ///
+    /// # Arguments
+    /// * `from` - The tree the index is expected to match
+    /// * `to` - The tree to move to
+    /// * `update_worktree` - Also update the files in the working tree
+    async fn read_tree_merge(&self, from: &str, to: &str, update_worktree: bool) -> Result<String> {
+        let mut args = vec!["read-tree".to_string(), "-m".to_string()];
+        if update_worktree {
+            args.push("-u".to_string());
+        }
+        args.push(from.to_string());
+        args.push(to.to_string());
+        self.execute(args).await
+    }
+
+    /// Applies the changes from `base` to `theirs` onto the index and working tree
+    /// with a three-way merge. Both must match `ours`.
+    ///
+    /// Conflicts are left in the files with standard conflict markers, labelled
+    /// with `ours_label` and `theirs_label`, and as unmerged index entries.
+    ///
+    /// # Returns
+    /// * `Ok(Vec<String>)` - The conflicted files, empty if the merge was clean
+    async fn merge_into_worktree(
+        &self,
+        base: &str,
+        ours: &str,
+        ours_label: &str,
+        theirs: &str,
+        theirs_label: &str,
+    ) -> Result<Vec<String>> {
+        // merge-recursive names each side in the conflict markers after these
+        let labels = vec![
+            (format!("GITHEAD_{}", ours), ours_label.to_string()),
+            (format!("GITHEAD_{}", theirs), theirs_label.to_string()),
+        ];
+        let merge = self
+            .execute_with_env(
+                vec![
+                    "merge-recursive".to_string(),
+                    base.to_string(),
+                    "--".to_string(),
+                    ours.to_string(),
+                    theirs.to_string(),
+                ],
+                labels,
+            )
+            .await;
+        match merge {
+            Ok(_) => Ok(Vec::new()),
+            // Exit code 1 means the merge stopped with conflicts
+            Err(e) if e.to_string().contains("exit code: 1)") => self.get_conflicted_files().await,
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Lists the files with unmerged entries in the index
+    async fn get_conflicted_files(&self) -> Result<Vec<String>> {
         self.execute(vec![
             "diff".to_string(),
-            "--cached".to_string(),
             "--name-only".to_string(),
+            "--diff-filter=U".to_string(),
+        ])
+        .await
+        .map(|output| output.lines().map(String::from).collect())
+    }
+
+    /// Pushes a branch to a remote repository
+    ///
+    /// # Arguments
+    /// * `remote` - Name of the remote (e.g., "origin")
+    /// * `branch` - Name of the branch to push
+    async fn push(&self, remote: &str, branch: &str) -> Result<String> {
+        self.execute(vec![
+            "push".to_string(),
+            remote.to_string(),
+            branch.to_string(),
         ])
         .await
     }
 
-    /// Gets a list of changed but unstaged files
-    async fn get_changed_files(&self) -> Result<String> {
-        self.execute(vec!["diff".to_string(), "--name-only".to_string()])
+    /// Fetches a branch from a remote into its remote-tracking ref
+    ///
+    /// # Returns
+    /// * `Ok(true)` if the branch was fetched
+    /// * `Ok(false)` if the remote has no such branch
+    async fn fetch_branch(&self, remote: &str, branch: &str) -> Result<bool> {
+        let reference = format!("refs/heads/{}", branch);
+        // Unlike fetch, ls-remote has an exit code of its own for a missing branch
+        match self
+            .execute(vec![
+                "ls-remote".to_string(),
+                "--exit-code".to_string(),
+                remote.to_string(),
+                reference.clone(),
+            ])
             .await
+        {
+            Err(e) if e.to_string().contains("exit code: 2)") => return Ok(false),
+            result => result?,
+        };
+        self.execute(vec![
+            "fetch".to_string(),
+            "--quiet".to_string(),
+            remote.to_string(),
+            format!("+{}:refs/remotes/{}/{}", reference, remote, branch),
+        ])
+        .await?;
+        Ok(true)
     }
 
-    /// Gets a list of untracked files
-    async fn get_untracked_files(&self) -> Result<String> {
+    /// Fetches the branches starting with `prefix` from a remote, removing the
+    /// remote-tracking refs of those that were deleted there
+    async fn fetch_branches(&self, remote: &str, prefix: &str) -> Result<String> {
         self.execute(vec![
-            "ls-files".to_string(),
-            "--others".to_string(),
-            "--exclude-standard".to_string(),
+            "fetch".to_string(),
+            "--quiet".to_string(),
+            "--prune".to_string(),
+            remote.to_string(),
+            format!("+refs/heads/{0}*:refs/remotes/{1}/{0}*", prefix, remote),
         ])
         .await
     }
 
-    /// Gets a list of WIP branches for a specific user
-    async fn get_user_wip_branches(&self, username: &str) -> Result<Vec<String>> {
-        let output = Output::new().await?;
-        let git_output = self
+    /// Gets a list of staged files
+    ///
+    /// Renames are listed as the deleted and the added path, so both can be restaged.
+    async fn get_staged_files(&self) -> Result<Vec<String>> {
+        let output = self
             .execute(vec![
-                "branch".to_string(),
-                "--all".to_string(),
-                "--format=%(refname:short)".to_string(),
+                "diff".to_string(),
+                "--cached".to_string(),
+                "--name-only".to_string(),
+                "--no-renames".to_string(),
+                "-z".to_string(),
             ])
             .await?;
+        Ok(split_paths(&output))
+    }
 
-        output.debug(&format!("Raw git output:\n{}", git_output))?;
+    /// Gets a list of changed but unstaged files, deletions included
+    async fn get_changed_files(&self) -> Result<Vec<String>> {
+        let output = self
+            .execute(vec![
+                "diff".to_string(),
+                "--name-only".to_string(),
+                "--no-renames".to_string(),
+                "-z".to_string(),
+            ])
+            .await?;
+        Ok(split_paths(&output))
+    }
 
-        let wip_prefix = format!("wip/{}/", username);
-        output.debug(&format!("Looking for branches with prefix: {}", wip_prefix))?;
+    /// Gets a list of untracked files
+    async fn get_untracked_files(&self) -> Result<Vec<String>> {
+        let output = self
+            .execute(vec![
+                "ls-files".to_string(),
+                "--others".to_string(),
+                "--exclude-standard".to_string(),
+                "-z".to_string(),
+            ])
+            .await?;
+        Ok(split_paths(&output))
+    }
 
-        let branches: Vec<String> = git_output
-            .lines()
-            .map(|line| line.trim().to_string())
-            .filter(|line| !line.is_empty())
-            .filter(|line| line.starts_with(&wip_prefix))
-            .map(|line| line.replace("remotes/origin/", ""))
-            .collect::<HashSet<_>>()
+    /// Gets a user's WIP branches, local and on any remote
+    async fn get_user_wip_branches(&self, username: &str) -> Result<Vec<WipBranch>> {
+        let output = Output::new().await?;
+        let branches: Vec<WipBranch> = self
+            .get_all_wip_branches()
+            .await?
             .into_iter()
+            .filter(|branch| branch.user == username)
             .collect();
 
-        output.debug(&format!("Found branches: {:?}", branches))?;
+        let names: Vec<&str> = branches.iter().map(|branch| branch.name.as_str()).collect();
+        output.debug(&format!("Found branches: {:?}", names))?;
         Ok(branches)
     }
 
+    /// Gets the WIP branches of all users, both local and on remotes
+    ///
+    /// Branches are recognised by the configured `wippy.branchTemplate`, or by
+    /// their tip commit for names older versions saved with a free-form date.
+    async fn get_all_wip_branches(&self) -> Result<Vec<WipBranch>> {
+        let template = BranchTemplate::load(self).await?;
+        let refs = self
+            .execute(vec![
+                "for-each-ref".to_string(),
+                "--format=%(refname) %(subject)".to_string(),
+                "refs/heads/".to_string(),
+                "refs/remotes/".to_string(),
+            ])
+            .await?;
+        let remotes = self.get_remotes().await?;
+        Ok(parse_wip_refs(&refs, &remotes, &template))
+    }
+
+    /// Lists the names of all local branches
+    async fn get_local_branches(&self) -> Result<Vec<String>> {
+        self.execute(vec![
+            "for-each-ref".to_string(),
+            "--format=%(refname:short)".to_string(),
+            "refs/heads/".to_string(),
+        ])
+        .await
+        .map(|output| output.lines().map(String::from).collect())
+    }
+
     /// Verifies if a branch exists
     async fn branch_exists(&self, branch: &str) -> Result<bool> {
         self.execute(vec![
@@ -245,6 +623,42 @@ pub trait Git: Send + Sync {
         .or_else(|_| Ok(false))
     }
 
+    /// Checks whether a commit is reachable from another one, e.g. whether a
+    /// branch was merged into the default branch
+    async fn is_ancestor(&self, ancestor: &str, descendant: &str) -> Result<bool> {
+        match self
+            .execute(vec![
+                "merge-base".to_string(),
+                "--is-ancestor".to_string(),
+                ancestor.to_string(),
+                descendant.to_string(),
+            ])
+            .await
+        {
+            Ok(_) => Ok(true),
+            Err(e) if e.to_string().contains("exit code: 1)") => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Gets the branch a remote's HEAD points to, e.g. `main`
+    ///
+    /// Returns `None` if the remote's HEAD was never fetched, as for remotes
+    /// added with `git remote add` rather than cloned.
+    async fn default_branch(&self, remote: &str) -> Result<Option<String>> {
+        let prefix = format!("{}/", remote);
+        Ok(self
+            .execute(vec![
+                "symbolic-ref".to_string(),
+                "--quiet".to_string(),
+                "--short".to_string(),
+                format!("refs/remotes/{}/HEAD", remote),
+            ])
+            .await
+            .ok()
+            .and_then(|branch| branch.trim().strip_prefix(&prefix).map(String::from)))
+    }
+
     /// Gets the last commit message from a branch
     async fn get_commit_message(&self, branch: &str) -> Result<String> {
         self.execute(vec![
@@ -256,6 +670,22 @@ pub trait Git: Send + Sync {
         .await
     }
 
+    /// Gets the commit time of the last commit on a branch as a Unix timestamp
+    async fn get_commit_time(&self, branch: &str) -> Result<i64> {
+        let output = self
+            .execute(vec![
+                "log".to_string(),
+                "-1".to_string(),
+                "--format=%ct".to_string(),
+                branch.to_string(),
+            ])
+            .await?;
+        output
+            .trim()
+            .parse()
+            .with_context(|| format!("Invalid commit time for '{}': {}", branch, output))
+    }
+
     /// Stashes changes with a message
     #[allow(dead_code)]
     async fn stash_push(&self, message: &str) -> Result<String> {
@@ -304,25 +734,26 @@ pub trait Git: Send + Sync {
             .map(|output| output.lines().map(|s| s.to_string()).collect())
     }
 
-    /// Stages specific files
+    /// Stages specific files, deletions included, with a single `git add`
     async fn stage_files(&self, files: &[String]) -> Result<()> {
-        for file in files {
-            self.execute(vec!["add".to_string(), file.clone()]).await?;
+        if files.is_empty() {
+            return Ok(());
         }
+        self.execute_with_stdin(pathspec_args(&["add"]), pathspec_input(files))
+            .await?;
         Ok(())
     }
 
-    /// Unstages specific files
+    /// Unstages specific files with a single `git reset`
+    ///
+    /// On an unborn branch the files are removed from the index instead, there is
+    /// no HEAD to reset them to.
     async fn unstage_files(&self, files: &[String]) -> Result<()> {
-        for file in files {
-            self.execute(vec![
-                "reset".to_string(),
-                "HEAD".to_string(),
-                "--".to_string(),
-                file.clone(),
-            ])
-            .await?;
+        if files.is_empty() {
+            return Ok(());
         }
+        self.execute_with_stdin(pathspec_args(&["reset", "--quiet"]), pathspec_input(files))
+            .await?;
         Ok(())
     }
 
@@ -330,8 +761,7 @@ pub trait Git: Send + Sync {
     #[allow(dead_code)]
     async fn reset_soft(&self) -> Result<String>;
 
-    /// Resets the current branch and working directory to HEAD
-    #[allow(dead_code)]
+    /// Resets the current branch and working directory to HEAD, or empties them on an unborn branch
     async fn reset_hard(&self) -> Result<String>;
 
     /// Check if working tree is clean
@@ -370,14 +800,103 @@ pub trait Git: Send + Sync {
             .await
     }
 
-    /// Writes content to a file
-    #[allow(dead_code)]
-    async fn write_file(&self, file: &str, content: &str) -> Result<()> {
-        use tokio::fs;
-        fs::write(file, content).await.map_err(|e| e.into())
+    /// Finds a stash entry by its message
+    ///
+    /// # Returns
+    /// * `Ok(Some(String))` - A stash ref like `stash@{1}`
+    /// * `Ok(None)` if no stash entry has the message
+    async fn find_stash(&self, message: &str) -> Result<Option<String>> {
+        let stash_list = self
+            .execute(vec!["stash".to_string(), "list".to_string()])
+            .await?;
+        Ok(find_stash_ref(&stash_list, message))
+    }
+}
+
+/// Where HEAD points.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Head {
+    /// The checked out branch, `None` on a detached checkout
+    pub branch: Option<String>,
+    /// The checked out commit, `None` on an unborn branch
+    pub commit: Option<String>,
+}
+
+impl Head {
+    /// Returns what `git checkout` needs to come back here: the branch, or the
+    /// commit on a detached checkout.
+    pub fn checkout_target(&self) -> Option<&str> {
+        self.branch.as_deref().or(self.commit.as_deref())
+    }
+
+    /// Describes HEAD for messages, e.g. `main` or `a1b2c3d`
+    pub fn describe(&self) -> String {
+        match (&self.branch, &self.commit) {
+            (Some(branch), _) => branch.clone(),
+            (None, Some(commit)) => short_commit(commit).to_string(),
+            (None, None) => "HEAD".to_string(),
+        }
+    }
+}
+
+/// Builds the arguments of a command that reads its paths from standard input.
+///
+/// Paths are matched literally, so names containing `*` or `:` aren't taken as
+/// patterns or pathspec magic.
+fn pathspec_args(command: &[&str]) -> Vec<String> {
+    let mut args = vec!["--literal-pathspecs".to_string()];
+    args.extend(command.iter().map(|arg| arg.to_string()));
+    args.push("--pathspec-from-file=-".to_string());
+    args.push("--pathspec-file-nul".to_string());
+    args
+}
+
+/// Joins paths for `--pathspec-file-nul`, which allows any character in a path.
+fn pathspec_input(files: &[String]) -> Vec<u8> {
+    let mut input = Vec::new();
+    for file in files {
+        input.extend_from_slice(file.as_bytes());
+        input.push(0);
+    }
+    input
+}
+
+/// Splits the output of a command run with `-z`.
+///
+/// Paths are listed as they are instead of C-quoted, so non-ASCII names and
+/// names containing newlines come through unchanged.
+fn split_paths(output: &str) -> Vec<String> {
+    output
+        .split('\0')
+        .filter(|path| !path.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Returns the commit a commit was made on top of, or the empty tree for a
+/// root commit, e.g. one saved on an unborn branch.
+pub async fn parent_or_empty_tree(git: &impl Git, commit: &str) -> Result<String> {
+    match git.get_parents(commit).await?.into_iter().next() {
+        Some(parent) => Ok(parent),
+        None => git.empty_tree().await,
     }
 }
 
+/// Abbreviates a commit id the way git does by default.
+pub fn short_commit(commit: &str) -> &str {
+    &commit[..commit.len().min(7)]
+}
+
+/// Finds the stash ref whose message ends with the given message.
+///
+/// `git stash list` prints entries as `stash@{n}: On branch: message`.
+fn find_stash_ref(stash_list: &str, message: &str) -> Option<String> {
+    stash_list
+        .lines()
+        .position(|line| line.ends_with(&format!(": {}", message)))
+        .map(|index| format!("stash@{{{}}}", index))
+}
+
 /// Thread-safe Git command implementation.
 /// Uses a unit struct since no internal state is needed.
 #[derive(Clone)]
@@ -388,6 +907,78 @@ impl GitCommand {
     pub fn new() -> Self {
         Self(())
     }
+
+    /// Runs git and captures its output, feeding it `input` on standard input if given.
+    async fn run(
+        &self,
+        args: Vec<String>,
+        env: Vec<(String, String)>,
+        input: Option<Vec<u8>>,
+    ) -> Result<String> {
+        let mut child = Command::new("git")
+            .args(&args)
+            .envs(env)
+            .stdin(if input.is_some() {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .context(format!("Failed to execute git command: {:?}", args))?;
+
+        if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+            stdin
+                .write_all(&input)
+                .await
+                .context(format!("Failed to write to git command: {:?}", args))?;
+            // Closing stdin tells git the input is complete
+            drop(stdin);
+        }
+        let output = child
+            .wait_with_output()
+            .await
+            .context(format!("Failed to execute git command: {:?}", args))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            // The exit code tells expected failures apart, e.g. an unset config key
+            let code = output
+                .status
+                .code()
+                .map_or("none".to_string(), |code| code.to_string());
+            return Err(anyhow::anyhow!(
+                "Git command 'git {}' failed (exit code: {}): {}",
+                args.join(" "),
+                code,
+                stderr.trim()
+            ));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+}
+
+/// Opens the git backend commands run against.
+///
+/// Built with the `libgit2` feature, refs, trees and commits are handled
+/// in-process unless `wippy.backend` is set to `git`. Otherwise every operation
+/// runs the git command.
+#[cfg(feature = "libgit2")]
+pub fn open_git() -> crate::utils::InProcessGit {
+    crate::utils::InProcessGit::open()
+}
+
+/// Opens the git backend commands run against.
+///
+/// Built with the `libgit2` feature, refs, trees and commits are handled
+/// in-process unless `wippy.backend` is set to `git`. Otherwise every operation
+/// runs the git command.
+#[cfg(not(feature = "libgit2"))]
+pub fn open_git() -> GitCommand {
+    GitCommand::new()
 }
 
 impl Default for GitCommand {
@@ -399,23 +990,39 @@ impl Default for GitCommand {
 #[async_trait]
 impl Git for GitCommand {
     async fn execute(&self, args: Vec<String>) -> Result<String> {
-        let output = Command::new("git")
+        self.execute_with_env(args, Vec::new()).await
+    }
+
+    async fn execute_with_env(
+        &self,
+        args: Vec<String>,
+        env: Vec<(String, String)>,
+    ) -> Result<String> {
+        self.run(args, env, None).await
+    }
+
+    async fn execute_with_stdin(&self, args: Vec<String>, input: Vec<u8>) -> Result<String> {
+        self.run(args, Vec::new(), Some(input)).await
+    }
+
+    async fn execute_in_terminal(&self, args: Vec<String>) -> Result<()> {
+        let status = Command::new("git")
             .args(&args)
             .kill_on_drop(true)
-            .output()
+            .status()
             .await
             .context(format!("Failed to execute git command: {:?}", args))?;
 
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
+        if !status.success() {
             return Err(anyhow::anyhow!(
-                "Git command 'git {}' failed: {}",
+                "Git command 'git {}' failed (exit code: {})",
                 args.join(" "),
-                stderr.trim()
+                status
+                    .code()
+                    .map_or("none".to_string(), |code| code.to_string())
             ));
         }
-
-        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+        Ok(())
     }
 
     async fn stage_all(&self) -> Result<String> {
@@ -423,35 +1030,6 @@ impl Git for GitCommand {
             .await
     }
 
-    async fn get_user_wip_branches(&self, username: &str) -> Result<Vec<String>> {
-        let output = Output::new().await?;
-        let git_output = self
-            .execute(vec![
-                "branch".to_string(),
-                "--all".to_string(),
-                "--format=%(refname:short)".to_string(),
-            ])
-            .await?;
-
-        output.debug(&format!("Raw git output:\n{}", git_output))?;
-
-        let wip_prefix = format!("wip/{}/", username);
-        output.debug(&format!("Looking for branches with prefix: {}", wip_prefix))?;
-
-        let branches: Vec<String> = git_output
-            .lines()
-            .map(|line| line.trim().to_string())
-            .filter(|line| !line.is_empty())
-            .filter(|line| line.starts_with(&wip_prefix))
-            .map(|line| line.replace("remotes/origin/", ""))
-            .collect::<HashSet<_>>()
-            .into_iter()
-            .collect();
-
-        output.debug(&format!("Found branches: {:?}", branches))?;
-        Ok(branches)
-    }
-
     async fn is_working_tree_clean(&self) -> Result<bool> {
         let output = self
             .execute(vec!["status".to_string(), "--porcelain".to_string()])
@@ -471,8 +1049,24 @@ impl Git for GitCommand {
     }
 
     /// Resets the current branch and working directory to HEAD
-    #[allow(dead_code)]
+    ///
+    /// On an unborn branch there is no HEAD to reset to, so every tracked file is
+    /// removed from the index and the working tree instead.
     async fn reset_hard(&self) -> Result<String> {
+        if self.get_head().await?.commit.is_none() {
+            return self
+                .execute(vec![
+                    "rm".to_string(),
+                    "-r".to_string(),
+                    "-f".to_string(),
+                    "-q".to_string(),
+                    "--ignore-unmatch".to_string(),
+                    "--".to_string(),
+                    ":/".to_string(),
+                ])
+                .await;
+        }
+
         self.execute(vec![
             "reset".to_string(),
             "--hard".to_string(),
@@ -486,6 +1080,65 @@ impl Git for GitCommand {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_head() {
+        let on_branch = Head {
+            branch: Some("main".to_string()),
+            commit: Some("1f0e3c2b9a8d7e6f".to_string()),
+        };
+        assert_eq!(on_branch.checkout_target(), Some("main"));
+        assert_eq!(on_branch.describe(), "main");
+
+        let detached = Head {
+            branch: None,
+            commit: Some("1f0e3c2b9a8d7e6f".to_string()),
+        };
+        assert_eq!(detached.checkout_target(), Some("1f0e3c2b9a8d7e6f"));
+        assert_eq!(detached.describe(), "1f0e3c2");
+
+        let unborn = Head {
+            branch: Some("main".to_string()),
+            commit: None,
+        };
+        assert_eq!(unborn.checkout_target(), Some("main"));
+    }
+
+    #[test]
+    fn test_pathspec_from_stdin() {
+        assert_eq!(
+            pathspec_args(&["reset", "--quiet"]),
+            [
+                "--literal-pathspecs",
+                "reset",
+                "--quiet",
+                "--pathspec-from-file=-",
+                "--pathspec-file-nul"
+            ]
+        );
+        assert_eq!(
+            pathspec_input(&["a b.txt".to_string(), "*.rs".to_string()]),
+            b"a b.txt\0*.rs\0"
+        );
+        assert_eq!(
+            split_paths("\u{e4}.txt\0line\nbreak.txt\0"),
+            ["\u{e4}.txt", "line\nbreak.txt"]
+        );
+    }
+
+    #[test]
+    fn test_find_stash_ref() {
+        let stash_list = "stash@{0}: WIP on main: 1234567 Initial commit
+stash@{1}: On main: git-wippy-autostash-main
+stash@{2}: On main: git-wippy-autostash-main-2";
+
+        assert_eq!(
+            find_stash_ref(stash_list, "git-wippy-autostash-main"),
+            Some("stash@{1}".to_string())
+        );
+        assert_eq!(find_stash_ref(stash_list, "git-wippy-autostash-dev"), None);
+        assert_eq!(find_stash_ref("", "git-wippy-autostash-main"), None);
+    }
+
     #[tokio::test]
     async fn test_git_command_success() {
         let mut mock = MockGit::new();
@@ -538,6 +1191,16 @@ mod tests {
         assert!(mock.checkout("test-branch").await.is_ok());
     }
 
+    fn wip_branch(name: &str) -> WipBranch {
+        WipBranch {
+            name: name.to_string(),
+            user: "test-user".to_string(),
+            timestamp: name.rsplit('/').next().unwrap().to_string(),
+            local: true,
+            remotes: Vec::new(),
+        }
+    }
+
     #[tokio::test]
     async fn test_get_user_wip_branches() -> Result<()> {
         let mut mock = MockGit::new();
@@ -546,15 +1209,15 @@ mod tests {
             .with(mockall::predicate::eq("test-user"))
             .returning(|_| {
                 Ok(vec![
-                    "wip/test-user/branch1".to_string(),
-                    "wip/test-user/branch2".to_string(),
+                    wip_branch("wip/test-user/2024-03-21-17-59-30"),
+                    wip_branch("wip/test-user/2024-03-22-09-00-00"),
                 ])
             });
 
         let branches = mock.get_user_wip_branches("test-user").await?;
         assert_eq!(branches.len(), 2);
-        assert!(branches.contains(&"wip/test-user/branch1".to_string()));
-        assert!(branches.contains(&"wip/test-user/branch2".to_string()));
+        assert!(branches.contains(&wip_branch("wip/test-user/2024-03-21-17-59-30")));
+        assert!(branches.contains(&wip_branch("wip/test-user/2024-03-22-09-00-00")));
         Ok(())
     }
 
@@ -590,11 +1253,11 @@ mod tests {
 
         mock.expect_get_user_wip_branches()
             .with(mockall::predicate::eq("test-user"))
-            .returning(|_| Ok(vec!["wip/test-user/branch1".to_string()]));
+            .returning(|_| Ok(vec![wip_branch("wip/test-user/2024-03-21-17-59-30")]));
 
         let branches = mock.get_user_wip_branches("test-user").await?;
         assert_eq!(branches.len(), 1);
-        assert!(branches.contains(&"wip/test-user/branch1".to_string()));
+        assert!(branches.contains(&wip_branch("wip/test-user/2024-03-21-17-59-30")));
         Ok(())
     }
 }
@@ -1,4 +1,4 @@
-use crate::utils::Git;
+use crate::utils::{sanitize_component, Git};
 use anyhow::Result;
 
 pub async fn git_username_with_git(git: &impl Git) -> Result<String> {
@@ -12,7 +12,20 @@ pub async fn git_username_with_git(git: &impl Git) -> Result<String> {
         anyhow::bail!("Git username not found. Please configure your git user.name");
     }
 
-    Ok(username)
+    username_component(&username)
+}
+
+/// Turns a user name into the `{user}` part of a WIP branch name, e.g. `Jane Doe`
+/// becomes `Jane-Doe`.
+pub fn username_component(username: &str) -> Result<String> {
+    let component = sanitize_component(username);
+    if component.is_empty() {
+        anyhow::bail!(
+            "User name '{}' has nothing that can be used in a branch name. Pass one with --username",
+            username
+        );
+    }
+    Ok(component)
 }
 
 #[cfg(test)]
@@ -32,7 +45,7 @@ mod tests {
             .returning(|_| Ok("Test User".to_string()));
 
         let username = git_username_with_git(&mock_git).await?;
-        assert_eq!(username, "Test User");
+        assert_eq!(username, "Test-User");
         Ok(())
     }
 
@@ -69,4 +82,21 @@ mod tests {
         assert!(result.unwrap_err().to_string().contains("not found"));
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_git_username_sanitized() -> Result<()> {
+        let mut mock_git = MockGit::new();
+        mock_git
+            .expect_execute()
+            .returning(|_| Ok("Zoë O'Neil".to_string()));
+
+        assert_eq!(git_username_with_git(&mock_git).await?, "Zoë-O'Neil");
+        Ok(())
+    }
+
+    #[test]
+    fn test_username_component_invalid() {
+        let error = username_component("???").unwrap_err();
+        assert!(error.to_string().contains("'???'"));
+    }
 }
@@ -0,0 +1,404 @@
+use crate::utils::{parse_wip_refs, BranchTemplate, Git, GitCommand, Head, WipBranch};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use git2::{BranchType, ObjectType, Oid, Repository, Signature};
+use std::sync::Mutex;
+
+/// Git config key that picks the backend, `libgit2` (the default) or `git`
+pub const BACKEND_CONFIG_KEY: &str = "wippy.backend";
+
+/// A [`Git`] backend that lists refs, reads trees and creates commits in-process
+/// with libgit2.
+///
+/// Everything else, such as checkouts, merges and pushes, still runs the git
+/// command. When the repository can't be opened or `wippy.backend` is set to
+/// `git`, every operation falls back to the git command.
+pub struct InProcessGit {
+    repo: Option<Mutex<Repository>>,
+    command: GitCommand,
+}
+
+impl InProcessGit {
+    /// Opens the repository git would use in the current directory.
+    pub fn open() -> Self {
+        let repo = Repository::open_from_env()
+            .ok()
+            .filter(|repo| !uses_git_command(repo));
+        Self {
+            repo: repo.map(Mutex::new),
+            command: GitCommand::new(),
+        }
+    }
+
+    /// Runs `f` against the repository, or returns `None` to fall back to the git command.
+    fn with_repo<T>(&self, f: impl FnOnce(&Repository) -> Result<T>) -> Option<Result<T>> {
+        let repo = self.repo.as_ref()?;
+        let repo = match repo.lock() {
+            Ok(repo) => repo,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        Some(f(&repo))
+    }
+}
+
+/// Whether `wippy.backend` asks for the git command.
+fn uses_git_command(repo: &Repository) -> bool {
+    repo.config()
+        .and_then(|config| config.get_string(BACKEND_CONFIG_KEY))
+        .is_ok_and(|backend| backend == "git")
+}
+
+/// Resolves a revision to the commit it points at.
+fn find_commit<'r>(repo: &'r Repository, revision: &str) -> Result<git2::Commit<'r>> {
+    repo.revparse_single(revision)
+        .and_then(|object| object.peel_to_commit())
+        .with_context(|| format!("Failed to resolve commit '{}'", revision))
+}
+
+/// Builds a signature like git does, from `GIT_{role}_NAME` and `GIT_{role}_EMAIL`
+/// or else `user.name` and `user.email`.
+fn signature(repo: &Repository, role: &str) -> Result<Signature<'static>> {
+    let config = repo.config()?;
+    let value = |env: &str, key: &str| {
+        std::env::var(format!("GIT_{}_{}", role, env))
+            .ok()
+            .or_else(|| config.get_string(key).ok())
+    };
+    match (value("NAME", "user.name"), value("EMAIL", "user.email")) {
+        (Some(name), Some(email)) => Ok(Signature::now(&name, &email)?),
+        _ => anyhow::bail!("Please set user.name and user.email to create commits"),
+    }
+}
+
+/// Lists all refs starting with one of `prefixes` as `{refname} {subject}`
+/// lines, sorted like `git for-each-ref --format='%(refname) %(subject)'` does.
+fn ref_subjects(repo: &Repository, prefixes: &[&str]) -> Result<Vec<String>> {
+    let mut lines = Vec::new();
+    for reference in repo.references()? {
+        let reference = reference?;
+        if let Some(name) = reference.name() {
+            if prefixes.iter().any(|prefix| name.starts_with(prefix)) {
+                let subject = reference
+                    .peel_to_commit()
+                    .ok()
+                    .and_then(|commit| commit.summary().map(String::from))
+                    .unwrap_or_default();
+                lines.push(format!("{} {}", name, subject));
+            }
+        }
+    }
+    lines.sort();
+    Ok(lines)
+}
+
+#[async_trait]
+impl Git for InProcessGit {
+    async fn execute(&self, args: Vec<String>) -> Result<String> {
+        self.command.execute(args).await
+    }
+
+    async fn execute_with_env(
+        &self,
+        args: Vec<String>,
+        env: Vec<(String, String)>,
+    ) -> Result<String> {
+        self.command.execute_with_env(args, env).await
+    }
+
+    async fn execute_with_stdin(&self, args: Vec<String>, input: Vec<u8>) -> Result<String> {
+        self.command.execute_with_stdin(args, input).await
+    }
+
+    async fn execute_in_terminal(&self, args: Vec<String>) -> Result<()> {
+        self.command.execute_in_terminal(args).await
+    }
+
+    async fn get_head(&self) -> Result<Head> {
+        let head = self.with_repo(|repo| {
+            let branch = repo
+                .find_reference("HEAD")?
+                .symbolic_target()
+                .and_then(|target| target.strip_prefix("refs/heads/"))
+                .map(String::from);
+            // An unborn branch has no commit to peel to
+            let commit = repo
+                .head()
+                .and_then(|head| head.peel_to_commit())
+                .ok()
+                .map(|commit| commit.id().to_string());
+            Ok(Head { branch, commit })
+        });
+        match head {
+            Some(head) => head,
+            None => self.command.get_head().await,
+        }
+    }
+
+    async fn get_parents(&self, commit: &str) -> Result<Vec<String>> {
+        let parents = self.with_repo(|repo| {
+            Ok(find_commit(repo, commit)?
+                .parent_ids()
+                .map(|id| id.to_string())
+                .collect())
+        });
+        match parents {
+            Some(parents) => parents,
+            None => self.command.get_parents(commit).await,
+        }
+    }
+
+    async fn empty_tree(&self) -> Result<String> {
+        let tree = self.with_repo(|repo| Ok(repo.treebuilder(None)?.write()?.to_string()));
+        match tree {
+            Some(tree) => tree,
+            None => self.command.empty_tree().await,
+        }
+    }
+
+    async fn rev_parse(&self, revision: &str) -> Result<String> {
+        let id = self.with_repo(|repo| {
+            repo.revparse_single(revision)
+                .map(|object| object.id().to_string())
+                .with_context(|| format!("Failed to resolve '{}'", revision))
+        });
+        match id {
+            Some(id) => id,
+            None => self.command.rev_parse(revision).await,
+        }
+    }
+
+    async fn write_tree(&self) -> Result<String> {
+        let tree = self.with_repo(|repo| {
+            let mut index = repo.index()?;
+            // The git command may have changed the index since it was last read
+            index.read(true)?;
+            Ok(index.write_tree()?.to_string())
+        });
+        match tree {
+            Some(tree) => tree,
+            None => self.command.write_tree().await,
+        }
+    }
+
+    async fn commit_tree(&self, tree: &str, parents: &[String], message: &str) -> Result<String> {
+        let commit = self.with_repo(|repo| {
+            let tree = repo.find_tree(Oid::from_str(tree)?)?;
+            let parents = parents
+                .iter()
+                .map(|parent| find_commit(repo, parent))
+                .collect::<Result<Vec<_>>>()?;
+            let parents: Vec<_> = parents.iter().collect();
+            // git commit-tree ends the message with a newline
+            let message = format!("{}\n", message.trim_end_matches('\n'));
+            let id = repo.commit(
+                None,
+                &signature(repo, "AUTHOR")?,
+                &signature(repo, "COMMITTER")?,
+                &message,
+                &tree,
+                &parents,
+            )?;
+            Ok(id.to_string())
+        });
+        match commit {
+            Some(commit) => commit,
+            None => self.command.commit_tree(tree, parents, message).await,
+        }
+    }
+
+    async fn create_ref(&self, reference: &str, target: &str) -> Result<String> {
+        let created = self.with_repo(|repo| {
+            let target = repo.revparse_single(target)?.id();
+            // Like the git command, refuse to overwrite an existing ref
+            repo.reference(reference, target, false, "")
+                .with_context(|| format!("Failed to create '{}'", reference))?;
+            Ok(String::new())
+        });
+        match created {
+            Some(created) => created,
+            None => self.command.create_ref(reference, target).await,
+        }
+    }
+
+    async fn get_all_wip_branches(&self) -> Result<Vec<WipBranch>> {
+        let refs = self.with_repo(|repo| ref_subjects(repo, &["refs/heads/", "refs/remotes/"]));
+        match refs {
+            Some(refs) => {
+                let template = BranchTemplate::load(self).await?;
+                let remotes = self.get_remotes().await?;
+                Ok(parse_wip_refs(&refs?.join("\n"), &remotes, &template))
+            }
+            None => self.command.get_all_wip_branches().await,
+        }
+    }
+
+    async fn get_local_branches(&self) -> Result<Vec<String>> {
+        let branches = self.with_repo(|repo| {
+            let mut names = Vec::new();
+            for branch in repo.branches(Some(BranchType::Local))? {
+                if let Some(name) = branch?.0.name()? {
+                    names.push(name.to_string());
+                }
+            }
+            names.sort();
+            Ok(names)
+        });
+        match branches {
+            Some(branches) => branches,
+            None => self.command.get_local_branches().await,
+        }
+    }
+
+    async fn branch_exists(&self, branch: &str) -> Result<bool> {
+        match self.with_repo(|repo| Ok(repo.revparse_single(branch).is_ok())) {
+            Some(exists) => exists,
+            None => self.command.branch_exists(branch).await,
+        }
+    }
+
+    async fn get_commit_message(&self, branch: &str) -> Result<String> {
+        let message = self.with_repo(|repo| {
+            let commit = find_commit(repo, branch)?;
+            Ok(String::from_utf8_lossy(commit.message_bytes())
+                .trim()
+                .to_string())
+        });
+        match message {
+            Some(message) => message,
+            None => self.command.get_commit_message(branch).await,
+        }
+    }
+
+    async fn get_commit_time(&self, branch: &str) -> Result<i64> {
+        let time =
+            self.with_repo(|repo| Ok(find_commit(repo, branch)?.committer().when().seconds()));
+        match time {
+            Some(time) => time,
+            None => self.command.get_commit_time(branch).await,
+        }
+    }
+
+    async fn get_remotes(&self) -> Result<Vec<String>> {
+        let remotes =
+            self.with_repo(|repo| Ok(repo.remotes()?.iter().flatten().map(String::from).collect()));
+        match remotes {
+            Some(remotes) => remotes,
+            None => self.command.get_remotes().await,
+        }
+    }
+
+    async fn show_file(&self, branch: &str, file: &str) -> Result<String> {
+        let content = self.with_repo(|repo| {
+            let spec = format!("{}:{}", branch, file);
+            let blob = repo
+                .revparse_single(&spec)
+                .and_then(|object| object.peel(ObjectType::Blob))
+                .with_context(|| format!("Failed to read '{}'", spec))?;
+            let blob = blob.as_blob().context("Not a file")?;
+            Ok(String::from_utf8_lossy(blob.content()).trim().to_string())
+        });
+        match content {
+            Some(content) => content,
+            None => self.command.show_file(branch, file).await,
+        }
+    }
+
+    async fn stage_all(&self) -> Result<String> {
+        self.command.stage_all().await
+    }
+
+    async fn is_working_tree_clean(&self) -> Result<bool> {
+        self.command.is_working_tree_clean().await
+    }
+
+    async fn reset_soft(&self) -> Result<String> {
+        self.command.reset_soft().await
+    }
+
+    async fn reset_hard(&self) -> Result<String> {
+        self.command.reset_hard().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn init_repo() -> Result<(TempDir, InProcessGit)> {
+        let dir = tempfile::tempdir()?;
+        let repo = Repository::init(dir.path())?;
+        let mut config = repo.config()?;
+        config.set_str("user.name", "test-user")?;
+        config.set_str("user.email", "test@example.com")?;
+        repo.set_head("refs/heads/main")?;
+        let git = InProcessGit {
+            repo: Some(Mutex::new(repo)),
+            command: GitCommand::new(),
+        };
+        Ok((dir, git))
+    }
+
+    #[tokio::test]
+    async fn test_commits_and_refs() -> Result<()> {
+        let (_dir, git) = init_repo()?;
+
+        let head = git.get_head().await?;
+        assert_eq!(head.branch.as_deref(), Some("main"));
+        assert_eq!(head.commit, None);
+
+        let tree = git.empty_tree().await?;
+        assert_eq!(tree, "4b825dc642cb6eb9a060e54bf8d69288fbee4904");
+        let first = git.commit_tree(&tree, &[], "first").await?;
+        let parents = vec![first.clone()];
+        let second = git
+            .commit_tree(&tree, &parents, "second\n\nWippy-Version: 1")
+            .await?;
+        git.create_ref("refs/heads/main", &first).await?;
+        git.create_ref("refs/heads/wip/test-user/2024-03-21-17-59-30", &second)
+            .await?;
+
+        // Existing refs are never overwritten
+        assert!(git.create_ref("refs/heads/main", &second).await.is_err());
+
+        assert_eq!(git.get_head().await?.commit, Some(first.clone()));
+        assert_eq!(git.rev_parse("main").await?, first);
+        assert_eq!(
+            git.get_parents("wip/test-user/2024-03-21-17-59-30").await?,
+            parents
+        );
+        assert_eq!(git.get_parents("main").await?, Vec::<String>::new());
+        assert_eq!(
+            git.get_commit_message("wip/test-user/2024-03-21-17-59-30")
+                .await?,
+            "second\n\nWippy-Version: 1"
+        );
+        assert!(git.get_commit_time("main").await? > 0);
+        assert!(git.branch_exists("main").await?);
+        assert!(!git.branch_exists("missing").await?);
+        assert_eq!(
+            git.get_local_branches().await?,
+            vec!["main", "wip/test-user/2024-03-21-17-59-30"]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_remotes_and_backend_config() -> Result<()> {
+        let (_dir, git) = init_repo()?;
+        git.with_repo(|repo| {
+            repo.remote("origin", "https://example.com/repo.git")?;
+            Ok(())
+        })
+        .unwrap()?;
+        assert_eq!(git.get_remotes().await?, vec!["origin"]);
+
+        git.with_repo(|repo| {
+            assert!(!uses_git_command(repo));
+            repo.config()?.set_str(BACKEND_CONFIG_KEY, "git")?;
+            assert!(uses_git_command(repo));
+            Ok(())
+        })
+        .unwrap()
+    }
+}
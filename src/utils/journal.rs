@@ -0,0 +1,361 @@
+use crate::utils::{read_file, remove_file, write_file, Git};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::fs::OpenOptions;
+use tokio::io::AsyncWriteExt;
+
+/// Name of the journal file inside the git directory
+const JOURNAL_FILE: &str = "wippy-journal.json";
+
+/// How long to wait for another running `save` or `restore` to finish
+const WAIT_FOR_RUNNING: Duration = Duration::from_secs(30);
+
+/// How often to check whether the other run finished
+const WAIT_POLL: Duration = Duration::from_millis(100);
+
+/// A change to the repository that a command has made and may have to undo.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "step", rename_all = "kebab-case")]
+pub enum Step {
+    /// A branch was created
+    CreatedBranch { branch: String },
+    /// A branch was moved from one commit to another, e.g. by `save --update`
+    UpdatedBranch {
+        branch: String,
+        previous: String,
+        commit: String,
+    },
+    /// A branch was pushed to a remote
+    PushedBranch { remote: String, branch: String },
+    /// A branch that was already on a remote was pushed again
+    PushedUpdate {
+        remote: String,
+        branch: String,
+        previous: String,
+    },
+    /// The saved changes are being removed from the working tree
+    ClearedWorkingTree {
+        index_commit: String,
+        wip_commit: String,
+    },
+    /// Local changes were stashed under the given message
+    Stashed { message: String },
+    /// Another branch was checked out
+    SwitchedBranch { from: String },
+    /// Changes are being written to the index and working tree
+    AppliedChanges,
+    /// A local branch was deleted
+    DeletedBranch { branch: String, commit: String },
+}
+
+/// The steps a running `save` or `restore` has completed.
+///
+/// The journal is created in the git directory when the command starts and
+/// removed once it finishes, so only one run at a time can hold it. A journal
+/// left on disk means a run is still going, e.g. a snapshot of `watch`, or was
+/// interrupted and `git wippy recover` can undo it.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Journal {
+    /// The command that wrote the journal
+    pub command: String,
+    /// The process running the command, missing in journals of older versions
+    #[serde(default)]
+    pub pid: Option<u32>,
+    /// Completed steps, oldest first
+    pub steps: Vec<Step>,
+    #[serde(skip)]
+    path: String,
+}
+
+impl Journal {
+    /// Starts a journal for a command.
+    ///
+    /// Waits for a run of another process that is still going, and fails if a
+    /// previous run left a journal behind, so interrupted runs are never
+    /// stacked on top of each other.
+    pub async fn start(git: &impl Git, command: &str) -> Result<Self> {
+        let journal = Self {
+            command: command.to_string(),
+            pid: Some(std::process::id()),
+            steps: Vec::new(),
+            path: journal_path(git).await?,
+        };
+
+        let mut waited = Duration::ZERO;
+        loop {
+            if journal.create().await? {
+                return Ok(journal);
+            }
+            let other = match read_file(&journal.path).await? {
+                // The other run finished in the meantime
+                None => continue,
+                // The other run created it and is about to write it
+                Some(content) if content.is_empty() => None,
+                Some(content) => Some(Self::parse(&journal.path, &content)?),
+            };
+            if let Some(other) = &other {
+                if !other.is_running().await {
+                    if other.steps.is_empty() {
+                        // Nothing was done, so there is nothing to undo either
+                        remove_file(&journal.path).await?;
+                        continue;
+                    }
+                    return Err(anyhow::anyhow!(
+                        "A previous 'git wippy {}' did not finish. Run 'git wippy recover' to undo it first",
+                        other.command
+                    ));
+                }
+            }
+            if waited >= WAIT_FOR_RUNNING {
+                return Err(match other {
+                    Some(other) => other.still_running(),
+                    None => anyhow::anyhow!(
+                        "Another 'git wippy' is still running. Try again once it finished"
+                    ),
+                });
+            }
+            tokio::time::sleep(WAIT_POLL).await;
+            waited += WAIT_POLL;
+        }
+    }
+
+    /// Creates the journal file, unless there is one already.
+    ///
+    /// # Returns
+    /// * `Ok(true)` if the journal was created
+    /// * `Ok(false)` if another run holds the journal
+    async fn create(&self) -> Result<bool> {
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&self.path)
+            .await;
+        let mut file = match file {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => return Ok(false),
+            Err(e) => return Err(e.into()),
+        };
+        file.write_all(serde_json::to_string_pretty(self)?.as_bytes())
+            .await?;
+        file.flush().await?;
+        Ok(true)
+    }
+
+    /// Whether the process that wrote the journal is still running it.
+    pub async fn is_running(&self) -> bool {
+        match self.pid {
+            Some(pid) if pid != std::process::id() => process_exists(pid).await,
+            _ => false,
+        }
+    }
+
+    /// The error for a journal whose command is still running.
+    pub fn still_running(&self) -> anyhow::Error {
+        anyhow::anyhow!(
+            "'git wippy {}' is still running in process {}. Try again once it finished",
+            self.command,
+            self.pid.unwrap_or_default()
+        )
+    }
+
+    /// Loads the journal left behind by an interrupted run, if any.
+    pub async fn load(git: &impl Git) -> Result<Option<Self>> {
+        let path = journal_path(git).await?;
+        let Some(content) = read_file(&path).await? else {
+            return Ok(None);
+        };
+        Self::parse(&path, &content).map(Some)
+    }
+
+    fn parse(path: &str, content: &str) -> Result<Self> {
+        let mut journal: Self = serde_json::from_str(content)
+            .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path, e))?;
+        journal.path = path.to_string();
+        Ok(journal)
+    }
+
+    /// Records a completed step and writes the journal to disk.
+    pub async fn record(&mut self, step: Step) -> Result<()> {
+        self.steps.push(step);
+        self.write().await
+    }
+
+    /// Removes the most recent step once it has been undone.
+    pub async fn forget_last(&mut self) -> Result<()> {
+        self.steps.pop();
+        self.write().await
+    }
+
+    /// Removes the journal after the command completed, or was rolled back.
+    pub async fn finish(self) -> Result<()> {
+        remove_file(&self.path).await
+    }
+
+    async fn write(&self) -> Result<()> {
+        write_file(&self.path, &serde_json::to_string_pretty(self)?).await
+    }
+}
+
+async fn journal_path(git: &impl Git) -> Result<String> {
+    Ok(format!("{}/{}", git.git_dir().await?, JOURNAL_FILE))
+}
+
+/// Whether a process with the given id is running.
+async fn process_exists(pid: u32) -> bool {
+    #[cfg(windows)]
+    let mut command = {
+        let mut command = tokio::process::Command::new("tasklist");
+        command.args(["/FI", &format!("PID eq {}", pid), "/NH"]);
+        command
+    };
+    #[cfg(not(windows))]
+    let mut command = {
+        let mut command = tokio::process::Command::new("kill");
+        command.args(["-0", &pid.to_string()]);
+        command
+    };
+    let output = command
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .await;
+    match output {
+        // tasklist succeeds either way, and only lists the process if it exists
+        Ok(output) if cfg!(windows) => {
+            String::from_utf8_lossy(&output.stdout).contains(&format!(" {} ", pid))
+        }
+        Ok(output) => output.status.success(),
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::MockGit;
+    use tempfile::TempDir;
+
+    /// Mocks a git directory in a temporary directory, where the journal goes
+    fn mock_git_dir() -> (MockGit, TempDir) {
+        let git_dir = TempDir::new().unwrap();
+        let path = git_dir.path().to_string_lossy().to_string();
+        let mut mock_git = MockGit::new();
+        mock_git
+            .expect_git_dir()
+            .returning(move || Ok(path.clone()));
+        (mock_git, git_dir)
+    }
+
+    #[tokio::test]
+    async fn test_journal_round_trip() -> Result<()> {
+        let (mock_git, git_dir) = mock_git_dir();
+        let path = git_dir.path().join(JOURNAL_FILE);
+
+        let mut journal = Journal::start(&mock_git, "save").await?;
+        assert!(path.exists());
+
+        // Every recorded step is written straight away
+        journal
+            .record(Step::CreatedBranch {
+                branch: "wip/jane/2024-03-21-17-59-30".to_string(),
+            })
+            .await?;
+        journal
+            .record(Step::PushedBranch {
+                remote: "origin".to_string(),
+                branch: "wip/jane/2024-03-21-17-59-30".to_string(),
+            })
+            .await?;
+        let written = Journal::load(&mock_git).await?.unwrap();
+        assert_eq!(written.command, "save");
+        assert_eq!(written.steps, journal.steps);
+
+        journal.finish().await?;
+        assert!(!path.exists());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_journal_refuses_to_start_over_interrupted_run() {
+        let (mock_git, git_dir) = mock_git_dir();
+        std::fs::write(
+            git_dir.path().join(JOURNAL_FILE),
+            r#"{"command": "restore", "steps": [{"step": "applied-changes"}]}"#,
+        )
+        .unwrap();
+
+        let error = Journal::start(&mock_git, "save").await.unwrap_err();
+        assert!(error.to_string().contains("'git wippy restore'"));
+    }
+
+    #[tokio::test]
+    async fn test_journal_is_held_by_one_run() -> Result<()> {
+        let (mock_git, git_dir) = mock_git_dir();
+        let journal = Journal::start(&mock_git, "save").await?;
+        // A run starting at the same time can't create it again
+        assert!(!journal.create().await?);
+
+        // A run that stopped before doing anything leaves nothing to recover
+        let path = git_dir.path().join(JOURNAL_FILE);
+        let mut other = tokio::process::Command::new("true").spawn()?;
+        let pid = other.id().unwrap();
+        other.wait().await?;
+        std::fs::write(
+            &path,
+            format!(r#"{{"command": "save", "pid": {}, "steps": []}}"#, pid),
+        )?;
+        let journal = Journal::start(&mock_git, "restore").await?;
+        assert_eq!(Journal::load(&mock_git).await?.unwrap().command, "restore");
+        journal.finish().await?;
+        assert!(!path.exists());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_journal_waits_for_running_process() -> Result<()> {
+        let (mock_git, git_dir) = mock_git_dir();
+        let path = git_dir.path().join(JOURNAL_FILE);
+        // Another process, e.g. a `watch` in the background, is in the middle of a save
+        let mut other = tokio::process::Command::new("sleep")
+            .arg("10")
+            .kill_on_drop(true)
+            .spawn()?;
+        std::fs::write(
+            &path,
+            format!(
+                r#"{{"command": "save", "pid": {}, "steps": [{{"step": "created-branch", "branch": "wip/jane/2024-03-21-17-59-30"}}]}}"#,
+                other.id().unwrap()
+            ),
+        )?;
+        let running = Journal::load(&mock_git).await?.unwrap();
+        assert!(running.is_running().await);
+
+        let finish = {
+            let path = path.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_millis(300)).await;
+                std::fs::remove_file(path).unwrap();
+            })
+        };
+        let journal = Journal::start(&mock_git, "restore").await?;
+        assert_eq!(journal.pid, Some(std::process::id()));
+        finish.await?;
+
+        other.kill().await?;
+        other.wait().await?;
+        assert!(!running.is_running().await);
+        Ok(())
+    }
+
+    #[test]
+    fn test_step_serialization() {
+        let step = Step::SwitchedBranch {
+            from: "main".to_string(),
+        };
+        let json = serde_json::to_string(&step).unwrap();
+        assert_eq!(json, r#"{"step":"switched-branch","from":"main"}"#);
+        assert_eq!(serde_json::from_str::<Step>(&json).unwrap(), step);
+    }
+}
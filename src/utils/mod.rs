@@ -1,14 +1,39 @@
+mod branch_template;
 mod color;
 mod formatted_datetime;
+mod fs;
 mod git;
 mod git_username;
+#[cfg(feature = "libgit2")]
+mod in_process_git;
+mod journal;
 mod parse_commit_message;
+mod ref_name;
+mod snapshot;
+mod wip_branch;
+mod wip_remote;
 
+pub use branch_template::{hostname, slugify, BranchFields, BranchTemplate, TEMPLATE_CONFIG_KEY};
 pub use color::{Color, ColorConfig};
-pub use formatted_datetime::formatted_datetime;
+pub use formatted_datetime::{
+    check_formatted_datetime, format_age, formatted_datetime, parse_age, parse_formatted_datetime,
+};
+pub use fs::{copy_file, read_file, remove_file, write_file};
 
 #[cfg(test)]
 pub use git::MockGit;
-pub use git::{Git, GitCommand};
-pub use git_username::git_username_with_git;
-pub use parse_commit_message::parse_commit_message;
+pub use git::{open_git, parent_or_empty_tree, short_commit, Git, GitCommand, Head};
+pub use git_username::{git_username_with_git, username_component};
+#[cfg(feature = "libgit2")]
+pub use in_process_git::InProcessGit;
+pub use journal::{Journal, Step};
+pub use parse_commit_message::{
+    format_commit_message, is_wip_commit_message, parse_commit_message, WipMetadata,
+    METADATA_VERSION,
+};
+pub use ref_name::{check_branch_name, sanitize_component, sanitize_path};
+pub use snapshot::{
+    list_snapshots, parse_snapshot_name, resolve_snapshot, trim_snapshots, Snapshot,
+};
+pub use wip_branch::{parse_wip_refs, retain_wip_commits, WipBranch};
+pub use wip_remote::wip_remote_with_git;
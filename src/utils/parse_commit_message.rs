@@ -1,23 +1,172 @@
-use std::collections::HashSet;
+/// Information extracted from a WIP commit message.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WipMetadata {
+    /// The metadata format version, `0` for messages without trailers
+    pub version: u32,
+    /// The original branch name, empty if the work was saved on a detached HEAD
+    pub source_branch: String,
+    /// The commit HEAD pointed to at save time, `None` on an unborn branch
+    pub source_commit: Option<String>,
+    /// The user supplied message, if any
+    pub message: Option<String>,
+    /// The commit recording the index at save time, if it was saved separately
+    pub index_commit: Option<String>,
+    /// The snapshot this one was added on top of with `save --update`, if any
+    pub previous_snapshot: Option<String>,
+    /// How many older snapshots were dropped from under this one to stay
+    /// within `watch --keep-last`
+    pub dropped_snapshots: usize,
+    /// Files that were staged
+    pub staged_files: Vec<String>,
+    /// Files that were changed but not staged
+    pub changed_files: Vec<String>,
+    /// Untracked files
+    pub untracked_files: Vec<String>,
+}
+
+/// The version of the metadata trailers written by [`format_commit_message`].
+pub const METADATA_VERSION: u32 = 1;
+
+/// The subject line of every WIP commit.
+const SUBJECT: &str = "chore: saving work in progress";
+
+const VERSION_TRAILER: &str = "Wippy-Version";
+const SOURCE_BRANCH_TRAILER: &str = "Wippy-Source-Branch";
+const SOURCE_COMMIT_TRAILER: &str = "Wippy-Source-Commit";
+const MESSAGE_TRAILER: &str = "Wippy-Message";
+const INDEX_TRAILER: &str = "Wippy-Index";
+const PREVIOUS_TRAILER: &str = "Wippy-Previous";
+const DROPPED_TRAILER: &str = "Wippy-Dropped";
+const STAGED_TRAILER: &str = "Wippy-Staged";
+const CHANGED_TRAILER: &str = "Wippy-Changed";
+const UNTRACKED_TRAILER: &str = "Wippy-Untracked";
+
+/// Formats WIP metadata as a commit message with git trailers.
+///
+/// Every value is stored as its own `Key: value` trailer in the last paragraph
+/// of the message, so file paths can never be mistaken for section headers and
+/// the order of the files is preserved. Paths that don't fit on a trailer line
+/// as they are, e.g. ones containing a newline, are quoted the way git quotes
+/// them, see [`quote_path`].
+///
+/// # Format
+/// ```text
+/// chore: saving work in progress
+///
+/// Wippy-Version: 1
+/// Wippy-Source-Branch: main
+/// Wippy-Source-Commit: 9a44f15083ae29725508c6c27b6f368d91324cc0
+/// Wippy-Message: Feature work in progress
+/// Wippy-Index: 1f0e3c2b9a8d7e6f5a4b3c2d1e0f9a8b7c6d5e4f
+/// Wippy-Previous: 5d4c3b2a1f0e9d8c7b6a5f4e3d2c1b0a9f8e7d6c
+/// Wippy-Dropped: 3
+/// Wippy-Staged: file1.txt
+/// Wippy-Changed: file2.txt
+/// Wippy-Untracked: file3.txt
+/// ```
+pub fn format_commit_message(metadata: &WipMetadata) -> String {
+    let mut trailers = vec![(VERSION_TRAILER, METADATA_VERSION.to_string())];
+    if !metadata.source_branch.is_empty() {
+        trailers.push((SOURCE_BRANCH_TRAILER, metadata.source_branch.clone()));
+    }
+    if let Some(source_commit) = &metadata.source_commit {
+        trailers.push((SOURCE_COMMIT_TRAILER, source_commit.clone()));
+    }
+    if let Some(message) = &metadata.message {
+        trailers.push((MESSAGE_TRAILER, message.clone()));
+    }
+    if let Some(index_commit) = &metadata.index_commit {
+        trailers.push((INDEX_TRAILER, index_commit.clone()));
+    }
+    if let Some(previous) = &metadata.previous_snapshot {
+        trailers.push((PREVIOUS_TRAILER, previous.clone()));
+    }
+    if metadata.dropped_snapshots > 0 {
+        trailers.push((DROPPED_TRAILER, metadata.dropped_snapshots.to_string()));
+    }
+    let files = [
+        (STAGED_TRAILER, &metadata.staged_files),
+        (CHANGED_TRAILER, &metadata.changed_files),
+        (UNTRACKED_TRAILER, &metadata.untracked_files),
+    ];
+    for (key, files) in files {
+        trailers.extend(files.iter().map(|file| (key, quote_path(file))));
+    }
+
+    let trailers: Vec<String> = trailers
+        .into_iter()
+        .map(|(key, value)| format!("{}: {}", key, value))
+        .collect();
+    format!("{}\n\n{}", SUBJECT, trailers.join("\n"))
+}
 
 /// Parses a WIP commit message to extract branch and file information.
 ///
+/// Messages carrying `Wippy-Version` trailers are read from their trailers.
+/// Messages written before the trailers were introduced are parsed from their
+/// free-text sections instead, and report version `0`.
+///
 /// # Arguments
 /// * `message` - The commit message to parse
 ///
 /// # Returns
-/// A tuple containing:
-/// * source_branch: The original branch name
-/// * staged_files: List of files that were staged
-/// * changed_files: List of files that were changed but not staged
-/// * untracked_files: List of untracked files
+/// A [`WipMetadata`] containing the source branch, the optional user message
+/// and the staged, changed and untracked files in the order they were saved.
+pub fn parse_commit_message(message: &str) -> WipMetadata {
+    parse_trailers(message).unwrap_or_else(|| parse_legacy_commit_message(message))
+}
+
+/// Whether a commit message was written by `save`, with metadata trailers or in
+/// the format used before them.
+pub fn is_wip_commit_message(message: &str) -> bool {
+    parse_trailers(message).is_some() || message.lines().next() == Some(SUBJECT)
+}
+
+/// Parses the metadata trailers in the last paragraph of a commit message.
+///
+/// Returns `None` if the message has no `Wippy-Version` trailer. Unknown
+/// trailers are ignored so newer versions can add fields.
+fn parse_trailers(message: &str) -> Option<WipMetadata> {
+    let last_paragraph = message.trim_end().rsplit("\n\n").next()?;
+    let trailers: Vec<(&str, &str)> = last_paragraph
+        .lines()
+        .filter_map(|line| line.trim_end_matches('\r').split_once(": "))
+        .collect();
+
+    let version = trailers
+        .iter()
+        .find(|(key, _)| *key == VERSION_TRAILER)
+        .and_then(|(_, value)| value.trim().parse().ok())?;
+
+    let mut metadata = WipMetadata {
+        version,
+        ..Default::default()
+    };
+    for (key, value) in trailers {
+        match key {
+            SOURCE_BRANCH_TRAILER => metadata.source_branch = source_branch(value),
+            SOURCE_COMMIT_TRAILER => metadata.source_commit = Some(value.to_string()),
+            MESSAGE_TRAILER => metadata.message = Some(value.to_string()),
+            INDEX_TRAILER => metadata.index_commit = Some(value.to_string()),
+            PREVIOUS_TRAILER => metadata.previous_snapshot = Some(value.to_string()),
+            DROPPED_TRAILER => metadata.dropped_snapshots = value.trim().parse().unwrap_or(0),
+            STAGED_TRAILER => metadata.staged_files.push(unquote_path(value)),
+            CHANGED_TRAILER => metadata.changed_files.push(unquote_path(value)),
+            UNTRACKED_TRAILER => metadata.untracked_files.push(unquote_path(value)),
+            _ => {}
+        }
+    }
+    Some(metadata)
+}
+
+/// Parses the free-text format used before metadata trailers were introduced.
 ///
 /// # Format
-/// Expected commit message format:
 /// ```text
 /// chore: saving work in progress
 ///
 /// Source branch: main
+/// Message: Feature work in progress
 /// Staged changes:
 ///     file1.txt
 ///     file2.txt
@@ -26,22 +175,23 @@ use std::collections::HashSet;
 /// Untracked:
 ///     file4.txt
 /// ```
-pub fn parse_commit_message(message: &str) -> (String, Vec<String>, Vec<String>, Vec<String>) {
-    let mut source_branch = String::new();
-    let mut staged_files = HashSet::new();
-    let mut changed_files = HashSet::new();
-    let mut untracked_files = HashSet::new();
-
+fn parse_legacy_commit_message(message: &str) -> WipMetadata {
+    let mut metadata = WipMetadata::default();
     let mut current_section = None;
 
     for line in message.lines() {
         let trimmed = line.trim();
 
         if trimmed.starts_with("Source branch:") {
-            source_branch = trimmed
-                .trim_start_matches("Source branch:")
-                .trim()
-                .to_string();
+            metadata.source_branch = source_branch(trimmed.trim_start_matches("Source branch:"));
+            continue;
+        }
+
+        if current_section.is_none() && trimmed.starts_with("Message:") {
+            let value = trimmed.trim_start_matches("Message:").trim();
+            if !value.is_empty() {
+                metadata.message = Some(value.to_string());
+            }
             continue;
         }
 
@@ -62,23 +212,111 @@ pub fn parse_commit_message(message: &str) -> (String, Vec<String>, Vec<String>,
             _ => {}
         }
 
-        if let Some(section) = current_section {
-            let file = trimmed.to_string();
-            match section {
-                "staged" => staged_files.insert(file),
-                "changed" => changed_files.insert(file),
-                "untracked" => untracked_files.insert(file),
-                _ => false,
-            };
+        let files = match current_section {
+            Some("staged") => &mut metadata.staged_files,
+            Some("changed") => &mut metadata.changed_files,
+            Some("untracked") => &mut metadata.untracked_files,
+            _ => continue,
+        };
+        let file = unquote_path(trimmed);
+        if !files.contains(&file) {
+            files.push(file);
+        }
+    }
+
+    metadata
+}
+
+/// Writes a path so it fits on a single trailer line and reads back unchanged.
+///
+/// Paths containing control characters such as newlines, starting or ending
+/// with whitespace, or starting with a double quote are written in double
+/// quotes with git's C-style escapes. Any other path, non-ASCII ones included,
+/// is written as it is.
+fn quote_path(path: &str) -> String {
+    let needs_quotes =
+        path.starts_with('"') || path.trim() != path || path.chars().any(|c| c.is_control());
+    if !needs_quotes {
+        return path.to_string();
+    }
+
+    let mut quoted = String::from("\"");
+    for c in path.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            c if c.is_ascii_control() => quoted.push_str(&format!("\\{:03o}", c as u8)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Reads a path written by [`quote_path`].
+///
+/// Also decodes the octal escapes git uses for non-ASCII bytes, which older
+/// versions copied into the metadata from `git diff --name-only`, e.g.
+/// `"\303\244.txt"` for `ä.txt`.
+fn unquote_path(value: &str) -> String {
+    let Some(inner) = value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+    else {
+        return value.to_string();
+    };
+
+    let mut bytes = Vec::with_capacity(inner.len());
+    let mut chars = inner.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buffer = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+            continue;
+        }
+        match chars.next() {
+            Some('a') => bytes.push(0x07),
+            Some('b') => bytes.push(0x08),
+            Some('t') => bytes.push(b'\t'),
+            Some('n') => bytes.push(b'\n'),
+            Some('v') => bytes.push(0x0b),
+            Some('f') => bytes.push(0x0c),
+            Some('r') => bytes.push(b'\r'),
+            Some(digit @ '0'..='7') => {
+                let mut byte = digit as u32 - '0' as u32;
+                for _ in 0..2 {
+                    match chars.peek() {
+                        Some(digit @ '0'..='7') => {
+                            byte = byte * 8 + (*digit as u32 - '0' as u32);
+                            chars.next();
+                        }
+                        _ => break,
+                    }
+                }
+                bytes.push(byte as u8);
+            }
+            Some(c) => {
+                let mut buffer = [0; 4];
+                bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+            }
+            None => bytes.push(b'\\'),
         }
     }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
 
-    (
-        source_branch,
-        staged_files.into_iter().collect(),
-        changed_files.into_iter().collect(),
-        untracked_files.into_iter().collect(),
-    )
+/// Reads a saved source branch name.
+///
+/// Older versions recorded `HEAD` when saving on a detached checkout. That can
+/// never be a real branch, so it is read as no branch at all.
+fn source_branch(value: &str) -> String {
+    match value.trim() {
+        "HEAD" => String::new(),
+        branch => branch.to_string(),
+    }
 }
 
 #[cfg(test)]
@@ -101,23 +339,41 @@ Untracked:
     untracked1.txt
     untracked2.txt"#;
 
-        let (branch, staged, changed, untracked) = parse_commit_message(message);
+        let metadata = parse_commit_message(message);
+
+        assert_eq!(metadata.source_branch, "main");
+        assert_eq!(metadata.message, None);
+        assert_eq!(metadata.staged_files.len(), 2);
+        assert_eq!(metadata.changed_files.len(), 2);
+        assert_eq!(metadata.untracked_files.len(), 2);
+    }
+
+    /// Tests parsing a commit message that carries a user message
+    #[test]
+    fn test_parse_commit_message_with_message() {
+        let message = r#"chore: saving work in progress
+
+Source branch: feature/login
+Message: Feature work in progress
+Changes:
+    src/login.rs"#;
+
+        let metadata = parse_commit_message(message);
 
-        assert_eq!(branch, "main");
-        assert_eq!(staged.len(), 2);
-        assert_eq!(changed.len(), 2);
-        assert_eq!(untracked.len(), 2);
+        assert_eq!(metadata.source_branch, "feature/login");
+        assert_eq!(
+            metadata.message.as_deref(),
+            Some("Feature work in progress")
+        );
+        assert_eq!(metadata.changed_files, vec!["src/login.rs".to_string()]);
     }
 
     /// Tests parsing an empty commit message
     #[test]
     fn test_empty_message() {
-        let (branch, staged, changed, untracked) = parse_commit_message("");
+        let metadata = parse_commit_message("");
 
-        assert!(branch.is_empty());
-        assert!(staged.is_empty());
-        assert!(changed.is_empty());
-        assert!(untracked.is_empty());
+        assert_eq!(metadata, WipMetadata::default());
     }
 
     /// Tests parsing a malformed commit message
@@ -125,11 +381,127 @@ Untracked:
     fn test_malformed_message() {
         let message = "Some random text\nwithout any expected sections";
 
-        let (branch, staged, changed, untracked) = parse_commit_message(message);
+        let metadata = parse_commit_message(message);
+
+        assert_eq!(metadata, WipMetadata::default());
+    }
+
+    /// Tests that formatted metadata parses back to the same values, in order
+    #[test]
+    fn test_trailers_round_trip() {
+        let metadata = WipMetadata {
+            version: METADATA_VERSION,
+            source_branch: "feature/login".to_string(),
+            source_commit: Some("9a44f15083ae29725508c6c27b6f368d91324cc0".to_string()),
+            message: Some("Login form: validation".to_string()),
+            index_commit: Some("1f0e3c2b9a8d7e6f5a4b3c2d1e0f9a8b7c6d5e4f".to_string()),
+            previous_snapshot: Some("5d4c3b2a1f0e9d8c7b6a5f4e3d2c1b0a9f8e7d6c".to_string()),
+            dropped_snapshots: 3,
+            staged_files: vec!["b.txt".to_string(), "a.txt".to_string()],
+            changed_files: vec!["Changes:".to_string(), "Staged changes:".to_string()],
+            untracked_files: vec!["Untracked:".to_string(), "dir/ spaced.txt".to_string()],
+        };
+
+        let message = format_commit_message(&metadata);
+
+        assert!(message.starts_with("chore: saving work in progress\n\n"));
+        assert!(message.contains("\nWippy-Version: 1\n"));
+        assert_eq!(parse_commit_message(&message), metadata);
+    }
+
+    /// Tests that any path survives the trailers, and that paths quoted by
+    /// older versions are read back
+    #[test]
+    fn test_quoted_paths() {
+        let metadata = WipMetadata {
+            version: METADATA_VERSION,
+            staged_files: vec!["\u{e4}.txt".to_string(), "line\nbreak.txt".to_string()],
+            changed_files: vec![" spaced ".to_string(), "\"quoted\\".to_string()],
+            untracked_files: vec!["tab\there\u{7}.txt".to_string(), "back\\slash".to_string()],
+            ..Default::default()
+        };
+
+        let message = format_commit_message(&metadata);
+
+        assert!(message.contains("\nWippy-Staged: \u{e4}.txt\n"));
+        assert!(message.contains("\nWippy-Staged: \"line\\nbreak.txt\"\n"));
+        assert!(message.contains("\nWippy-Untracked: back\\slash"));
+        assert_eq!(parse_commit_message(&message), metadata);
+
+        let legacy =
+            "chore: saving work in progress\n\nWippy-Version: 1\nWippy-Staged: \"\\303\\244.txt\"";
+        assert_eq!(parse_commit_message(legacy).staged_files, ["\u{e4}.txt"]);
+    }
+
+    /// Tests telling WIP commits apart from ordinary ones
+    #[test]
+    fn test_is_wip_commit_message() {
+        let metadata = WipMetadata {
+            version: METADATA_VERSION,
+            ..Default::default()
+        };
+        assert!(is_wip_commit_message(&format_commit_message(&metadata)));
+        assert!(is_wip_commit_message(
+            "chore: saving work in progress\n\nSource branch: main"
+        ));
+        assert!(!is_wip_commit_message("Add login form"));
+        assert!(!is_wip_commit_message(""));
+    }
+
+    /// Tests that a detached HEAD is saved as a commit without a branch
+    #[test]
+    fn test_detached_source() {
+        let metadata = WipMetadata {
+            version: METADATA_VERSION,
+            source_commit: Some("9a44f15083ae29725508c6c27b6f368d91324cc0".to_string()),
+            changed_files: vec!["a.txt".to_string()],
+            ..Default::default()
+        };
+
+        let message = format_commit_message(&metadata);
+
+        assert!(!message.contains("Wippy-Source-Branch"));
+        assert_eq!(parse_commit_message(&message), metadata);
+
+        // Older versions recorded the detached HEAD as a branch called HEAD
+        let legacy = "chore: saving work in progress\n\nSource branch: HEAD\nChanges:\n    a.txt";
+        assert_eq!(parse_commit_message(legacy).source_branch, "");
+    }
+
+    /// Tests that unknown trailers of newer versions are ignored
+    #[test]
+    fn test_trailers_unknown_keys() {
+        let message = "chore: saving work in progress
+
+Wippy-Version: 2
+Wippy-Source-Branch: main
+Wippy-Something-New: value
+Wippy-Changed: a.txt";
+
+        let metadata = parse_commit_message(message);
+
+        assert_eq!(metadata.version, 2);
+        assert_eq!(metadata.source_branch, "main");
+        assert_eq!(metadata.changed_files, vec!["a.txt".to_string()]);
+    }
+
+    /// Tests that legacy messages keep the order of their files
+    #[test]
+    fn test_legacy_message_keeps_order() {
+        let message = "chore: saving work in progress
+
+Source branch: main
+Changes:
+\tz.txt
+\ta.txt
+\tz.txt";
+
+        let metadata = parse_commit_message(message);
 
-        assert!(branch.is_empty());
-        assert!(staged.is_empty());
-        assert!(changed.is_empty());
-        assert!(untracked.is_empty());
+        assert_eq!(metadata.version, 0);
+        assert_eq!(
+            metadata.changed_files,
+            vec!["z.txt".to_string(), "a.txt".to_string()]
+        );
     }
 }
@@ -0,0 +1,128 @@
+/// Characters git never allows in a ref name, besides control characters
+const FORBIDDEN_CHARS: &[char] = &[' ', '~', '^', ':', '?', '*', '[', '\\'];
+
+/// Turns a value into a single branch name component, e.g. `Jane Doe` becomes
+/// `Jane-Doe`.
+///
+/// Only what git rejects in a component is rewritten, so names that were valid
+/// before, like `J.R.` or `O'Neil`, stay the same and existing branches keep
+/// matching. Runs of characters git never allows become a single dash, and `..`,
+/// a leading `.` and a trailing `.lock` are dropped.
+pub fn sanitize_component(value: &str) -> String {
+    let mut component = value
+        .split(|c: char| c == '/' || c.is_ascii_control() || FORBIDDEN_CHARS.contains(&c))
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+        .replace("@{", "@-");
+
+    while component.contains("..") {
+        component = component.replace("..", ".");
+    }
+    loop {
+        let trimmed = component.trim_start_matches('.');
+        let trimmed = trimmed.strip_suffix(".lock").unwrap_or(trimmed);
+        if trimmed.len() == component.len() {
+            return component;
+        }
+        component = trimmed.to_string();
+    }
+}
+
+/// Sanitizes every `/`-separated component of a value that may span several,
+/// such as a source branch. Empty components are dropped.
+pub fn sanitize_path(value: &str) -> String {
+    value
+        .split('/')
+        .map(sanitize_component)
+        .filter(|component| !component.is_empty())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Checks a branch name against git's ref format rules, like
+/// `git check-ref-format --branch` does.
+///
+/// # Returns
+/// * `Ok(())` if git accepts the name
+/// * `Err(String)` - Why the name is invalid
+pub fn check_branch_name(name: &str) -> Result<(), String> {
+    if name.is_empty() {
+        return Err("it is empty".to_string());
+    }
+    if name == "@" {
+        return Err("it is '@'".to_string());
+    }
+    if name.starts_with('-') {
+        return Err("it starts with '-'".to_string());
+    }
+    if let Some(c) = name
+        .chars()
+        .find(|c| c.is_ascii_control() || FORBIDDEN_CHARS.contains(c))
+    {
+        return Err(format!("it contains {:?}", c));
+    }
+    for sequence in ["..", "@{", "//"] {
+        if name.contains(sequence) {
+            return Err(format!("it contains '{}'", sequence));
+        }
+    }
+    if name.ends_with('/') || name.ends_with('.') {
+        return Err("it ends with '/' or '.'".to_string());
+    }
+    for component in name.split('/') {
+        if component.starts_with('.') {
+            return Err(format!("'{}' starts with '.'", component));
+        }
+        if component.ends_with(".lock") {
+            return Err(format!("'{}' ends with '.lock'", component));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_component() {
+        assert_eq!(sanitize_component("test.user"), "test.user");
+        assert_eq!(sanitize_component("Jane Doe"), "Jane-Doe");
+        assert_eq!(sanitize_component("Zoë O'Neil"), "Zoë-O'Neil");
+        // Valid names are left alone, so branches saved by older versions still match
+        assert_eq!(sanitize_component("J.R."), "J.R.");
+        assert_eq!(sanitize_component("-jane-"), "-jane-");
+        assert_eq!(sanitize_component("  .hidden..name.lock "), "hidden.name");
+        assert_eq!(sanitize_component("a/b"), "a-b");
+        assert_eq!(sanitize_component("a@{1}"), "a@-1}");
+        assert_eq!(sanitize_component("???"), "");
+    }
+
+    #[test]
+    fn test_sanitize_path() {
+        assert_eq!(sanitize_path("feature/login form"), "feature/login-form");
+        assert_eq!(sanitize_path("a//.b/"), "a/b");
+    }
+
+    #[test]
+    fn test_check_branch_name() {
+        assert!(check_branch_name("wip/Zoë-O-Neil/2024-03-21-17-59-30").is_ok());
+
+        for (name, reason) in [
+            ("", "empty"),
+            ("-wip", "starts with '-'"),
+            ("wip/Jane Doe", "' '"),
+            ("wip/a:b", "':'"),
+            ("wip/a..b", "'..'"),
+            ("wip/a@{1}", "'@{'"),
+            ("wip//a", "'//'"),
+            ("wip/a.", "ends with"),
+            ("wip/.a", "'.a' starts with '.'"),
+            ("wip/a.lock/b", "'a.lock' ends with '.lock'"),
+        ] {
+            let error = check_branch_name(name).unwrap_err();
+            assert!(error.contains(reason), "{}: {}", name, error);
+        }
+    }
+}
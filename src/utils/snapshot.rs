@@ -0,0 +1,279 @@
+use crate::utils::{format_commit_message, parse_commit_message, Git, WipMetadata};
+use anyhow::Result;
+
+/// A snapshot of a WIP branch, one per `save` or `save --update`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    /// The worktree commit of the snapshot
+    pub commit: String,
+    pub metadata: WipMetadata,
+}
+
+/// Splits a `@{n}` suffix picking an older snapshot off a WIP branch name, e.g.
+/// `wip/jane/2024-03-21-17-59-30@{2}` is the snapshot two saves before the latest.
+///
+/// Branch names can't contain `@{`, so the suffix is never part of the name.
+///
+/// # Returns
+/// * `Ok((name, n))` - The branch name, and `0` for the latest snapshot if
+///   there is no suffix
+pub fn parse_snapshot_name(name: &str) -> Result<(String, usize)> {
+    let Some((branch, suffix)) = name.split_once("@{") else {
+        return Ok((name.to_string(), 0));
+    };
+    let index = suffix
+        .strip_suffix('}')
+        .and_then(|index| index.parse().ok())
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "Invalid snapshot '{}', expected the branch followed by @{{n}}, e.g. {}@{{1}}",
+                name,
+                branch
+            )
+        })?;
+    Ok((branch.to_string(), index))
+}
+
+/// Lists the snapshots of a WIP branch, newest first.
+///
+/// Each snapshot names the one before it in its `Wippy-Previous` trailer, the
+/// first one of a branch has none.
+pub async fn list_snapshots(git: &impl Git, reference: &str) -> Result<Vec<Snapshot>> {
+    let mut snapshots = Vec::new();
+    let mut commit = Some(git.rev_parse(reference).await?);
+    while let Some(current) = commit {
+        let metadata = parse_commit_message(&git.get_commit_message(&current).await?);
+        commit = metadata.previous_snapshot.clone();
+        snapshots.push(Snapshot {
+            commit: current,
+            metadata,
+        });
+    }
+    Ok(snapshots)
+}
+
+/// Finds the commit of the snapshot `index` saves before the latest one.
+///
+/// The latest snapshot is read through `reference` itself.
+pub async fn resolve_snapshot(git: &impl Git, reference: &str, index: usize) -> Result<String> {
+    let mut commit = reference.to_string();
+    for step in 0..index {
+        let metadata = parse_commit_message(&git.get_commit_message(&commit).await?);
+        commit = metadata.previous_snapshot.ok_or_else(|| {
+            anyhow::anyhow!(
+                "'{}' has no snapshot @{{{}}}, it only has {}",
+                reference,
+                index,
+                step + 1
+            )
+        })?;
+    }
+    Ok(commit)
+}
+
+/// Drops all but the newest `keep` snapshots of a local WIP branch.
+///
+/// The kept snapshots are committed again without the dropped ones, keeping
+/// their trees, index commits and dates, and the branch is moved to the new
+/// latest snapshot. The oldest kept snapshot counts the dropped ones in its
+/// `Wippy-Dropped` trailer, so a branch trimmed down to a single snapshot still
+/// reads as updated. A copy of the branch on a remote is left alone.
+///
+/// # Arguments
+/// * `branch` - The name of the local WIP branch
+/// * `keep` - How many snapshots to keep, at least one
+///
+/// # Returns
+/// * `Ok(usize)` - How many snapshots were dropped
+pub async fn trim_snapshots(git: &impl Git, branch: &str, keep: usize) -> Result<usize> {
+    let reference = format!("refs/heads/{}", branch);
+    anyhow::ensure!(
+        keep > 0,
+        "At least one snapshot of '{}' has to be kept",
+        branch
+    );
+    let snapshots = list_snapshots(git, &reference).await?;
+    if snapshots.len() <= keep {
+        return Ok(0);
+    }
+    let dropped: usize = snapshots[keep..]
+        .iter()
+        .map(|snapshot| 1 + snapshot.metadata.dropped_snapshots)
+        .sum();
+
+    let mut previous: Option<String> = None;
+    for Snapshot { commit, metadata } in snapshots[..keep].iter().rev() {
+        let tree = git.rev_parse(&format!("{}^{{tree}}", commit)).await?;
+        let index_commit = match &metadata.index_commit {
+            Some(index_commit) => index_commit.clone(),
+            None => git.rev_parse(&format!("{}^1", commit)).await?,
+        };
+        let metadata = WipMetadata {
+            dropped_snapshots: match previous {
+                Some(_) => metadata.dropped_snapshots,
+                None => dropped,
+            },
+            previous_snapshot: previous.clone(),
+            ..metadata.clone()
+        };
+        let mut parents = vec![index_commit];
+        parents.extend(previous);
+        let time = git.get_commit_time(commit).await?;
+        previous = Some(
+            git.commit_tree_at(&tree, &parents, &format_commit_message(&metadata), time)
+                .await?,
+        );
+    }
+
+    // Only move the branch if nothing saved a snapshot in the meantime
+    if let Some(latest) = previous {
+        git.update_ref(&reference, &latest, &snapshots[0].commit)
+            .await?;
+    }
+    Ok(snapshots.len() - keep)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::MockGit;
+    use mockall::predicate::eq;
+
+    /// Mocks a WIP branch with three snapshots, `c` on top of `b` on top of `a`
+    fn mock_snapshots() -> MockGit {
+        let mut mock_git = MockGit::new();
+        mock_git
+            .expect_rev_parse()
+            .with(eq("wip/jane/2024-03-21-17-59-30"))
+            .returning(|_| Ok("c".to_string()));
+        for (reference, previous) in [
+            ("wip/jane/2024-03-21-17-59-30", Some("b")),
+            ("c", Some("b")),
+            ("b", Some("a")),
+            ("a", None),
+        ] {
+            let mut message = "chore: saving work in progress\n\nWippy-Version: 1".to_string();
+            if let Some(previous) = previous {
+                message.push_str(&format!("\nWippy-Previous: {}", previous));
+            }
+            mock_git
+                .expect_get_commit_message()
+                .with(eq(reference))
+                .returning(move |_| Ok(message.clone()));
+        }
+        mock_git
+    }
+
+    #[test]
+    fn test_parse_snapshot_name() {
+        assert_eq!(
+            parse_snapshot_name("wip/jane/2024-03-21-17-59-30").unwrap(),
+            ("wip/jane/2024-03-21-17-59-30".to_string(), 0)
+        );
+        assert_eq!(
+            parse_snapshot_name("wip/jane/2024-03-21-17-59-30@{2}").unwrap(),
+            ("wip/jane/2024-03-21-17-59-30".to_string(), 2)
+        );
+        for name in [
+            "wip/jane@{",
+            "wip/jane@{x}",
+            "wip/jane@{-1}",
+            "wip/jane@{1}x",
+        ] {
+            let error = parse_snapshot_name(name).unwrap_err();
+            assert!(error.to_string().contains(name), "{}", error);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_list_snapshots() -> Result<()> {
+        let snapshots = list_snapshots(&mock_snapshots(), "wip/jane/2024-03-21-17-59-30").await?;
+        let commits: Vec<&str> = snapshots.iter().map(|s| s.commit.as_str()).collect();
+        assert_eq!(commits, ["c", "b", "a"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_resolve_snapshot() -> Result<()> {
+        let mock_git = mock_snapshots();
+        let branch = "wip/jane/2024-03-21-17-59-30";
+        assert_eq!(resolve_snapshot(&mock_git, branch, 0).await?, branch);
+        assert_eq!(resolve_snapshot(&mock_git, branch, 1).await?, "b");
+        assert_eq!(resolve_snapshot(&mock_git, branch, 2).await?, "a");
+
+        let error = resolve_snapshot(&mock_git, branch, 3).await.unwrap_err();
+        assert!(error.to_string().contains("it only has 3"), "{}", error);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_trim_snapshots() -> Result<()> {
+        let mut mock_git = MockGit::new();
+        let reference = "refs/heads/wip/jane/2024-03-21-17-59-30";
+        mock_git
+            .expect_rev_parse()
+            .with(eq(reference))
+            .returning(|_| Ok("c".to_string()));
+        for (commit, previous) in [("c", Some("b")), ("b", Some("a")), ("a", None)] {
+            let mut message = format!(
+                "chore: saving work in progress\n\nWippy-Version: 1\nWippy-Index: {}-index",
+                commit
+            );
+            match previous {
+                Some(previous) => message.push_str(&format!("\nWippy-Previous: {}", previous)),
+                // Trimmed before
+                None => message.push_str("\nWippy-Dropped: 2"),
+            }
+            mock_git
+                .expect_get_commit_message()
+                .with(eq(commit))
+                .returning(move |_| Ok(message.clone()));
+        }
+        for (commit, time) in [("c", 300), ("b", 200)] {
+            mock_git
+                .expect_rev_parse()
+                .with(eq(format!("{}^{{tree}}", commit)))
+                .returning(move |_| Ok(format!("{}-tree", commit)));
+            mock_git
+                .expect_get_commit_time()
+                .with(eq(commit))
+                .returning(move |_| Ok(time));
+        }
+
+        // The oldest kept snapshot loses its link to the dropped one, and
+        // counts it with the ones dropped before
+        mock_git
+            .expect_commit_tree_at()
+            .withf(|tree, parents, message, time| {
+                tree == "b-tree"
+                    && parents == ["b-index"]
+                    && !message.contains("Wippy-Previous")
+                    && message.contains("Wippy-Dropped: 3")
+                    && *time == 200
+            })
+            .times(1)
+            .returning(|_, _, _, _| Ok("new-b".to_string()));
+        mock_git
+            .expect_commit_tree_at()
+            .withf(|tree, parents, message, time| {
+                tree == "c-tree"
+                    && parents == ["c-index", "new-b"]
+                    && message.contains("Wippy-Previous: new-b")
+                    && !message.contains("Wippy-Dropped")
+                    && *time == 300
+            })
+            .times(1)
+            .returning(|_, _, _, _| Ok("new-c".to_string()));
+        mock_git
+            .expect_update_ref()
+            .with(eq(reference), eq("new-c"), eq("c"))
+            .times(1)
+            .returning(|_, _, _| Ok(String::new()));
+
+        let branch = "wip/jane/2024-03-21-17-59-30";
+        assert_eq!(trim_snapshots(&mock_git, branch, 3).await?, 0);
+        assert_eq!(trim_snapshots(&mock_git, branch, 2).await?, 1);
+        assert!(trim_snapshots(&mock_git, branch, 0).await.is_err());
+        Ok(())
+    }
+}
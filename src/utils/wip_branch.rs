@@ -0,0 +1,243 @@
+use crate::utils::{is_wip_commit_message, BranchTemplate, Git};
+use std::collections::BTreeMap;
+
+/// A WIP branch found locally and/or on one or more remotes.
+///
+/// WIP branches are named after the branch template, `wip/{user}/{date}` by
+/// default. The same branch may exist locally, on a remote, or both, so listings
+/// are keyed by the branch name with the remote prefix stripped.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WipBranch {
+    /// Branch name without any remote prefix, e.g. `wip/jane/2024-03-21-17-59-30`
+    pub name: String,
+    /// The `{user}` part of the branch name
+    pub user: String,
+    /// The `{date}` part of the branch name
+    pub timestamp: String,
+    /// Whether the branch exists locally
+    pub local: bool,
+    /// Remotes the branch exists on
+    pub remotes: Vec<String>,
+}
+
+impl WipBranch {
+    /// Returns a ref that can be used to read the branch, preferring the local copy.
+    pub fn reference(&self) -> String {
+        match (self.local, self.remotes.first()) {
+            (false, Some(remote)) => format!("{}/{}", remote, self.name),
+            _ => self.name.clone(),
+        }
+    }
+}
+
+/// Parses the output of `git for-each-ref --format='%(refname) %(subject)'`
+/// into WIP branches.
+///
+/// Only branches named after `template` are kept, and branches older versions
+/// saved with a free-form `--datetime` whose tip is a WIP commit. Local
+/// (`refs/heads/`) and remote (`refs/remotes/{remote}/`) copies of the same
+/// branch are merged into a single entry. The result is sorted by branch name.
+///
+/// Remote names may contain slashes, e.g. `team/me`, so remote refs are matched
+/// against the configured `remotes`, longest first. Refs of other remotes are
+/// skipped.
+pub fn parse_wip_refs(refs: &str, remotes: &[String], template: &BranchTemplate) -> Vec<WipBranch> {
+    let mut branches: BTreeMap<String, WipBranch> = BTreeMap::new();
+
+    for line in refs.lines().map(str::trim) {
+        let (refname, subject) = line.split_once(' ').unwrap_or((line, ""));
+        let (name, remote) = if let Some(name) = refname.strip_prefix("refs/heads/") {
+            (name, None)
+        } else if let Some(rest) = refname.strip_prefix("refs/remotes/") {
+            let remote = remotes
+                .iter()
+                .filter_map(|remote| {
+                    let name = rest.strip_prefix(remote.as_str())?.strip_prefix('/')?;
+                    Some((name, remote.as_str()))
+                })
+                .max_by_key(|(_, remote)| remote.len());
+            match remote {
+                Some((name, remote)) => (name, Some(remote)),
+                None => continue,
+            }
+        } else {
+            continue;
+        };
+
+        let fields = template.match_name(name).or_else(|| {
+            is_wip_commit_message(subject)
+                .then(|| template.match_legacy_name(name))
+                .flatten()
+        });
+        let Some(fields) = fields else {
+            continue;
+        };
+
+        let branch = branches
+            .entry(name.to_string())
+            .or_insert_with(|| WipBranch {
+                name: name.to_string(),
+                user: fields.user,
+                timestamp: fields.date,
+                local: false,
+                remotes: Vec::new(),
+            });
+
+        match remote {
+            Some(remote) => branch.remotes.push(remote.to_string()),
+            None => branch.local = true,
+        }
+    }
+
+    branches.into_values().collect()
+}
+
+/// Keeps the branches whose tip is a commit `save` wrote.
+///
+/// A name matching the template isn't enough to delete a branch, so `delete`
+/// and `prune` check the commit too. Branches that can't be read are dropped.
+pub async fn retain_wip_commits(git: &impl Git, branches: Vec<WipBranch>) -> Vec<WipBranch> {
+    let mut wip_branches = Vec::with_capacity(branches.len());
+    for branch in branches {
+        let is_wip = git
+            .get_commit_message(&branch.reference())
+            .await
+            .is_ok_and(|message| is_wip_commit_message(&message));
+        if is_wip {
+            wip_branches.push(branch);
+        }
+    }
+    wip_branches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::MockGit;
+    use mockall::predicate::eq;
+
+    fn remotes(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_wip_refs_merges_local_and_remote() {
+        let refs = "refs/heads/main
+refs/heads/wip/jane/2024-03-21-17-59-30
+refs/remotes/origin/HEAD
+refs/remotes/origin/main
+refs/remotes/origin/wip/jane/2024-03-21-17-59-30
+refs/remotes/origin/wip/john/2024-03-20-09-00-00";
+
+        let branches = parse_wip_refs(refs, &remotes(&["origin"]), &BranchTemplate::default());
+
+        assert_eq!(branches.len(), 2);
+        assert_eq!(branches[0].name, "wip/jane/2024-03-21-17-59-30");
+        assert_eq!(branches[0].user, "jane");
+        assert!(branches[0].local);
+        assert_eq!(branches[0].remotes, vec!["origin".to_string()]);
+        assert_eq!(branches[0].reference(), "wip/jane/2024-03-21-17-59-30");
+
+        assert_eq!(branches[1].user, "john");
+        assert_eq!(branches[1].timestamp, "2024-03-20-09-00-00");
+        assert!(!branches[1].local);
+        assert_eq!(
+            branches[1].reference(),
+            "origin/wip/john/2024-03-20-09-00-00"
+        );
+    }
+
+    #[test]
+    fn test_parse_wip_refs_custom_template() {
+        let refs = "refs/heads/wip/jane/2024-03-21-17-59-30
+refs/heads/mine/jane/feature/login/2024-03-21-17-59-30
+refs/remotes/me/mine/jane/main/2024-03-20-09-00-00";
+        let template = BranchTemplate::parse("mine/{user}/{source}/{date}").unwrap();
+
+        let branches = parse_wip_refs(refs, &remotes(&["me"]), &template);
+
+        // Branches named after the default template are not recognised
+        assert_eq!(branches.len(), 2);
+        assert_eq!(
+            branches[0].name,
+            "mine/jane/feature/login/2024-03-21-17-59-30"
+        );
+        assert_eq!(branches[0].user, "jane");
+        assert_eq!(branches[0].timestamp, "2024-03-21-17-59-30");
+        assert_eq!(branches[1].remotes, vec!["me".to_string()]);
+    }
+
+    #[test]
+    fn test_parse_wip_refs_remote_names_with_slashes() {
+        let refs = "refs/remotes/team/wip/jane/2024-03-21-17-59-30
+refs/remotes/team/me/wip/jane/2024-03-21-17-59-30
+refs/remotes/team/me/wip/john/2024-03-20-09-00-00
+refs/remotes/gone/wip/jane/2024-03-19-09-00-00";
+
+        let branches = parse_wip_refs(
+            refs,
+            &remotes(&["team", "team/me"]),
+            &BranchTemplate::default(),
+        );
+
+        // `team/me/wip/...` belongs to `team/me`, not to `team` as `me/wip/...`
+        assert_eq!(branches.len(), 2);
+        assert_eq!(branches[0].name, "wip/jane/2024-03-21-17-59-30");
+        assert_eq!(
+            branches[0].remotes,
+            vec!["team".to_string(), "team/me".to_string()]
+        );
+        assert_eq!(branches[1].user, "john");
+        assert_eq!(branches[1].remotes, vec!["team/me".to_string()]);
+        assert_eq!(
+            branches[1].reference(),
+            "team/me/wip/john/2024-03-20-09-00-00"
+        );
+    }
+
+    #[test]
+    fn test_parse_wip_refs_legacy_names() {
+        let refs = "refs/heads/wip/jane/before-release chore: saving work in progress
+refs/heads/wip/jane/2024-03-21-175930 chore: saving work in progress
+refs/heads/wip/jane/not-mine Release 1.0
+refs/heads/wip/jane/unknown";
+
+        let branches = parse_wip_refs(refs, &[], &BranchTemplate::default());
+
+        // Free-form dates only count when the tip is a WIP commit
+        assert_eq!(branches.len(), 2);
+        assert_eq!(branches[0].name, "wip/jane/2024-03-21-175930");
+        assert_eq!(branches[0].timestamp, "2024-03-21-175930");
+        assert_eq!(branches[1].name, "wip/jane/before-release");
+        assert_eq!(branches[1].user, "jane");
+    }
+
+    #[test]
+    fn test_parse_wip_refs_empty() {
+        assert!(parse_wip_refs("", &[], &BranchTemplate::default()).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_retain_wip_commits() {
+        let mut mock_git = MockGit::new();
+        mock_git
+            .expect_get_commit_message()
+            .with(eq("wip/jane/2024-03-21-17-59-30"))
+            .returning(|_| Ok("chore: saving work in progress\n\nWippy-Version: 1".to_string()));
+        mock_git
+            .expect_get_commit_message()
+            .with(eq("origin/wip/jane/2024-03-20-09-00-00"))
+            .returning(|_| Ok("Release 2024-03-20".to_string()));
+        let branches = parse_wip_refs(
+            "refs/heads/wip/jane/2024-03-21-17-59-30
+refs/remotes/origin/wip/jane/2024-03-20-09-00-00",
+            &remotes(&["origin"]),
+            &BranchTemplate::default(),
+        );
+
+        let kept = retain_wip_commits(&mock_git, branches).await;
+
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].name, "wip/jane/2024-03-21-17-59-30");
+    }
+}
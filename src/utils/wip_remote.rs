@@ -0,0 +1,122 @@
+use crate::utils::Git;
+use anyhow::Result;
+
+/// Git config key that sets the remote WIP branches are pushed to
+pub const REMOTE_CONFIG_KEY: &str = "wippy.remote";
+
+/// The remote used when none is requested or configured
+const DEFAULT_REMOTE: &str = "origin";
+
+/// Picks the remote that WIP branches are pushed to and deleted from.
+///
+/// A remote given with `--remote` wins over the `wippy.remote` git config, which
+/// wins over `origin`. A repository without `origin` but with exactly one remote
+/// uses that remote.
+///
+/// # Arguments
+/// * `requested` - The remote given on the command line, if any
+///
+/// # Returns
+/// * `Ok(Some(String))` - The remote to use
+/// * `Ok(None)` if no remote is requested or configured and none can be picked
+/// * `Err` if the requested or configured remote does not exist
+pub async fn wip_remote_with_git(
+    git: &impl Git,
+    requested: Option<&str>,
+) -> Result<Option<String>> {
+    let remotes = git.get_remotes().await?;
+
+    let chosen = match requested {
+        Some(remote) => Some((remote.to_string(), "--remote")),
+        None => git
+            .get_config_value(REMOTE_CONFIG_KEY)
+            .await?
+            .filter(|remote| !remote.is_empty())
+            .map(|remote| (remote, REMOTE_CONFIG_KEY)),
+    };
+
+    if let Some((remote, source)) = chosen {
+        if !remotes.contains(&remote) {
+            anyhow::bail!("Remote '{}' from {} does not exist", remote, source);
+        }
+        return Ok(Some(remote));
+    }
+
+    if remotes.iter().any(|remote| remote == DEFAULT_REMOTE) {
+        return Ok(Some(DEFAULT_REMOTE.to_string()));
+    }
+    match remotes.as_slice() {
+        [only] => Ok(Some(only.clone())),
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::MockGit;
+    use mockall::predicate::eq;
+
+    fn mock_remotes(remotes: &[&str], configured: Option<&str>) -> MockGit {
+        let remotes: Vec<String> = remotes.iter().map(|r| r.to_string()).collect();
+        let configured = configured.map(String::from);
+        let mut mock_git = MockGit::new();
+        mock_git
+            .expect_get_remotes()
+            .returning(move || Ok(remotes.clone()));
+        mock_git
+            .expect_get_config_value()
+            .with(eq(REMOTE_CONFIG_KEY))
+            .returning(move |_| Ok(configured.clone()));
+        mock_git
+    }
+
+    #[tokio::test]
+    async fn test_requested_remote_wins() -> Result<()> {
+        let mock_git = mock_remotes(&["origin", "me"], Some("origin"));
+        let remote = wip_remote_with_git(&mock_git, Some("me")).await?;
+        assert_eq!(remote.as_deref(), Some("me"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_configured_remote() -> Result<()> {
+        let mock_git = mock_remotes(&["origin", "me"], Some("me"));
+        let remote = wip_remote_with_git(&mock_git, None).await?;
+        assert_eq!(remote.as_deref(), Some("me"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_default_remote() -> Result<()> {
+        let mock_git = mock_remotes(&["me", "origin"], None);
+        assert_eq!(
+            wip_remote_with_git(&mock_git, None).await?.as_deref(),
+            Some("origin")
+        );
+
+        // Without origin, a single remote is used and several are ambiguous
+        let mock_git = mock_remotes(&["me"], None);
+        assert_eq!(
+            wip_remote_with_git(&mock_git, None).await?.as_deref(),
+            Some("me")
+        );
+        let mock_git = mock_remotes(&["me", "upstream"], None);
+        assert_eq!(wip_remote_with_git(&mock_git, None).await?, None);
+        let mock_git = mock_remotes(&[], None);
+        assert_eq!(wip_remote_with_git(&mock_git, None).await?, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_missing_remote() {
+        let mock_git = mock_remotes(&["origin"], Some("me"));
+        let error = wip_remote_with_git(&mock_git, None).await.unwrap_err();
+        assert!(error.to_string().contains("'me' from wippy.remote"));
+
+        let error = wip_remote_with_git(&mock_git, Some("fork"))
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("'fork' from --remote"));
+    }
+}
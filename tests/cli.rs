@@ -2,7 +2,8 @@ mod common;
 
 use assert_cmd::Command;
 use common::{
-    get_wip_branch_name, normalize_text, setup_git_repo, setup_git_repo_with_remote, t_with_args,
+    get_wip_branch_name, git, normalize_text, setup_git_repo, setup_git_repo_with_remote, stdout,
+    t_with_args, wippy, wippy_in_locale,
 };
 use predicates::function::function;
 use std::fs;
@@ -32,7 +33,7 @@ async fn test_save_and_list() {
             }))
             .stdout(function(|output: &str| {
                 normalize_text(output).contains(&normalize_text(&t_with_args(
-                    "staged-all-changes",
+                    "recorded-changes",
                     &[],
                     locale,
                 )))
@@ -321,6 +322,590 @@ async fn test_restore_wip() {
     }
 }
 
+#[tokio::test]
+async fn test_restore_wip_partially_staged() {
+    let temp_dir = setup_git_repo();
+
+    fs::write(temp_dir.path().join("notes.txt"), "one\ntwo\nthree\n").unwrap();
+    git(&temp_dir, &["add", "notes.txt"]);
+    git(&temp_dir, &["commit", "-m", "Add notes"]);
+
+    // Stage a change to the first line only, then change the last line as well
+    fs::write(temp_dir.path().join("notes.txt"), "ONE\ntwo\nthree\n").unwrap();
+    git(&temp_dir, &["add", "notes.txt"]);
+    fs::write(temp_dir.path().join("notes.txt"), "ONE\ntwo\nTHREE\n").unwrap();
+    fs::write(temp_dir.path().join("untracked.txt"), "new untracked").unwrap();
+
+    let staged_before = git(&temp_dir, &["diff", "--cached"]);
+    let unstaged_before = git(&temp_dir, &["diff"]);
+
+    let mut cmd = Command::cargo_bin("git-wippy").unwrap();
+    cmd.current_dir(&temp_dir)
+        .args(["save", "--local"])
+        .assert()
+        .success();
+    assert!(git(&temp_dir, &["status", "--porcelain"]).is_empty());
+
+    let branch_name = get_wip_branch_name(&temp_dir);
+    let mut cmd = Command::cargo_bin("git-wippy").unwrap();
+    cmd.current_dir(&temp_dir)
+        .args(["restore", &branch_name])
+        .assert()
+        .success();
+
+    // The staged and unstaged hunks end up exactly where they were
+    assert_eq!(git(&temp_dir, &["diff", "--cached"]), staged_before);
+    assert_eq!(git(&temp_dir, &["diff"]), unstaged_before);
+    assert_eq!(
+        git(&temp_dir, &["status", "--porcelain"]),
+        "MM notes.txt\n?? untracked.txt"
+    );
+}
+
+#[tokio::test]
+async fn test_save_and_restore_detached_head() {
+    for locale in ["en", "fr", "de"] {
+        let temp_dir = setup_git_repo();
+
+        // Work on a detached commit that is not the tip of any branch
+        let detached = git(&temp_dir, &["rev-parse", "HEAD"]);
+        fs::write(temp_dir.path().join("test.txt"), "second").unwrap();
+        git(&temp_dir, &["commit", "-am", "Second commit"]);
+        git(&temp_dir, &["checkout", "--detach", &detached]);
+        fs::write(temp_dir.path().join("test.txt"), "detached work").unwrap();
+
+        let mut cmd = Command::cargo_bin("git-wippy").unwrap();
+        cmd.current_dir(&temp_dir)
+            .env("LANG", locale)
+            .args(["save", "--local"])
+            .assert()
+            .success();
+
+        let branch_name = get_wip_branch_name(&temp_dir);
+        let message = git(&temp_dir, &["log", "-1", "--format=%B", &branch_name]);
+        assert!(message.contains(&format!("Wippy-Source-Commit: {}", detached)));
+        assert!(!message.contains("Wippy-Source-Branch"));
+
+        // Restore from somewhere else puts us back on the exact commit
+        git(&temp_dir, &["checkout", "main"]);
+        let mut cmd = Command::cargo_bin("git-wippy").unwrap();
+        cmd.current_dir(&temp_dir)
+            .env("LANG", locale)
+            .args(["restore", &branch_name])
+            .assert()
+            .success()
+            .stdout(function(|output: &str| {
+                normalize_text(output).contains(&normalize_text(&t_with_args(
+                    "checked-out-commit",
+                    &[("commit", &detached[..7])],
+                    locale,
+                )))
+            }));
+
+        assert_eq!(git(&temp_dir, &["rev-parse", "HEAD"]), detached);
+        assert!(git(&temp_dir, &["branch", "--show-current"]).is_empty());
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("test.txt")).unwrap(),
+            "detached work"
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_save_and_restore_unborn_branch() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    git(&temp_dir, &["init", "--initial-branch=main"]);
+    git(&temp_dir, &["config", "--local", "user.name", "test.user"]);
+    git(
+        &temp_dir,
+        &["config", "--local", "user.email", "test@example.com"],
+    );
+
+    // Nothing has been committed yet
+    fs::write(temp_dir.path().join("staged.txt"), "staged").unwrap();
+    git(&temp_dir, &["add", "staged.txt"]);
+    fs::write(temp_dir.path().join("untracked.txt"), "untracked").unwrap();
+
+    let mut cmd = Command::cargo_bin("git-wippy").unwrap();
+    cmd.current_dir(&temp_dir)
+        .args(["save", "--local"])
+        .assert()
+        .success();
+    assert!(git(&temp_dir, &["status", "--porcelain"]).is_empty());
+    assert!(!temp_dir.path().join("staged.txt").exists());
+
+    let branch_name = get_wip_branch_name(&temp_dir);
+    let mut cmd = Command::cargo_bin("git-wippy").unwrap();
+    cmd.current_dir(&temp_dir)
+        .args(["restore", &branch_name])
+        .assert()
+        .success();
+
+    // Still on the unborn branch, with the files as they were
+    assert_eq!(git(&temp_dir, &["symbolic-ref", "--short", "HEAD"]), "main");
+    assert_eq!(
+        git(&temp_dir, &["status", "--porcelain"]),
+        "A  staged.txt\n?? untracked.txt"
+    );
+}
+
+#[tokio::test]
+async fn test_save_and_restore_in_linked_worktree() {
+    let temp_dir = setup_git_repo();
+    let worktree = temp_dir.path().join("linked");
+    Command::new("git")
+        .current_dir(&temp_dir)
+        .args(["worktree", "add", "-b", "feature", "linked"])
+        .assert()
+        .success();
+
+    fs::write(worktree.join("test.txt"), "worktree change").unwrap();
+    fs::write(worktree.join("new.txt"), "new file").unwrap();
+
+    let mut cmd = Command::cargo_bin("git-wippy").unwrap();
+    cmd.current_dir(&worktree)
+        .args(["save", "--local"])
+        .assert()
+        .success();
+    assert!(git(&worktree, &["status", "--porcelain"]).is_empty());
+
+    // The main worktree is left alone
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("test.txt")).unwrap(),
+        "initial content"
+    );
+
+    let branch_name = get_wip_branch_name(&temp_dir);
+    let message = git(&worktree, &["log", "-1", "--format=%B", &branch_name]);
+    assert!(message.contains("Wippy-Source-Branch: feature"));
+
+    let mut cmd = Command::cargo_bin("git-wippy").unwrap();
+    cmd.current_dir(&worktree)
+        .args(["restore", &branch_name])
+        .assert()
+        .success();
+
+    assert_eq!(
+        git(&worktree, &["symbolic-ref", "--short", "HEAD"]),
+        "feature"
+    );
+    assert_eq!(
+        git(&worktree, &["status", "--porcelain"]),
+        " M test.txt\n?? new.txt"
+    );
+}
+
+#[tokio::test]
+async fn test_restore_keep() {
+    for locale in ["en", "fr", "de"] {
+        let (local_dir, _remote_dir) = setup_git_repo_with_remote();
+        let on_remote = |branch: &str| {
+            git(&local_dir, &["ls-remote", "--heads", "origin", branch]).contains(branch)
+        };
+
+        fs::write(local_dir.path().join("test.txt"), "saved content").unwrap();
+        wippy_in_locale(&local_dir, locale, &["save"]);
+        let branch_name = get_wip_branch_name(&local_dir);
+
+        // --keep applies the changes and leaves the WIP alone everywhere
+        wippy_in_locale(&local_dir, locale, &["restore", "--keep", &branch_name]).stdout(function(
+            |output: &str| {
+                normalize_text(output).contains(&normalize_text(&t_with_args(
+                    "kept-wip-branch",
+                    &[("name", &branch_name)],
+                    locale,
+                )))
+            },
+        ));
+        assert_eq!(
+            fs::read_to_string(local_dir.path().join("test.txt")).unwrap(),
+            "saved content"
+        );
+        assert_eq!(get_wip_branch_name(&local_dir), branch_name);
+        assert!(on_remote(&branch_name));
+
+        // --keep-remote only deletes the local branch
+        git(&local_dir, &["checkout", "--", "test.txt"]);
+        wippy_in_locale(
+            &local_dir,
+            locale,
+            &["restore", "--keep-remote", &branch_name],
+        );
+        assert!(git(&local_dir, &["branch", "--list", "wip/*"]).is_empty());
+        assert!(on_remote(&branch_name));
+
+        // So does wippy.keepRemote
+        git(&local_dir, &["checkout", "--", "test.txt"]);
+        git(
+            &local_dir,
+            &["fetch", "origin", &format!("{0}:{0}", branch_name)],
+        );
+        git(&local_dir, &["config", "wippy.keepRemote", "true"]);
+        wippy_in_locale(&local_dir, locale, &["restore", &branch_name]);
+        assert!(git(&local_dir, &["branch", "--list", "wip/*"]).is_empty());
+        assert!(on_remote(&branch_name));
+
+        // By default the pushed copy goes too, a local-only WIP just has none to delete
+        git(&local_dir, &["checkout", "--", "test.txt"]);
+        git(
+            &local_dir,
+            &["fetch", "origin", &format!("{0}:{0}", branch_name)],
+        );
+        git(&local_dir, &["config", "--unset", "wippy.keepRemote"]);
+        wippy_in_locale(&local_dir, locale, &["restore", &branch_name]);
+        assert!(!on_remote(&branch_name));
+
+        wippy_in_locale(&local_dir, locale, &["save", "--local"]);
+        let local_branch = get_wip_branch_name(&local_dir);
+        wippy_in_locale(&local_dir, locale, &["restore", &local_branch]);
+        assert!(git(&local_dir, &["branch", "--list", "wip/*"]).is_empty());
+    }
+}
+
+#[tokio::test]
+async fn test_restore_remote_only() {
+    for locale in ["en", "fr", "de"] {
+        let (local_dir, _remote_dir) = setup_git_repo_with_remote();
+        let on_remote = |branch: &str| {
+            git(&local_dir, &["ls-remote", "--heads", "origin", branch]).contains(branch)
+        };
+
+        // Saved and pushed on one machine, only the remote copy is known on another
+        fs::write(local_dir.path().join("test.txt"), "saved elsewhere").unwrap();
+        wippy_in_locale(
+            &local_dir,
+            locale,
+            &["save", "--datetime", "2024-03-21-17-59-30"],
+        );
+        let branch_name = get_wip_branch_name(&local_dir);
+        git(&local_dir, &["branch", "-D", &branch_name]);
+        git(&local_dir, &["checkout", "--", "test.txt"]);
+
+        wippy_in_locale(&local_dir, locale, &["show", &branch_name])
+            .stdout(predicates::str::contains("test.txt"));
+        assert!(git(&local_dir, &["branch", "--list", "wip/*"]).is_empty());
+
+        // A WIP that was never fetched is fetched first
+        git(
+            &local_dir,
+            &[
+                "update-ref",
+                "-d",
+                &format!("refs/remotes/origin/{}", branch_name),
+            ],
+        );
+        wippy_in_locale(&local_dir, locale, &["restore", &branch_name]).stdout(function(
+            |output: &str| {
+                normalize_text(output).contains(&normalize_text(&t_with_args(
+                    "fetched-wip-branch",
+                    &[("name", &branch_name), ("remote", "origin")],
+                    locale,
+                )))
+            },
+        ));
+        assert_eq!(
+            fs::read_to_string(local_dir.path().join("test.txt")).unwrap(),
+            "saved elsewhere"
+        );
+        assert!(git(&local_dir, &["branch", "--list", "wip/*"]).is_empty());
+        assert!(!on_remote(&branch_name));
+
+        // Deleting a remote-only WIP deletes the remote copy
+        wippy_in_locale(
+            &local_dir,
+            locale,
+            &["save", "--datetime", "2024-03-21-18-00-00"],
+        );
+        let branch_name = get_wip_branch_name(&local_dir);
+        git(&local_dir, &["branch", "-D", &branch_name]);
+        wippy_in_locale(&local_dir, locale, &["delete", "--force", &branch_name]).stdout(function(
+            |output: &str| {
+                normalize_text(output).contains(&normalize_text(&t_with_args(
+                    "wip-branch-deleted-remote",
+                    &[("name", &branch_name), ("remote", "origin")],
+                    locale,
+                )))
+            },
+        ));
+        assert!(!on_remote(&branch_name));
+
+        // So does deleting one that was never fetched
+        fs::write(local_dir.path().join("test.txt"), "saved again").unwrap();
+        wippy_in_locale(
+            &local_dir,
+            locale,
+            &["save", "--datetime", "2024-03-21-18-01-00"],
+        );
+        let branch_name = get_wip_branch_name(&local_dir);
+        git(&local_dir, &["branch", "-D", &branch_name]);
+        git(
+            &local_dir,
+            &[
+                "update-ref",
+                "-d",
+                &format!("refs/remotes/origin/{}", branch_name),
+            ],
+        );
+        wippy_in_locale(&local_dir, locale, &["delete", "--force", &branch_name]);
+        assert!(!on_remote(&branch_name));
+
+        // A WIP that is found nowhere is an error
+        Command::cargo_bin("git-wippy")
+            .unwrap()
+            .current_dir(&local_dir)
+            .env("LANG", locale)
+            .args(["delete", "--force", &branch_name])
+            .assert()
+            .failure();
+    }
+}
+
+#[tokio::test]
+async fn test_sync() {
+    for locale in ["en", "fr", "de"] {
+        let (local_dir, remote_dir) = setup_git_repo_with_remote();
+        let remote_git = |args: &[&str]| {
+            Command::new("git")
+                .current_dir(&remote_dir)
+                .args(args)
+                .assert()
+                .success();
+        };
+        let save = |date: &str, local: bool| {
+            fs::write(local_dir.path().join("test.txt"), date).unwrap();
+            let mut args = vec!["save", "--datetime", date];
+            if local {
+                args.push("--local");
+            }
+            wippy_in_locale(&local_dir, locale, &args);
+            format!("wip/test.user/{}", date)
+        };
+        let on_remote = |branch: &str| {
+            git(&local_dir, &["ls-remote", "--heads", "origin", branch]).contains(branch)
+        };
+
+        let local_only = save("2024-03-21-17-59-30", true);
+        // Restored on another machine
+        let deleted = save("2024-03-21-18-00-00", false);
+        remote_git(&["branch", "-D", &deleted]);
+        // Saved on another machine
+        let elsewhere = save("2024-03-21-18-01-00", false);
+        git(&local_dir, &["branch", "-D", &elsewhere]);
+        let synced = save("2024-03-21-18-02-00", false);
+
+        let output = Command::cargo_bin("git-wippy")
+            .unwrap()
+            .current_dir(&local_dir)
+            .args(["--format", "json", "sync"])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let result: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(result["remote"], "origin");
+        let statuses: Vec<(String, String)> = result["branches"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|branch| {
+                (
+                    branch["branch"].as_str().unwrap().to_string(),
+                    branch["status"].as_str().unwrap().to_string(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            statuses,
+            [
+                (local_only.clone(), "pushed".to_string()),
+                (deleted.clone(), "pruned".to_string()),
+                (elsewhere.clone(), "remote_only".to_string()),
+                (synced.clone(), "up_to_date".to_string()),
+            ]
+        );
+        assert!(on_remote(&local_only));
+        assert!(git(&local_dir, &["branch", "--list", &deleted]).is_empty());
+        assert!(git(&local_dir, &["branch", "--list", &elsewhere]).is_empty());
+
+        // Once pushed, a WIP is up to date on the next sync
+        wippy_in_locale(&local_dir, locale, &["sync"]).stdout(function(|output: &str| {
+            let output = normalize_text(output);
+            output.contains(&normalize_text(&t_with_args(
+                "sync-fetching",
+                &[("remote", "origin")],
+                locale,
+            ))) && output.contains(&t_with_args("sync-up-to-date", &[], locale))
+                && !output.contains(&t_with_args("sync-pushed", &[], locale))
+        }));
+    }
+}
+
+#[tokio::test]
+async fn test_prune() {
+    for locale in ["en", "fr", "de"] {
+        let (local_dir, _remote_dir) = setup_git_repo_with_remote();
+        let save = |date: &str| {
+            fs::write(local_dir.path().join("test.txt"), date).unwrap();
+            wippy_in_locale(&local_dir, locale, &["save", "--datetime", date]);
+            format!("wip/test.user/{}", date)
+        };
+        let exists = |branch: &str| {
+            !git(&local_dir, &["branch", "--list", branch]).is_empty()
+                || git(&local_dir, &["ls-remote", "--heads", "origin", branch]).contains(branch)
+        };
+
+        let oldest = save("2020-01-01-00-00-00");
+        let old = save("2020-01-02-00-00-00");
+
+        // A dry run only reports what would be pruned
+        let output = Command::cargo_bin("git-wippy")
+            .unwrap()
+            .current_dir(&local_dir)
+            .args([
+                "--format",
+                "json",
+                "prune",
+                "--older-than",
+                "30d",
+                "--dry-run",
+            ])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let result: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        let pruned: Vec<(&str, &str)> = result["branches"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|branch| {
+                (
+                    branch["branch"].as_str().unwrap(),
+                    branch["reason"].as_str().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            pruned,
+            [
+                (old.as_str(), "older_than"),
+                (oldest.as_str(), "older_than")
+            ]
+        );
+        assert!(exists(&oldest) && exists(&old));
+
+        // The newest is kept, the other one is deleted locally and on the remote
+        wippy_in_locale(
+            &local_dir,
+            locale,
+            &[
+                "prune",
+                "--older-than",
+                "30d",
+                "--keep-last",
+                "1",
+                "--force",
+            ],
+        );
+        assert!(!exists(&oldest));
+        assert!(exists(&old));
+
+        // Work saved on a feature branch that was merged since
+        git(&local_dir, &["checkout", "-b", "feature"]);
+        fs::write(local_dir.path().join("feature.txt"), "feature").unwrap();
+        git(&local_dir, &["add", "feature.txt"]);
+        git(&local_dir, &["commit", "-m", "Add feature"]);
+        fs::write(local_dir.path().join("feature.txt"), "more").unwrap();
+        wippy_in_locale(&local_dir, locale, &["save", "--local"]);
+        let feature_wip = git(
+            &local_dir,
+            &["branch", "--list", "--format=%(refname:short)", "wip/*"],
+        )
+        .lines()
+        .find(|branch| *branch != old)
+        .unwrap()
+        .to_string();
+        git(&local_dir, &["checkout", "main"]);
+        git(
+            &local_dir,
+            &["merge", "--no-ff", "-m", "Merge feature", "feature"],
+        );
+
+        wippy_in_locale(&local_dir, locale, &["prune", "--source-merged", "--force"]).stdout(
+            function(|output: &str| {
+                output.contains(&t_with_args("prune-source-merged", &[], locale))
+            }),
+        );
+        assert!(!exists(&feature_wip));
+        assert!(exists(&old));
+
+        wippy_in_locale(
+            &local_dir,
+            locale,
+            &["prune", "--source-merged", "--dry-run"],
+        )
+        .stdout(function(|output: &str| {
+            output.contains(&t_with_args("prune-nothing", &[], locale))
+        }));
+    }
+}
+
+#[tokio::test]
+async fn test_restore_onto() {
+    let temp_dir = setup_git_repo();
+
+    // Save work on a feature branch, which is then renamed
+    git(&temp_dir, &["checkout", "-b", "feature"]);
+    fs::write(temp_dir.path().join("test.txt"), "feature work").unwrap();
+    wippy(&temp_dir, &["save", "--local"]);
+    let branch_name = get_wip_branch_name(&temp_dir);
+    git(&temp_dir, &["branch", "-m", "feature", "feature-v2"]);
+    git(&temp_dir, &["checkout", "main"]);
+
+    // A target that does not exist is refused before anything changes
+    Command::cargo_bin("git-wippy")
+        .unwrap()
+        .current_dir(&temp_dir)
+        .args(["restore", "--onto", "nope", &branch_name])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("'nope' given with --onto"));
+    assert_eq!(get_wip_branch_name(&temp_dir), branch_name);
+
+    wippy(
+        &temp_dir,
+        &["restore", "--onto", "feature-v2", &branch_name],
+    );
+    assert_eq!(
+        git(&temp_dir, &["symbolic-ref", "--short", "HEAD"]),
+        "feature-v2"
+    );
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("test.txt")).unwrap(),
+        "feature work"
+    );
+    assert!(git(&temp_dir, &["branch", "--list", "feature"]).is_empty());
+
+    // Without a terminal to ask on, a missing source branch is recreated
+    fs::write(temp_dir.path().join("test.txt"), "more work").unwrap();
+    wippy(
+        &temp_dir,
+        &["save", "--local", "--datetime", "2024-03-21-17-59-30"],
+    );
+    let branch_name = "wip/test.user/2024-03-21-17-59-30";
+    git(&temp_dir, &["checkout", "main"]);
+    git(&temp_dir, &["branch", "-D", "feature-v2"]);
+    let output = stdout(&wippy(
+        &temp_dir,
+        &["--format", "json", "restore", branch_name],
+    ));
+    let result: serde_json::Value = serde_json::from_str(&output).unwrap();
+    assert_eq!(result["created_source_branch"], true);
+    assert_eq!(result["restored_onto"], "feature-v2");
+    assert_eq!(
+        git(&temp_dir, &["symbolic-ref", "--short", "HEAD"]),
+        "feature-v2"
+    );
+}
+
 #[tokio::test]
 async fn test_restore_wip_with_autostash() {
     for locale in ["en", "fr", "de"] {
@@ -622,71 +1207,630 @@ async fn test_restore_wip_with_autostash_conflicts() {
 }
 
 #[tokio::test]
-async fn test_save_with_remote() {
+async fn test_restore_merges_when_branch_moved() {
     for locale in ["en", "fr", "de"] {
-        let (local_dir, _remote_dir) = setup_git_repo_with_remote();
+        let temp_dir = setup_git_repo();
+        fs::write(temp_dir.path().join("other.txt"), "one\ntwo\nthree\n").unwrap();
+        git(&temp_dir, &["add", "other.txt"]);
+        git(&temp_dir, &["commit", "-m", "Add other.txt"]);
 
-        // Create a change
-        fs::write(local_dir.path().join("test.txt"), "modified content").unwrap();
+        // Save work on two files, then commit unrelated changes to both
+        fs::write(temp_dir.path().join("test.txt"), "wip content").unwrap();
+        fs::write(temp_dir.path().join("other.txt"), "one\ntwo\nthree\nwip\n").unwrap();
+        git(&temp_dir, &["add", "other.txt"]);
+        wippy_in_locale(
+            &temp_dir,
+            locale,
+            &["save", "--local", "--datetime", "2024-03-21-17-59-30"],
+        );
+        let branch_name = "wip/test.user/2024-03-21-17-59-30";
 
-        // Test save command with remote
+        fs::write(temp_dir.path().join("other.txt"), "upstream\ntwo\nthree\n").unwrap();
+        git(&temp_dir, &["commit", "-am", "Upstream change"]);
+
+        // Nothing committed since the save is overwritten, and what was staged is staged again
         let mut cmd = Command::cargo_bin("git-wippy").unwrap();
-        cmd.current_dir(&local_dir)
+        cmd.current_dir(&temp_dir)
             .env("LANG", locale)
-            .arg("save")
+            .args(["restore", "--keep", branch_name])
             .assert()
-            .success()
+            .success();
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("other.txt")).unwrap(),
+            "upstream\ntwo\nthree\nwip\n"
+        );
+        assert_eq!(
+            git(&temp_dir, &["status", "--porcelain"]),
+            "M  other.txt\n M test.txt"
+        );
+
+        // Changes to the same lines leave conflict markers and keep the WIP branch
+        git(&temp_dir, &["reset", "--hard"]);
+        fs::write(temp_dir.path().join("test.txt"), "upstream content").unwrap();
+        git(&temp_dir, &["commit", "-am", "Conflicting change"]);
+        // Like `git stash pop`, the restore fails, after reporting the conflicts
+        let output = Command::cargo_bin("git-wippy")
+            .unwrap()
+            .current_dir(&temp_dir)
+            .env("LANG", locale)
+            .args(["--format", "json", "restore", branch_name])
+            .assert()
+            .code(1)
+            .get_output()
+            .stdout
+            .clone();
+        let result: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(result["conflicts"], serde_json::json!(["test.txt"]));
+        assert_eq!(result["deleted_local"], false);
+
+        let conflicted = fs::read_to_string(temp_dir.path().join("test.txt")).unwrap();
+        assert!(conflicted.contains("<<<<<<< main\nupstream content"));
+        assert!(conflicted.contains(&format!("wip content\n>>>>>>> {}", branch_name)));
+        assert!(git(&temp_dir, &["status", "--porcelain"]).contains("UU test.txt"));
+        assert_eq!(get_wip_branch_name(&temp_dir), branch_name);
+        assert!(!temp_dir.path().join(".git/wippy-journal.json").exists());
+
+        // In text mode the conflicted files are listed
+        git(&temp_dir, &["reset", "--hard"]);
+        let mut cmd = Command::cargo_bin("git-wippy").unwrap();
+        cmd.current_dir(&temp_dir)
+            .env("LANG", locale)
+            .args(["restore", branch_name])
+            .assert()
+            .failure()
             .stdout(function(|output: &str| {
                 normalize_text(output).contains(&normalize_text(&t_with_args(
-                    "saving-wip",
-                    &[],
+                    "restore-conflicts",
+                    &[("count", "1")],
                     locale,
-                )))
-            }))
+                ))) && output.contains("  test.txt")
+            }));
+    }
+}
+
+#[tokio::test]
+async fn test_restore_non_ascii_paths_after_branch_moved() {
+    let temp_dir = setup_git_repo();
+
+    // git quotes these names unless asked not to
+    fs::write(temp_dir.path().join("\u{e4}.txt"), "staged").unwrap();
+    git(&temp_dir, &["add", "\u{e4}.txt"]);
+    fs::write(temp_dir.path().join("test.txt"), "changed").unwrap();
+    fs::write(temp_dir.path().join("\u{65e5}\u{672c}.txt"), "untracked").unwrap();
+    fs::write(temp_dir.path().join("line\nbreak.txt"), "untracked").unwrap();
+    wippy(
+        &temp_dir,
+        &["save", "--local", "--datetime", "2024-03-21-17-59-30"],
+    );
+    let branch_name = "wip/test.user/2024-03-21-17-59-30";
+
+    // Restoring onto a moved branch stages the saved files again by name
+    fs::write(temp_dir.path().join("upstream.txt"), "upstream").unwrap();
+    git(&temp_dir, &["add", "upstream.txt"]);
+    git(&temp_dir, &["commit", "-m", "Upstream change"]);
+    wippy(&temp_dir, &["restore", branch_name]);
+
+    assert_eq!(
+        git(&temp_dir, &["diff", "--cached", "--name-only", "-z"]),
+        "\u{e4}.txt\0"
+    );
+    assert_eq!(git(&temp_dir, &["diff", "--name-only", "-z"]), "test.txt\0");
+    assert_eq!(
+        git(
+            &temp_dir,
+            &["ls-files", "--others", "--exclude-standard", "-z"]
+        ),
+        "line\nbreak.txt\0\u{65e5}\u{672c}.txt\0"
+    );
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("\u{e4}.txt")).unwrap(),
+        "staged"
+    );
+}
+
+#[tokio::test]
+async fn test_restore_deletions_renames_and_modes() {
+    let temp_dir = setup_git_repo();
+    let path = |name: &str| temp_dir.path().join(name);
+
+    for name in ["gone-staged.txt", "gone.txt", "old.txt", "run.sh"] {
+        fs::write(path(name), format!("{}\n", name)).unwrap();
+    }
+    git(&temp_dir, &["add", "."]);
+    git(&temp_dir, &["commit", "-m", "Add files"]);
+
+    git(&temp_dir, &["rm", "-q", "gone-staged.txt"]);
+    fs::remove_file(path("gone.txt")).unwrap();
+    git(&temp_dir, &["mv", "old.txt", "new.txt"]);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path("run.sh"), fs::Permissions::from_mode(0o755)).unwrap();
+        std::os::unix::fs::symlink("new.txt", path("link")).unwrap();
+    }
+    let status_before = git(&temp_dir, &["status", "--porcelain"]);
+
+    // Restored onto the commit it was saved on, and onto a branch that moved on
+    for moved in [false, true] {
+        wippy(
+            &temp_dir,
+            &["save", "--local", "--datetime", "2024-03-21-17-59-30"],
+        );
+        assert!(path("old.txt").exists());
+        assert!(!path("new.txt").exists());
+        if moved {
+            fs::write(path("other.txt"), "upstream").unwrap();
+            git(&temp_dir, &["add", "other.txt"]);
+            git(&temp_dir, &["commit", "-m", "Upstream change"]);
+        }
+
+        wippy(&temp_dir, &["restore", "wip/test.user/2024-03-21-17-59-30"]);
+        assert_eq!(git(&temp_dir, &["status", "--porcelain"]), status_before);
+        assert!(!path("gone.txt").exists());
+        assert!(!path("old.txt").exists());
+        assert_eq!(fs::read_to_string(path("new.txt")).unwrap(), "old.txt\n");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(path("run.sh")).unwrap().permissions().mode();
+            assert_eq!(mode & 0o111, 0o111);
+            assert_eq!(
+                fs::read_link(path("link")).unwrap(),
+                std::path::Path::new("new.txt")
+            );
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_restore_many_files_with_unusual_names() {
+    let temp_dir = setup_git_repo();
+
+    // Names that are pathspec patterns or magic must be taken literally
+    let mut names: Vec<String> = (0..200).map(|i| format!("file {}.txt", i)).collect();
+    names.extend(["*.txt", ":(top)x", "-dash"].map(String::from));
+    for name in &names {
+        fs::write(temp_dir.path().join(name), "content").unwrap();
+    }
+    git(&temp_dir, &["add", "--", "."]);
+    git(&temp_dir, &["commit", "-m", "Add files"]);
+    for (i, name) in names.iter().enumerate() {
+        fs::write(temp_dir.path().join(name), "changed").unwrap();
+        if i % 2 == 0 {
+            git(&temp_dir, &["--literal-pathspecs", "add", "--", name]);
+        }
+    }
+    let status_before = git(&temp_dir, &["status", "--porcelain"]);
+
+    // Staging is recreated from the file lists when the branch moved on
+    wippy(&temp_dir, &["save", "--local"]);
+    fs::write(temp_dir.path().join("other.txt"), "upstream").unwrap();
+    git(&temp_dir, &["add", "other.txt"]);
+    git(&temp_dir, &["commit", "-m", "Upstream change"]);
+    wippy(&temp_dir, &["restore"]);
+
+    assert_eq!(git(&temp_dir, &["status", "--porcelain"]), status_before);
+}
+
+#[tokio::test]
+async fn test_restore_rolls_back_on_failure() {
+    for locale in ["en", "fr", "de"] {
+        let temp_dir = setup_git_repo();
+
+        fs::write(temp_dir.path().join("test.txt"), "wip content").unwrap();
+        fs::write(temp_dir.path().join("local.txt"), "wip untracked").unwrap();
+        let mut cmd = Command::cargo_bin("git-wippy").unwrap();
+        cmd.current_dir(&temp_dir)
+            .env("LANG", locale)
+            .args(["save", "--local"])
+            .assert()
+            .success();
+        let branch_name = get_wip_branch_name(&temp_dir);
+
+        // The WIP brings back a file the autostash also holds, so the stash can't
+        // be reapplied on top of it
+        fs::write(temp_dir.path().join("local.txt"), "local untracked").unwrap();
+        let status_before = git(&temp_dir, &["status", "--porcelain"]);
+
+        let mut cmd = Command::cargo_bin("git-wippy").unwrap();
+        cmd.current_dir(&temp_dir)
+            .env("LANG", locale)
+            .args(["restore", "--autostash", &branch_name])
+            .assert()
+            .failure()
             .stdout(function(|output: &str| {
                 normalize_text(output).contains(&normalize_text(&t_with_args(
-                    "pushed-changes",
+                    "rollback-complete",
                     &[],
                     locale,
                 )))
             }));
 
-        // Verify branch exists on remote
-        let branch_name = get_wip_branch_name(&local_dir);
-        Command::new("git")
-            .current_dir(&local_dir)
-            .args(&["ls-remote", "--heads", "origin", &branch_name])
-            .assert()
-            .success()
-            .stdout(predicates::str::contains(&branch_name));
+        // Everything is back: local changes, branch, WIP branch, no stash
+        assert_eq!(git(&temp_dir, &["status", "--porcelain"]), status_before);
+        assert_eq!(
+            git(&temp_dir, &["rev-parse", "--abbrev-ref", "HEAD"]),
+            "main"
+        );
+        assert_eq!(get_wip_branch_name(&temp_dir), branch_name);
+        assert!(git(&temp_dir, &["stash", "list"]).is_empty());
+        assert!(!temp_dir.path().join(".git/wippy-journal.json").exists());
     }
 }
 
 #[tokio::test]
-async fn test_save_without_remote() {
+async fn test_recover_interrupted_run() {
     for locale in ["en", "fr", "de"] {
         let temp_dir = setup_git_repo();
 
-        // Create a change
-        fs::write(temp_dir.path().join("test.txt"), "modified content").unwrap();
+        // A save that was killed right after creating its branch
+        git(&temp_dir, &["branch", "wip/test.user/2024-03-21-17-59-30"]);
+        git(&temp_dir, &["branch", "git-wippy-temp-main"]);
+        fs::write(
+            temp_dir.path().join(".git/wippy-journal.json"),
+            r#"{"command": "save", "steps": [
+                {"step": "created-branch", "branch": "wip/test.user/2024-03-21-17-59-30"}
+            ]}"#,
+        )
+        .unwrap();
 
-        // Test save command without remote
+        // Nothing else runs until the interrupted save is dealt with
         let mut cmd = Command::cargo_bin("git-wippy").unwrap();
         cmd.current_dir(&temp_dir)
             .env("LANG", locale)
-            .arg("save")
+            .args(["save", "--local"])
+            .assert()
+            .failure()
+            .stderr(predicates::str::contains("git wippy recover"));
+
+        let mut cmd = Command::cargo_bin("git-wippy").unwrap();
+        cmd.current_dir(&temp_dir)
+            .env("LANG", locale)
+            .arg("recover")
             .assert()
             .success()
             .stdout(function(|output: &str| {
                 normalize_text(output).contains(&normalize_text(&t_with_args(
-                    "saving-wip",
-                    &[],
+                    "rolled-back-created-branch",
+                    &[("name", "wip/test.user/2024-03-21-17-59-30")],
                     locale,
                 )))
-            }))
+            }));
+
+        assert_eq!(
+            git(&temp_dir, &["branch", "--format=%(refname:short)"]),
+            "main"
+        );
+        assert!(!temp_dir.path().join(".git/wippy-journal.json").exists());
+
+        let mut cmd = Command::cargo_bin("git-wippy").unwrap();
+        cmd.current_dir(&temp_dir)
+            .env("LANG", locale)
+            .arg("recover")
+            .assert()
+            .success()
             .stdout(function(|output: &str| {
                 normalize_text(output).contains(&normalize_text(&t_with_args(
-                    "skipped-push-no-remote",
+                    "nothing-to-recover",
+                    &[],
+                    locale,
+                )))
+            }));
+    }
+}
+
+#[tokio::test]
+async fn test_save_with_remote() {
+    for locale in ["en", "fr", "de"] {
+        let (local_dir, _remote_dir) = setup_git_repo_with_remote();
+
+        // Create a change
+        fs::write(local_dir.path().join("test.txt"), "modified content").unwrap();
+
+        // Test save command with remote
+        let mut cmd = Command::cargo_bin("git-wippy").unwrap();
+        cmd.current_dir(&local_dir)
+            .env("LANG", locale)
+            .arg("save")
+            .assert()
+            .success()
+            .stdout(function(|output: &str| {
+                normalize_text(output).contains(&normalize_text(&t_with_args(
+                    "saving-wip",
+                    &[],
+                    locale,
+                )))
+            }))
+            .stdout(function(|output: &str| {
+                normalize_text(output).contains(&normalize_text(&t_with_args(
+                    "pushed-changes",
+                    &[],
+                    locale,
+                )))
+            }));
+
+        // Verify branch exists on remote
+        let branch_name = get_wip_branch_name(&local_dir);
+        Command::new("git")
+            .current_dir(&local_dir)
+            .args(["ls-remote", "--heads", "origin", &branch_name])
+            .assert()
+            .success()
+            .stdout(predicates::str::contains(&branch_name));
+    }
+}
+
+#[tokio::test]
+async fn test_configured_remote() {
+    let (local_dir, _remote_dir) = setup_git_repo_with_remote();
+    let fork_dir = tempfile::TempDir::new().unwrap();
+
+    Command::new("git")
+        .current_dir(&fork_dir)
+        .args(["init", "--bare"])
+        .assert()
+        .success();
+    git(
+        &local_dir,
+        &["remote", "add", "me", fork_dir.path().to_str().unwrap()],
+    );
+    git(&local_dir, &["config", "wippy.remote", "me"]);
+
+    // The WIP goes to the configured remote only
+    fs::write(local_dir.path().join("test.txt"), "modified content").unwrap();
+    wippy(&local_dir, &["save"]);
+    let branch_name = get_wip_branch_name(&local_dir);
+    assert!(git(&local_dir, &["ls-remote", "--heads", "me", &branch_name]).contains(&branch_name));
+    assert!(git(
+        &local_dir,
+        &["ls-remote", "--heads", "origin", &branch_name]
+    )
+    .is_empty());
+
+    // Listing finds copies on every remote, unless asked for one
+    git(&local_dir, &["push", "origin", &branch_name]);
+    let records: serde_json::Value =
+        serde_json::from_str(&stdout(&wippy(&local_dir, &["list", "--format", "json"]))).unwrap();
+    assert_eq!(records[0]["remotes"], serde_json::json!(["me", "origin"]));
+    let records: serde_json::Value = serde_json::from_str(&stdout(&wippy(
+        &local_dir,
+        &["list", "--remote", "origin", "--format", "json"],
+    )))
+    .unwrap();
+    assert_eq!(records[0]["remotes"], serde_json::json!(["origin"]));
+
+    // An explicit --remote wins over the configured one
+    wippy(&local_dir, &["restore", "--remote", "origin", &branch_name]);
+    assert!(git(
+        &local_dir,
+        &["ls-remote", "--heads", "origin", &branch_name]
+    )
+    .is_empty());
+    assert!(git(&local_dir, &["ls-remote", "--heads", "me", &branch_name]).contains(&branch_name));
+
+    // A remote that does not exist is an error
+    Command::cargo_bin("git-wippy")
+        .unwrap()
+        .current_dir(&local_dir)
+        .args(["save", "--remote", "nowhere"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("'nowhere'"));
+}
+
+#[tokio::test]
+async fn test_branch_template() {
+    let temp_dir = setup_git_repo();
+    git(&temp_dir, &["checkout", "-b", "feature/login"]);
+    git(
+        &temp_dir,
+        &[
+            "config",
+            "wippy.branchTemplate",
+            "stash/{user}/{source}/{date}-{slug}",
+        ],
+    );
+
+    fs::write(temp_dir.path().join("test.txt"), "modified content").unwrap();
+    let mut cmd = Command::cargo_bin("git-wippy").unwrap();
+    cmd.current_dir(&temp_dir)
+        .args([
+            "save",
+            "--local",
+            "--datetime",
+            "2024-03-21-17-59-30",
+            "--message",
+            "Login form",
+        ])
+        .assert()
+        .success();
+
+    let branch_name = "stash/test.user/feature/login/2024-03-21-17-59-30-login-form";
+    assert_eq!(
+        git(
+            &temp_dir,
+            &["branch", "--list", "--format=%(refname:short)", "stash/*"]
+        ),
+        branch_name
+    );
+
+    // The branch is recognised by every command
+    let output = Command::cargo_bin("git-wippy")
+        .unwrap()
+        .current_dir(&temp_dir)
+        .args(["list", "--format", "json"])
+        .output()
+        .unwrap();
+    let records: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(records[0]["branch"], branch_name);
+    assert_eq!(records[0]["user"], "test.user");
+    assert_eq!(records[0]["timestamp"], "2024-03-21-17-59-30");
+
+    git(&temp_dir, &["checkout", "main"]);
+    let mut cmd = Command::cargo_bin("git-wippy").unwrap();
+    cmd.current_dir(&temp_dir)
+        .args(["restore", branch_name])
+        .assert()
+        .success();
+    assert_eq!(
+        git(&temp_dir, &["symbolic-ref", "--short", "HEAD"]),
+        "feature/login"
+    );
+    assert!(git(&temp_dir, &["branch", "--list", "stash/*"]).is_empty());
+
+    // Templates that can't tell users apart, or WIP branches from other
+    // branches, are rejected
+    for template in ["stash/{date}", "{user}/{date}"] {
+        git(&temp_dir, &["config", "wippy.branchTemplate", template]);
+        let mut cmd = Command::cargo_bin("git-wippy").unwrap();
+        cmd.current_dir(&temp_dir)
+            .arg("list")
+            .assert()
+            .failure()
+            .stderr(predicates::str::contains("wippy.branchTemplate"));
+    }
+}
+
+#[tokio::test]
+async fn test_branches_without_wip_commit_are_left_alone() {
+    let temp_dir = setup_git_repo();
+    // Named like a WIP branch, but its commit wasn't written by `save`
+    let branch_name = "wip/test.user/2024-03-21-17-59-30";
+    git(&temp_dir, &["branch", branch_name]);
+
+    for args in [
+        vec!["delete", "--all", "--force", "--local"],
+        vec!["prune", "--older-than", "1s", "--force", "--local"],
+    ] {
+        let mut cmd = Command::cargo_bin("git-wippy").unwrap();
+        cmd.current_dir(&temp_dir).args(&args).assert().success();
+        assert_eq!(
+            git(
+                &temp_dir,
+                &["branch", "--list", "--format=%(refname:short)", "wip/*"]
+            ),
+            branch_name
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_undated_branches_from_older_versions() {
+    let temp_dir = setup_git_repo();
+
+    // Older versions accepted any --datetime, e.g. `wip/{user}/before-release`
+    fs::write(temp_dir.path().join("test.txt"), "wip content").unwrap();
+    wippy(&temp_dir, &["save", "--local"]);
+    let saved = git(
+        &temp_dir,
+        &["branch", "--list", "--format=%(refname:short)", "wip/*"],
+    );
+    let branch_name = "wip/test.user/before-release";
+    git(&temp_dir, &["branch", "-m", &saved, branch_name]);
+    // An undated branch that `save` didn't write is not a WIP branch
+    git(&temp_dir, &["branch", "wip/test.user/feature"]);
+
+    let records: serde_json::Value =
+        serde_json::from_str(&stdout(&wippy(&temp_dir, &["list", "--format", "json"]))).unwrap();
+    let records = records.as_array().unwrap();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0]["branch"], branch_name);
+    assert_eq!(records[0]["changed_files"], serde_json::json!(["test.txt"]));
+
+    wippy(&temp_dir, &["restore", branch_name]);
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("test.txt")).unwrap(),
+        "wip content"
+    );
+    assert_eq!(
+        git(
+            &temp_dir,
+            &["branch", "--list", "--format=%(refname:short)", "wip/*"]
+        ),
+        "wip/test.user/feature"
+    );
+}
+
+#[tokio::test]
+async fn test_save_sanitizes_branch_names() {
+    let temp_dir = setup_git_repo();
+    let save = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin("git-wippy").unwrap();
+        cmd.current_dir(&temp_dir)
+            .args(["save", "--local"])
+            .args(args)
+            .assert()
+    };
+    git(&temp_dir, &["config", "user.name", "Zoë O'Neil"]);
+    fs::write(temp_dir.path().join("test.txt"), "modified content").unwrap();
+
+    // Invalid parts are reported before anything changes
+    save(&["--datetime", "next tuesday"])
+        .failure()
+        .stderr(predicates::str::contains("--datetime 'next tuesday'"));
+    git(
+        &temp_dir,
+        &["config", "wippy.branchTemplate", "wip/{user}/{date}.lock"],
+    );
+    save(&[])
+        .failure()
+        .stderr(predicates::str::contains("ends with '.lock'"))
+        .stderr(predicates::str::contains("wippy.branchTemplate"));
+    assert!(git(&temp_dir, &["branch", "--list", "wip/*"]).is_empty());
+    assert_eq!(git(&temp_dir, &["status", "--porcelain"]), " M test.txt");
+
+    // The user name is made safe for the branch name
+    git(&temp_dir, &["config", "--unset", "wippy.branchTemplate"]);
+    save(&["--datetime", "2024-03-21-17-59-30"]).success();
+    assert_eq!(
+        git(
+            &temp_dir,
+            &["branch", "--list", "--format=%(refname:short)", "wip/*"]
+        ),
+        "wip/Zoë-O'Neil/2024-03-21-17-59-30"
+    );
+
+    let output = Command::cargo_bin("git-wippy")
+        .unwrap()
+        .current_dir(&temp_dir)
+        .args(["list", "--format", "json"])
+        .output()
+        .unwrap();
+    let records: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(records[0]["user"], "Zoë-O'Neil");
+
+    // Names git accepts are kept as they are, as older versions saved them
+    git(&temp_dir, &["config", "user.name", "J.R."]);
+    fs::write(temp_dir.path().join("test.txt"), "more content").unwrap();
+    save(&["--datetime", "2024-03-21-18-00-00"]).success();
+    assert!(stdout(&wippy(&temp_dir, &["list"])).contains("wip/J.R./2024-03-21-18-00-00"));
+}
+
+#[tokio::test]
+async fn test_save_without_remote() {
+    for locale in ["en", "fr", "de"] {
+        let temp_dir = setup_git_repo();
+
+        // Create a change
+        fs::write(temp_dir.path().join("test.txt"), "modified content").unwrap();
+
+        // Test save command without remote
+        let mut cmd = Command::cargo_bin("git-wippy").unwrap();
+        cmd.current_dir(&temp_dir)
+            .env("LANG", locale)
+            .arg("save")
+            .assert()
+            .success()
+            .stdout(function(|output: &str| {
+                normalize_text(output).contains(&normalize_text(&t_with_args(
+                    "saving-wip",
+                    &[],
+                    locale,
+                )))
+            }))
+            .stdout(function(|output: &str| {
+                normalize_text(output).contains(&normalize_text(&t_with_args(
+                    "skipped-push-no-remote",
                     &[],
                     locale,
                 )))
@@ -696,9 +1840,706 @@ async fn test_save_without_remote() {
         let branch_name = get_wip_branch_name(&temp_dir);
         Command::new("git")
             .current_dir(&temp_dir)
-            .args(&["branch", "--list", &branch_name])
+            .args(["branch", "--list", &branch_name])
             .assert()
             .success()
             .stdout(predicates::str::contains(&branch_name));
     }
 }
+
+#[tokio::test]
+async fn test_save_keep_leaves_working_tree_alone() {
+    for locale in ["en", "fr", "de"] {
+        let temp_dir = setup_git_repo();
+
+        // Hooks must not get a say, nothing is committed the porcelain way
+        let hook = temp_dir.path().join(".git/hooks/pre-commit");
+        fs::write(&hook, "#!/bin/sh\nexit 1\n").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&hook, fs::Permissions::from_mode(0o755)).unwrap();
+        }
+
+        fs::write(temp_dir.path().join("test.txt"), "staged content").unwrap();
+        git(&temp_dir, &["add", "test.txt"]);
+        fs::write(temp_dir.path().join("test.txt"), "unstaged content").unwrap();
+        fs::create_dir(temp_dir.path().join("sub")).unwrap();
+        fs::write(temp_dir.path().join("sub/new.txt"), "new untracked").unwrap();
+        let status_before = git(&temp_dir, &["status", "--porcelain"]);
+
+        // Run from a subdirectory, the whole working tree is saved regardless
+        let mut cmd = Command::cargo_bin("git-wippy").unwrap();
+        cmd.current_dir(temp_dir.path().join("sub"))
+            .env("LANG", locale)
+            .args(["save", "--local", "--keep"])
+            .assert()
+            .success()
+            .stdout(function(|output: &str| {
+                normalize_text(output).contains(&normalize_text(&t_with_args(
+                    "kept-changes",
+                    &[],
+                    locale,
+                )))
+            }));
+
+        assert_eq!(git(&temp_dir, &["status", "--porcelain"]), status_before);
+        assert_eq!(
+            git(&temp_dir, &["rev-parse", "--abbrev-ref", "HEAD"]),
+            "main"
+        );
+
+        let branch_name = get_wip_branch_name(&temp_dir);
+        assert_eq!(
+            git(&temp_dir, &["show", &format!("{}:test.txt", branch_name)]),
+            "unstaged content"
+        );
+        assert_eq!(
+            git(&temp_dir, &["show", &format!("{}^:test.txt", branch_name)]),
+            "staged content"
+        );
+        assert_eq!(
+            git(
+                &temp_dir,
+                &["show", &format!("{}:sub/new.txt", branch_name)]
+            ),
+            "new untracked"
+        );
+
+        // Without --keep the saved changes are cleared, untracked directories included
+        let mut cmd = Command::cargo_bin("git-wippy").unwrap();
+        cmd.current_dir(&temp_dir)
+            .env("LANG", locale)
+            .args(["save", "--local", "--datetime", "2024-03-21-17-59-30"])
+            .assert()
+            .success();
+        assert!(git(&temp_dir, &["status", "--porcelain"]).is_empty());
+        assert!(!temp_dir.path().join("sub").exists());
+    }
+}
+
+#[tokio::test]
+async fn test_show_wip() {
+    for locale in ["en", "fr", "de"] {
+        let temp_dir = setup_git_repo();
+
+        fs::write(temp_dir.path().join("test.txt"), "staged content").unwrap();
+        git(&temp_dir, &["add", "test.txt"]);
+        fs::write(temp_dir.path().join("test.txt"), "unstaged content").unwrap();
+        fs::write(temp_dir.path().join("new.txt"), "new untracked").unwrap();
+
+        let mut cmd = Command::cargo_bin("git-wippy").unwrap();
+        cmd.current_dir(&temp_dir)
+            .args(["save", "--local", "--message", "Login form"])
+            .assert()
+            .success();
+        let branch_name = get_wip_branch_name(&temp_dir);
+
+        let mut cmd = Command::cargo_bin("git-wippy").unwrap();
+        cmd.current_dir(&temp_dir)
+            .env("LANG", locale)
+            .env("GIT_PAGER", "cat")
+            .args(["show", &branch_name])
+            .assert()
+            .success()
+            .stdout(function(|output: &str| {
+                normalize_text(output).contains(&normalize_text(&t_with_args(
+                    "show-staged-files",
+                    &[],
+                    locale,
+                )))
+            }))
+            .stdout(function(|output: &str| {
+                normalize_text(output).contains(&normalize_text(&t_with_args(
+                    "show-untracked-files",
+                    &[],
+                    locale,
+                )))
+            }))
+            .stdout(predicates::str::contains("Login form"))
+            .stdout(predicates::str::contains("new.txt"))
+            .stdout(predicates::str::contains("2 files changed"));
+
+        let mut cmd = Command::cargo_bin("git-wippy").unwrap();
+        let output = cmd
+            .current_dir(&temp_dir)
+            .env("LANG", locale)
+            .args(["--format", "json", "show", "--patch", &branch_name])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let show: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(show["branch"], branch_name.as_str());
+        assert_eq!(show["source_branch"], "main");
+        assert_eq!(show["message"], "Login form");
+        assert_eq!(show["untracked_files"][0], "new.txt");
+        let diff = show["diff"].as_str().unwrap();
+        assert!(diff.contains("diff --git a/test.txt b/test.txt"));
+        assert!(diff.contains("+unstaged content"));
+
+        // Showing leaves the WIP branch and the working tree alone
+        assert_eq!(get_wip_branch_name(&temp_dir), branch_name);
+        assert!(!temp_dir.path().join("new.txt").exists());
+    }
+}
+
+#[tokio::test]
+async fn test_diff_wip() {
+    for locale in ["en", "fr", "de"] {
+        let temp_dir = setup_git_repo();
+
+        fs::write(temp_dir.path().join("test.txt"), "first save").unwrap();
+        wippy_in_locale(
+            &temp_dir,
+            locale,
+            &[
+                "save",
+                "--local",
+                "--keep",
+                "--datetime",
+                "2024-03-21-17-59-30",
+            ],
+        );
+        fs::write(temp_dir.path().join("test.txt"), "second save").unwrap();
+        fs::write(temp_dir.path().join("new.txt"), "new untracked").unwrap();
+        wippy_in_locale(
+            &temp_dir,
+            locale,
+            &[
+                "save",
+                "--local",
+                "--keep",
+                "--datetime",
+                "2024-03-22-09-00-00",
+            ],
+        );
+        let first = "wip/test.user/2024-03-21-17-59-30";
+        let second = "wip/test.user/2024-03-22-09-00-00";
+
+        // The working tree moved on from the first save, untracked files included
+        fs::write(temp_dir.path().join("later.txt"), "after saving").unwrap();
+        let output = stdout(&wippy_in_locale(&temp_dir, locale, &["diff", first]));
+        assert!(
+            normalize_text(&output).contains(&normalize_text(&t_with_args(
+                "diff-state-untracked",
+                &[],
+                locale,
+            )))
+        );
+        assert!(output.contains("-first save"));
+        assert!(output.contains("+second save"));
+        assert!(output.contains("+after saving"));
+
+        // Two saved WIPs are compared without looking at the working tree
+        let diff: serde_json::Value = serde_json::from_str(&stdout(&wippy_in_locale(
+            &temp_dir,
+            locale,
+            &["--format", "json", "diff", "--stat", first, second],
+        )))
+        .unwrap();
+        assert_eq!(diff["from"], first);
+        assert_eq!(diff["to"], second);
+        assert_eq!(
+            diff["files"],
+            serde_json::json!([
+                {"path": "new.txt", "from": [], "to": ["untracked"]},
+                {"path": "test.txt", "from": ["changed"], "to": ["changed"]},
+            ])
+        );
+        let stat = diff["diff"].as_str().unwrap();
+        assert!(stat.contains("2 files changed"));
+        assert!(!stat.contains("later.txt"));
+
+        // Comparing leaves the index alone
+        let status = Command::new("git")
+            .current_dir(&temp_dir)
+            .args(["diff", "--cached", "--name-only"])
+            .output()
+            .unwrap();
+        assert!(status.stdout.is_empty());
+    }
+}
+
+#[tokio::test]
+async fn test_save_with_message() {
+    let temp_dir = setup_git_repo();
+
+    // Create a change
+    fs::write(temp_dir.path().join("test.txt"), "modified content").unwrap();
+
+    // Save with a message
+    let mut cmd = Command::cargo_bin("git-wippy").unwrap();
+    cmd.current_dir(&temp_dir)
+        .arg("save")
+        .arg("--local")
+        .arg("-m")
+        .arg("Feature work in progress")
+        .assert()
+        .success();
+
+    // The message is stored in the WIP commit
+    let branch_name = get_wip_branch_name(&temp_dir);
+    Command::new("git")
+        .current_dir(&temp_dir)
+        .args(["log", "-1", "--pretty=%B", &branch_name])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains(
+            "Message: Feature work in progress",
+        ));
+
+    // The message is shown next to the branch in the list
+    let mut cmd = Command::cargo_bin("git-wippy").unwrap();
+    cmd.current_dir(&temp_dir)
+        .arg("list")
+        .assert()
+        .success()
+        .stdout(function(|output: &str| {
+            normalize_text(output).lines().any(|line| {
+                line.contains(&branch_name) && line.ends_with("Feature work in progress")
+            })
+        }));
+}
+
+#[tokio::test]
+async fn test_save_update_log_and_restore_snapshot() {
+    for locale in ["en", "fr", "de"] {
+        let temp_dir = setup_git_repo();
+        let read = || fs::read_to_string(temp_dir.path().join("test.txt")).unwrap();
+
+        // One WIP branch collects every snapshot
+        fs::write(temp_dir.path().join("test.txt"), "one").unwrap();
+        wippy_in_locale(&temp_dir, locale, &["save", "--local", "-m", "Login form"]);
+        let branch_name = get_wip_branch_name(&temp_dir);
+        for content in ["two", "three"] {
+            fs::write(temp_dir.path().join("test.txt"), content).unwrap();
+            wippy_in_locale(&temp_dir, locale, &["save", "--local", "--update"]).stdout(function(
+                |output: &str| {
+                    normalize_text(output).contains(&normalize_text(&t_with_args(
+                        "wip-snapshot-added",
+                        &[("name", &branch_name)],
+                        locale,
+                    )))
+                },
+            ));
+        }
+        let branches = Command::new("git")
+            .current_dir(&temp_dir)
+            .args(["branch", "--list", "wip/*"])
+            .output()
+            .unwrap();
+        assert_eq!(String::from_utf8_lossy(&branches.stdout).lines().count(), 1);
+
+        let output = Command::cargo_bin("git-wippy")
+            .unwrap()
+            .current_dir(&temp_dir)
+            .args(["--format", "json", "log", &branch_name])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let result: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        let snapshots = result["snapshots"].as_array().unwrap();
+        assert_eq!(snapshots.len(), 3);
+        assert_eq!(snapshots[1]["snapshot"], format!("{}@{{1}}", branch_name));
+        // The message carries over to the snapshots
+        assert!(snapshots
+            .iter()
+            .all(|snapshot| snapshot["message"] == "Login form"));
+
+        // An older snapshot is restored without losing the newer ones
+        wippy_in_locale(
+            &temp_dir,
+            locale,
+            &["restore", &format!("{}@{{1}}", branch_name)],
+        );
+        assert_eq!(read(), "two");
+        assert_eq!(get_wip_branch_name(&temp_dir), branch_name);
+
+        Command::new("git")
+            .current_dir(&temp_dir)
+            .args(["checkout", "--", "test.txt"])
+            .assert()
+            .success();
+        wippy_in_locale(&temp_dir, locale, &["restore", &branch_name]);
+        assert_eq!(read(), "three");
+
+        // A snapshot that doesn't exist is reported
+        fs::write(temp_dir.path().join("test.txt"), "four").unwrap();
+        wippy_in_locale(&temp_dir, locale, &["save", "--local"]);
+        let branch_name = get_wip_branch_name(&temp_dir);
+        Command::cargo_bin("git-wippy")
+            .unwrap()
+            .current_dir(&temp_dir)
+            .args(["restore", &format!("{}@{{1}}", branch_name)])
+            .assert()
+            .failure()
+            .stderr(predicates::str::contains("it only has 1"));
+    }
+}
+
+#[test]
+#[cfg(unix)]
+fn test_watch() {
+    let temp_dir = setup_git_repo();
+    // The content of test.txt in each snapshot of the WIP branch, newest first
+    let snapshots = || -> Vec<String> {
+        let branch = git(
+            &temp_dir,
+            &[
+                "for-each-ref",
+                "--format=%(refname:short)",
+                "refs/heads/wip/",
+            ],
+        );
+        if branch.is_empty() {
+            return Vec::new();
+        }
+        let output = Command::cargo_bin("git-wippy")
+            .unwrap()
+            .current_dir(&temp_dir)
+            .args(["--format", "json", "log", &branch])
+            .output()
+            .unwrap();
+        let result: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        result["snapshots"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|snapshot| {
+                let commit = snapshot["commit"].as_str().unwrap();
+                git(&temp_dir, &["show", &format!("{}:test.txt", commit)])
+            })
+            .collect()
+    };
+    // Waits for the watch to have saved, and trimmed, exactly these snapshots
+    let wait_for_snapshots = |expected: &[&str]| {
+        for _ in 0..200 {
+            if snapshots() == expected {
+                return;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(100));
+        }
+        panic!("expected snapshots {:?}, found {:?}", expected, snapshots());
+    };
+    // Replaces the file in one go, so a snapshot never sees it half written
+    let write = |content: &str| {
+        let temporary = temp_dir.path().join("test.txt.tmp");
+        fs::write(&temporary, content).unwrap();
+        fs::rename(&temporary, temp_dir.path().join("test.txt")).unwrap();
+    };
+
+    write("one");
+    fs::write(temp_dir.path().join("staged.txt"), "staged").unwrap();
+    git(&temp_dir, &["add", "staged.txt"]);
+    let head = git(&temp_dir, &["rev-parse", "HEAD"]);
+
+    // There is always at least one snapshot to keep
+    let mut cmd = Command::cargo_bin("git-wippy").unwrap();
+    cmd.current_dir(&temp_dir)
+        .args(["watch", "--keep-last", "0"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("--keep-last"));
+
+    let watch = std::process::Command::new(assert_cmd::cargo::cargo_bin("git-wippy"))
+        .current_dir(&temp_dir)
+        .env("LANG", "en")
+        .args(["watch", "--interval", "1s", "--keep-last", "2"])
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    wait_for_snapshots(&["one"]);
+    write("two");
+    wait_for_snapshots(&["two", "one"]);
+    // Only the newest snapshots are kept
+    write("three");
+    wait_for_snapshots(&["three", "two"]);
+    let tip = git(
+        &temp_dir,
+        &["for-each-ref", "--format=%(objectname)", "refs/heads/wip/"],
+    );
+
+    Command::new("kill")
+        .arg(watch.id().to_string())
+        .assert()
+        .success();
+    let output = watch.wait_with_output().unwrap();
+    assert!(output.status.success());
+    let stdout = normalize_text(&String::from_utf8_lossy(&output.stdout));
+    let branch_name = get_wip_branch_name(&temp_dir);
+    assert!(stdout.contains(&normalize_text(&t_with_args(
+        "watch-snapshot",
+        &[("name", &branch_name)],
+        "en"
+    ))));
+
+    // Nothing changed after the last snapshot, so nothing more was saved
+    assert_eq!(
+        git(
+            &temp_dir,
+            &["for-each-ref", "--format=%(objectname)", "refs/heads/wip/"]
+        ),
+        tip
+    );
+    assert_eq!(snapshots(), ["three", "two"]);
+
+    // HEAD, the index and the working tree are left alone
+    assert_eq!(git(&temp_dir, &["rev-parse", "HEAD"]), head);
+    assert_eq!(
+        git(&temp_dir, &["diff", "--cached", "--name-only"]),
+        "staged.txt"
+    );
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("test.txt")).unwrap(),
+        "three"
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn test_watch_messages() {
+    use std::io::BufRead;
+
+    for locale in ["en", "fr", "de"] {
+        let temp_dir = setup_git_repo();
+        let mut watch = std::process::Command::new(assert_cmd::cargo::cargo_bin("git-wippy"))
+            .current_dir(&temp_dir)
+            .env("LANG", locale)
+            .args(["watch", "--interval", "1h"])
+            .stdout(std::process::Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        // Stop the watch once it has started
+        let mut started = String::new();
+        std::io::BufReader::new(watch.stdout.as_mut().unwrap())
+            .read_line(&mut started)
+            .unwrap();
+        assert!(
+            normalize_text(&started).contains(&normalize_text(&t_with_args(
+                "watch-started",
+                &[("interval", "1h")],
+                locale
+            )))
+        );
+        Command::new("kill")
+            .arg(watch.id().to_string())
+            .assert()
+            .success();
+
+        let output = watch.wait_with_output().unwrap();
+        assert!(output.status.success());
+        let stdout = normalize_text(&String::from_utf8_lossy(&output.stdout));
+        assert!(stdout.contains(&normalize_text(&t_with_args("watch-stopped", &[], locale))));
+    }
+}
+
+#[tokio::test]
+async fn test_list_all_users() {
+    for locale in ["en", "fr", "de"] {
+        let (local_dir, _remote_dir) = setup_git_repo_with_remote();
+
+        // Save a WIP for the configured user and push it
+        fs::write(local_dir.path().join("test.txt"), "first change").unwrap();
+        let mut cmd = Command::cargo_bin("git-wippy").unwrap();
+        cmd.current_dir(&local_dir)
+            .env("LANG", locale)
+            .arg("save")
+            .assert()
+            .success();
+
+        // Keep only the remote copy of it
+        let pushed_branch = get_wip_branch_name(&local_dir);
+        Command::new("git")
+            .current_dir(&local_dir)
+            .args(["branch", "-D", &pushed_branch])
+            .assert()
+            .success();
+
+        // Save a local WIP for another user
+        fs::write(local_dir.path().join("test.txt"), "second change").unwrap();
+        let mut cmd = Command::cargo_bin("git-wippy").unwrap();
+        cmd.current_dir(&local_dir)
+            .env("LANG", locale)
+            .arg("save")
+            .arg("--local")
+            .arg("--username")
+            .arg("other.user")
+            .arg("--datetime")
+            .arg("2024-03-21-17-59-30")
+            .assert()
+            .success();
+
+        let mut cmd = Command::cargo_bin("git-wippy").unwrap();
+        cmd.current_dir(&local_dir)
+            .env("LANG", locale)
+            .arg("list")
+            .arg("--all")
+            .assert()
+            .success()
+            .stdout(function(|output: &str| {
+                normalize_text(output).contains(&normalize_text(&t_with_args(
+                    "found-wip-branches-all",
+                    &[],
+                    locale,
+                )))
+            }))
+            .stdout(function(|output: &str| {
+                let normalized = normalize_text(output);
+                let heading = |username: &str| {
+                    normalize_text(&t_with_args(
+                        "wip-user-heading",
+                        &[("username", username)],
+                        locale,
+                    ))
+                };
+                match (
+                    normalized.find(&heading("other.user")),
+                    normalized.find(&heading("test.user")),
+                ) {
+                    (Some(other), Some(test)) => other < test,
+                    _ => false,
+                }
+            }))
+            .stdout(function(|output: &str| {
+                // The pushed branch only exists on the remote now
+                normalize_text(output).lines().any(|line| {
+                    line.contains(&pushed_branch)
+                        && line.contains(" main ")
+                        && line.trim_end().ends_with("origin")
+                        && !line.contains(&t_with_args("location-local", &[], locale))
+                })
+            }))
+            .stdout(function(|output: &str| {
+                normalize_text(output).lines().any(|line| {
+                    line.contains("wip/other.user/2024-03-21-17-59-30")
+                        && line.contains(" main ")
+                        && line
+                            .trim_end()
+                            .ends_with(&t_with_args("location-local", &[], locale))
+                })
+            }));
+    }
+}
+
+#[tokio::test]
+async fn test_json_output() {
+    let temp_dir = setup_git_repo();
+
+    // Save a WIP with a modified and an untracked file
+    fs::write(temp_dir.path().join("test.txt"), "modified content").unwrap();
+    fs::write(temp_dir.path().join("new.txt"), "new content").unwrap();
+    let output = Command::cargo_bin("git-wippy")
+        .unwrap()
+        .current_dir(&temp_dir)
+        .args(["save", "--local", "-m", "Scripted", "--format", "json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let saved: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let branch_name = get_wip_branch_name(&temp_dir);
+    assert_eq!(saved["branch"], branch_name.as_str());
+    assert_eq!(saved["source_branch"], "main");
+    assert_eq!(saved["message"], "Scripted");
+    assert!(saved["pushed_to"].is_null());
+
+    // List emits an array of WIP records
+    let output = Command::cargo_bin("git-wippy")
+        .unwrap()
+        .current_dir(&temp_dir)
+        .args(["--format", "json", "list"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let records: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let records = records.as_array().unwrap();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0]["branch"], branch_name.as_str());
+    assert_eq!(records[0]["user"], "test.user");
+    assert_eq!(records[0]["source_branch"], "main");
+    assert_eq!(records[0]["changed_files"], serde_json::json!(["test.txt"]));
+    assert_eq!(
+        records[0]["untracked_files"],
+        serde_json::json!(["new.txt"])
+    );
+    assert_eq!(records[0]["local"], true);
+
+    // Errors are reported as JSON too
+    fs::write(temp_dir.path().join("test.txt"), "local changes").unwrap();
+    let output = Command::cargo_bin("git-wippy")
+        .unwrap()
+        .current_dir(&temp_dir)
+        .args(["restore", "-y", &branch_name, "--format", "json"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let error: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(error["error"]["message"]
+        .as_str()
+        .unwrap()
+        .contains("--autostash"));
+
+    // Delete reports what was removed
+    let output = Command::cargo_bin("git-wippy")
+        .unwrap()
+        .current_dir(&temp_dir)
+        .args([
+            "delete",
+            "--force",
+            "--local",
+            &branch_name,
+            "--format",
+            "json",
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let deleted: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(deleted["deleted"][0]["branch"], branch_name.as_str());
+    assert_eq!(deleted["deleted"][0]["local"], true);
+}
+
+#[tokio::test]
+async fn test_json_output_never_prompts() {
+    let temp_dir = setup_git_repo();
+    for date in ["2020-01-01-00-00-00", "2020-01-02-00-00-00"] {
+        fs::write(temp_dir.path().join("test.txt"), date).unwrap();
+        Command::cargo_bin("git-wippy")
+            .unwrap()
+            .current_dir(&temp_dir)
+            .args(["save", "--local", "--datetime", date])
+            .assert()
+            .success();
+    }
+
+    // Commands that would ask which branch, or for a confirmation, fail instead
+    for (args, hint) in [
+        (vec!["restore"], "name of the WIP branch"),
+        (vec!["show"], "name of the WIP branch"),
+        (vec!["delete", "--local"], "--all"),
+        (vec!["delete", "--all", "--local"], "--force"),
+        (vec!["prune", "--older-than", "1d", "--local"], "--force"),
+    ] {
+        let output = Command::cargo_bin("git-wippy")
+            .unwrap()
+            .current_dir(&temp_dir)
+            .args(["--format", "json"])
+            .args(&args)
+            .timeout(std::time::Duration::from_secs(30))
+            .output()
+            .unwrap();
+        assert!(!output.status.success(), "{:?}", args);
+        let error: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        let message = error["error"]["message"].as_str().unwrap();
+        assert!(message.contains("--format json"), "{}", message);
+        assert!(message.contains(hint), "{}", message);
+    }
+    // Nothing was deleted
+    let output = Command::new("git")
+        .current_dir(&temp_dir)
+        .args(["branch", "--list", "wip/*"])
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout).lines().count(), 2);
+}
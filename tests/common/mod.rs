@@ -1,13 +1,15 @@
+use assert_cmd::assert::Assert;
 use assert_cmd::Command;
 use fluent::{FluentArgs, FluentBundle, FluentResource, FluentValue};
 use std::fs;
+use std::path::Path;
 use tempfile::TempDir;
 use unic_langid::LanguageIdentifier;
 
 pub fn setup_git_repo() -> TempDir {
     let temp_dir = TempDir::new().unwrap();
     Command::new("git")
-        .args(&["init"])
+        .args(&["init", "--initial-branch=main"])
         .current_dir(&temp_dir)
         .assert()
         .success();
@@ -45,6 +47,42 @@ pub fn setup_git_repo() -> TempDir {
     temp_dir
 }
 
+/// Runs git in `dir`, asserts that it succeeded and returns its output without
+/// the trailing newline
+pub fn git(dir: impl AsRef<Path>, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "git {:?}: {:?}", args, output);
+    String::from_utf8_lossy(&output.stdout)
+        .trim_end()
+        .to_string()
+}
+
+/// Runs git-wippy in `dir` and asserts that it succeeded
+pub fn wippy(dir: impl AsRef<Path>, args: &[&str]) -> Assert {
+    wippy_in_locale(dir, "en", args)
+}
+
+/// Runs git-wippy in `dir` with its messages in `locale` and asserts that it succeeded
+pub fn wippy_in_locale(dir: impl AsRef<Path>, locale: &str, args: &[&str]) -> Assert {
+    Command::cargo_bin("git-wippy")
+        .unwrap()
+        .current_dir(dir)
+        .env("LANG", locale)
+        .env("GIT_PAGER", "cat")
+        .args(args)
+        .assert()
+        .success()
+}
+
+/// The standard output of a command as text
+pub fn stdout(assert: &Assert) -> String {
+    String::from_utf8_lossy(&assert.get_output().stdout).to_string()
+}
+
 pub fn get_wip_branch_name(temp_dir: &TempDir) -> String {
     let output = Command::new("git")
         .args(&["branch", "--list", "wip/test.user/*"])